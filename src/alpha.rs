@@ -0,0 +1,137 @@
+use crate::buffer::Sample;
+
+/// Scales the colour channels of an RGBA buffer by their alpha.
+pub fn premultiply<S: Sample>(data: &mut [S]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let alpha = pixel[3].to_unit();
+        for value in &mut pixel[..3] {
+            *value = S::from_unit(value.to_unit() * alpha);
+        }
+    }
+}
+
+/// Turns a premultiplied buffer, filtered together with its alpha, back
+/// into straight colour. `alpha` holds the filtered alpha in its red
+/// channel and `original` the buffer before filtering. The filtered alpha
+/// is kept when `filter_alpha` is set; otherwise the original alpha is
+/// restored and the filter only spreads colour weighted by coverage.
+/// Colour is clamped to the coverage before dividing, so filters that
+/// overshoot cannot produce values outside the sample range.
+pub fn unpremultiply<S: Sample>(data: &mut [S], alpha: &[S], original: &[S], filter_alpha: bool) {
+    for ((pixel, alpha), original) in data
+        .chunks_exact_mut(4)
+        .zip(alpha.chunks_exact(4))
+        .zip(original.chunks_exact(4))
+    {
+        let coverage = alpha[0].to_unit();
+        for (value, original) in pixel[..3].iter_mut().zip(original) {
+            // With no coverage left to divide by, keep the pixel's colour.
+            *value = if coverage > 0.0 {
+                S::from_unit(value.to_unit().clamp(0.0, coverage) / coverage)
+            } else {
+                *original
+            };
+        }
+
+        pixel[3] = if filter_alpha { alpha[0] } else { original[3] };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const CLEAR: [f32; 4] = [0.0, 0.0, 0.0, 0.0];
+
+    /// A row of opaque red pixels followed by transparent black ones.
+    fn edge() -> Vec<f32> {
+        [RED, RED, RED, CLEAR, CLEAR, CLEAR].concat()
+    }
+
+    /// Averages every sample with its horizontal neighbours, repeating the
+    /// samples at either end.
+    fn box_blur(data: &mut [f32]) {
+        let source = data.to_vec();
+        let pixels = source.len() / 4;
+        for (i, value) in data.iter_mut().enumerate() {
+            let (x, channel) = (i / 4, i % 4);
+            let left = source[x.saturating_sub(1) * 4 + channel];
+            let right = source[(x + 1).min(pixels - 1) * 4 + channel];
+            *value = (left + source[i] + right) / 3.0;
+        }
+    }
+
+    /// Copies each pixel's alpha into all four of its samples.
+    fn alpha_of(data: &[f32]) -> Vec<f32> {
+        data.chunks_exact(4)
+            .flat_map(|pixel| [pixel[3]; 4])
+            .collect()
+    }
+
+    /// Blurs `data` the way the app filters transparent images.
+    fn blur_premultiplied(data: &[f32], filter_alpha: bool) -> Vec<f32> {
+        let mut filtered = data.to_vec();
+        premultiply(&mut filtered);
+        box_blur(&mut filtered);
+
+        let mut coverage = alpha_of(data);
+        box_blur(&mut coverage);
+
+        unpremultiply(&mut filtered, &coverage, data, filter_alpha);
+        filtered
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn blurred_edge_has_no_dark_fringe() {
+        let blurred = blur_premultiplied(&edge(), true);
+
+        for pixel in blurred.chunks_exact(4).filter(|pixel| pixel[3] > 0.0) {
+            assert_close(&pixel[..3], &RED[..3]);
+        }
+        assert!(blurred[3 * 4 + 3] > 0.0 && blurred[3 * 4 + 3] < 1.0);
+    }
+
+    #[test]
+    fn unpremultiply_undoes_premultiply() {
+        let original = [
+            [0.2, 0.4, 0.6, 0.5],
+            [1.0, 0.5, 0.0, 0.25],
+            [0.3, 0.3, 0.3, 1.0],
+            [0.7, 0.1, 0.9, 0.0],
+        ]
+        .concat();
+        let mut data = original.clone();
+        premultiply(&mut data);
+        unpremultiply(&mut data, &alpha_of(&original), &original, true);
+
+        assert_close(&data, &original);
+    }
+
+    #[test]
+    fn original_alpha_is_kept_unless_filtered() {
+        let original = edge();
+        let blurred = blur_premultiplied(&original, false);
+
+        assert_close(&alpha_of(&blurred), &alpha_of(&original));
+    }
+
+    #[test]
+    fn overshoot_is_clamped_to_coverage() {
+        let original = [[0.5, 0.5, 0.5, 0.5]; 2].concat();
+        let mut data = [[0.9, -0.2, 0.25, 0.5]; 2].concat();
+        unpremultiply(&mut data, &alpha_of(&original), &original, true);
+
+        assert_close(&data, &[[1.0, 0.0, 0.5, 0.5]; 2].concat());
+    }
+}
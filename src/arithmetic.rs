@@ -0,0 +1,99 @@
+use image::{imageops, imageops::FilterType, RgbaImage};
+
+use crate::buffer::Sample;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    AbsDifference,
+    Min,
+    Max,
+    Average,
+    /// Weighted blend, `weight` being the share of the second image.
+    Blend(f32),
+    And,
+    Or,
+    Xor,
+    /// Bitwise complement of the first image; the operand is ignored.
+    Not,
+}
+
+impl ImageOperation {
+    /// Combines two values in levels, from 0.0 to 255.0, without rounding.
+    /// Bitwise operations work on the values rounded to 8 bits.
+    pub fn apply(&self, a: f32, b: f32) -> f32 {
+        let (ba, bb) = (u8::from_f32(a), u8::from_f32(b));
+
+        match *self {
+            ImageOperation::Add => (a + b).min(255.0),
+            ImageOperation::Subtract => (a - b).max(0.0),
+            ImageOperation::Multiply => a * b / 255.0,
+            ImageOperation::Divide => {
+                if b <= 0.0 {
+                    255.0
+                } else {
+                    (a / b * 255.0).min(255.0)
+                }
+            }
+            ImageOperation::AbsDifference => (a - b).abs(),
+            ImageOperation::Min => a.min(b),
+            ImageOperation::Max => a.max(b),
+            ImageOperation::Average => (a + b) / 2.0,
+            ImageOperation::Blend(weight) => (a + (b - a) * weight).clamp(0.0, 255.0),
+            ImageOperation::And => (ba & bb) as f32,
+            ImageOperation::Or => (ba | bb) as f32,
+            ImageOperation::Xor => (ba ^ bb) as f32,
+            ImageOperation::Not => !ba as f32,
+        }
+    }
+}
+
+/// How to line up a second image whose size differs from the first.
+#[derive(Clone, Copy, PartialEq)]
+pub enum OperandFit {
+    Resize,
+    AlignTopLeft,
+    AlignCenter,
+}
+
+/// Returns the operand as an RGBA buffer of exactly `width` x `height`,
+/// either stretched or placed on a transparent black background.
+pub fn fit_operand(operand: &RgbaImage, width: u32, height: u32, fit: OperandFit) -> Vec<u8> {
+    if operand.width() == width && operand.height() == height {
+        return operand.as_raw().clone();
+    }
+
+    match fit {
+        OperandFit::Resize => {
+            imageops::resize(operand, width, height, FilterType::Triangle).into_raw()
+        }
+        OperandFit::AlignTopLeft | OperandFit::AlignCenter => {
+            let (x, y) = match fit {
+                OperandFit::AlignCenter => (
+                    (width as i64 - operand.width() as i64) / 2,
+                    (height as i64 - operand.height() as i64) / 2,
+                ),
+                _ => (0, 0),
+            };
+
+            let mut canvas = RgbaImage::new(width, height);
+            imageops::replace(&mut canvas, operand, x, y);
+            canvas.into_raw()
+        }
+    }
+}
+
+/// Combines the colour channels of two equally sized RGBA buffers in place,
+/// leaving the alpha channel of `data` untouched.
+pub fn combine<S: Sample>(data: &mut [S], operand: &[u8], operation: ImageOperation) {
+    for (index, (a, b)) in data.iter_mut().zip(operand).enumerate() {
+        if index % 4 == 3 {
+            continue;
+        }
+
+        *a = S::from_unit(operation.apply(a.to_unit() * 255.0, *b as f32) / 255.0);
+    }
+}
@@ -0,0 +1,41 @@
+/// Returns bit `bit` (0 is the least significant) of one channel of an
+/// RGBA buffer as an opaque black and white image.
+pub fn plane(data: &[u8], channel: usize, bit: u32) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|pixel| {
+            let value = (pixel[channel] >> bit & 1) * 255;
+            [value, value, value, 255]
+        })
+        .collect()
+}
+
+/// Clears every bit of the colour channels that is not set in `planes`,
+/// rebuilding the image from the remaining bit planes.
+pub fn keep_planes(data: &mut [u8], planes: u8) {
+    for pixel in data.chunks_exact_mut(4) {
+        for value in &mut pixel[..3] {
+            *value &= planes;
+        }
+    }
+}
+
+/// Reduces each channel to the given number of bits, 1 to 8, spreading
+/// the remaining levels back over the full range.
+pub fn reduce_depth(data: &mut [u8], bits: [u32; 4]) {
+    let luts = bits.map(|bits| {
+        let bits = bits.clamp(1, 8);
+        let max = (1u32 << bits) - 1;
+        let mut lut = [0u8; 256];
+        for (value, entry) in lut.iter_mut().enumerate() {
+            let level = value as u32 >> (8 - bits);
+            *entry = ((level * 255 + max / 2) / max) as u8;
+        }
+        lut
+    });
+
+    for pixel in data.chunks_exact_mut(4) {
+        for (value, lut) in pixel.iter_mut().zip(&luts) {
+            *value = lut[*value as usize];
+        }
+    }
+}
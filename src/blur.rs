@@ -0,0 +1,105 @@
+use crate::buffer::Sample;
+
+/// Normalised 1D Gaussian kernel covering three standard deviations on
+/// each side.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil().max(1.0) as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    let sum: f32 = kernel.iter().sum();
+    for weight in kernel.iter_mut() {
+        *weight /= sum;
+    }
+
+    kernel
+}
+
+/// Convolves a single-channel plane with `kernel` horizontally and then
+/// vertically, repeating edge values past the border.
+pub fn convolve_separable(plane: &[f32], width: u32, height: u32, kernel: &[f32]) -> Vec<f32> {
+    let width = width as i32;
+    let height = height as i32;
+    let radius = (kernel.len() / 2) as i32;
+    let mut horizontal = vec![0.0; plane.len()];
+    let mut output = vec![0.0; plane.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for (i, weight) in kernel.iter().enumerate() {
+                let sx = (x + i as i32 - radius).clamp(0, width - 1);
+                sum += plane[(y * width + sx) as usize] * weight;
+            }
+            horizontal[(y * width + x) as usize] = sum;
+        }
+    }
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for (i, weight) in kernel.iter().enumerate() {
+                let sy = (y + i as i32 - radius).clamp(0, height - 1);
+                sum += horizontal[(sy * width + x) as usize] * weight;
+            }
+            output[(y * width + x) as usize] = sum;
+        }
+    }
+
+    output
+}
+
+pub fn gaussian_blur_plane(plane: &[f32], width: u32, height: u32, sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return plane.to_vec();
+    }
+
+    convolve_separable(plane, width, height, &gaussian_kernel(sigma))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct UnsharpMask {
+    /// Multiplier applied to the difference between image and blur.
+    pub amount: f32,
+    /// Sigma of the Gaussian blur, in pixels.
+    pub radius: f32,
+    /// Smallest difference, in levels, that gets sharpened.
+    pub threshold: f32,
+}
+
+impl Default for UnsharpMask {
+    fn default() -> Self {
+        Self {
+            amount: 1.0,
+            radius: 2.0,
+            threshold: 0.0,
+        }
+    }
+}
+
+/// Sharpens the colour channels of an RGBA buffer in place by adding back
+/// the signed difference from a Gaussian blurred copy, so edges get both
+/// lighter and darker sides.
+pub fn unsharp_mask<S: Sample>(data: &mut [S], width: u32, height: u32, params: &UnsharpMask) {
+    for channel in 0..3 {
+        // In levels, so the threshold means the same at any depth.
+        let plane: Vec<f32> = data
+            .iter()
+            .skip(channel)
+            .step_by(4)
+            .map(|value| value.to_unit() * 255.0)
+            .collect();
+        let blurred = gaussian_blur_plane(&plane, width, height, params.radius);
+
+        for (i, (value, blurred)) in plane.iter().zip(blurred).enumerate() {
+            let difference = value - blurred;
+            if difference.abs() < params.threshold {
+                continue;
+            }
+
+            let sharpened = (value + difference * params.amount).clamp(0.0, 255.0);
+            data[i * 4 + channel] = S::from_unit(sharpened / 255.0);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use image::{DynamicImage, ImageBuffer};
+
+/// Sample type an RGBA [`Buffer`] can hold.
+pub trait Sample: Copy + Default + PartialEq {
+    /// Value of a fully saturated sample.
+    const MAX: f32;
+
+    fn to_f32(self) -> f32;
+    /// Rounds and clamps to the sample range. Floats are kept as they are.
+    fn from_f32(value: f32) -> Self;
+    /// Decodes `image` to RGBA at this sample's depth.
+    fn decode(image: &DynamicImage) -> Vec<Self>;
+    fn to_image(data: Vec<Self>, width: u32, height: u32) -> DynamicImage;
+
+    /// The sample as a fraction of full scale.
+    fn to_unit(self) -> f32 {
+        self.to_f32() / Self::MAX
+    }
+
+    fn from_unit(value: f32) -> Self {
+        Self::from_f32(value * Self::MAX)
+    }
+}
+
+impl Sample for u8 {
+    const MAX: f32 = 255.0;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, 255.0) as u8
+    }
+
+    fn decode(image: &DynamicImage) -> Vec<Self> {
+        image.to_rgba8().into_raw()
+    }
+
+    fn to_image(data: Vec<Self>, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, data).unwrap())
+    }
+}
+
+impl Sample for u16 {
+    const MAX: f32 = 65535.0;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value.round().clamp(0.0, 65535.0) as u16
+    }
+
+    fn decode(image: &DynamicImage) -> Vec<Self> {
+        image.to_rgba16().into_raw()
+    }
+
+    fn to_image(data: Vec<Self>, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, data).unwrap())
+    }
+}
+
+/// Floats are normalised, with 1.0 at full scale.
+impl Sample for f32 {
+    const MAX: f32 = 1.0;
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn decode(image: &DynamicImage) -> Vec<Self> {
+        image.to_rgba32f().into_raw()
+    }
+
+    fn to_image(data: Vec<Self>, width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, data).unwrap())
+    }
+}
+
+/// RGBA pixels with four samples per pixel, row by row.
+#[derive(Clone, PartialEq)]
+pub struct Buffer<S> {
+    pub data: Vec<S>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<S: Sample> Buffer<S> {
+    /// Decodes `image` without going through 8 bits, so 16-bit and float
+    /// sources keep their precision in wider sample types.
+    pub fn decode(image: &DynamicImage) -> Self {
+        Self {
+            data: S::decode(image),
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    pub fn from_rgba8(data: &[u8], width: u32, height: u32) -> Self {
+        Buffer {
+            data: data.to_vec(),
+            width,
+            height,
+        }
+        .convert()
+    }
+
+    pub fn convert<T: Sample>(&self) -> Buffer<T> {
+        Buffer {
+            data: self
+                .data
+                .iter()
+                .map(|value| T::from_unit(value.to_unit()))
+                .collect(),
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Rounds every sample to 8 bits, for display and 8-bit operations.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.convert::<u8>().data
+    }
+
+    pub fn to_image(&self) -> DynamicImage {
+        S::to_image(self.data.clone(), self.width, self.height)
+    }
+}
+
+/// Largest image, in pixels, that operations will allocate.
+pub const MAX_PIXELS: usize = 8192 * 8192;
+
+/// Number of samples in an RGBA buffer of the given size, or `None` if it
+/// is larger than [`MAX_PIXELS`].
+pub fn rgba_len(width: u32, height: u32) -> Option<usize> {
+    let pixels = (width as usize).checked_mul(height as usize)?;
+    (pixels <= MAX_PIXELS).then(|| pixels * 4)
+}
+
+/// Bits per channel of a decoded image.
+pub fn depth(image: &DynamicImage) -> u32 {
+    let color = image.color();
+    color.bits_per_pixel() as u32 / color.channel_count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::ImageOutputFormat;
+    use std::io::Cursor;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 64;
+
+    /// 16-bit RGBA gradient whose samples mostly fall between 8-bit levels.
+    fn gradient() -> Vec<u16> {
+        (0..WIDTH * HEIGHT * 4)
+            .map(|i| (i * 4099 % 65536) as u16)
+            .collect()
+    }
+
+    fn encode_png(image: &DynamicImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn sixteen_bit_png_survives_decode_and_export() {
+        let source = Buffer {
+            data: gradient(),
+            width: WIDTH,
+            height: HEIGHT,
+        };
+        let png = encode_png(&source.to_image());
+
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(depth(&decoded), 16);
+        let working = Buffer::<f32>::decode(&decoded);
+
+        let exported = encode_png(&working.convert::<u16>().to_image());
+        let reloaded = Buffer::<u16>::decode(&image::load_from_memory(&exported).unwrap());
+        assert!(reloaded == source);
+    }
+}
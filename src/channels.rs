@@ -0,0 +1,153 @@
+use crate::buffer::Sample;
+
+pub const CHANNEL_NAMES: [&str; 4] = ["Red", "Green", "Blue", "Alpha"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MixerPreset {
+    Identity,
+    SwapRedBlue,
+    Monochrome,
+    Sepia,
+}
+
+impl MixerPreset {
+    pub const ALL: [MixerPreset; 4] = [
+        MixerPreset::Identity,
+        MixerPreset::SwapRedBlue,
+        MixerPreset::Monochrome,
+        MixerPreset::Sepia,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MixerPreset::Identity => "Identity",
+            MixerPreset::SwapRedBlue => "Swap red and blue",
+            MixerPreset::Monochrome => "Monochrome",
+            MixerPreset::Sepia => "Sepia",
+        }
+    }
+}
+
+/// Affine map on RGBA: each output channel is a weighted sum of the input
+/// channels plus an offset in levels.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ChannelMixer {
+    /// One row per output channel, one column per input channel.
+    pub matrix: [[f32; 4]; 4],
+    pub offsets: [f32; 4],
+    /// Mix the alpha channel too; otherwise only the 3×3 colour part and
+    /// its offsets are used and alpha is kept.
+    pub alpha: bool,
+}
+
+impl ChannelMixer {
+    pub fn preset(preset: MixerPreset) -> Self {
+        let rgb = match preset {
+            MixerPreset::Identity => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            MixerPreset::SwapRedBlue => [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+            MixerPreset::Monochrome => [[0.2126, 0.7152, 0.0722]; 3],
+            MixerPreset::Sepia => [
+                [0.393, 0.769, 0.189],
+                [0.349, 0.686, 0.168],
+                [0.272, 0.534, 0.131],
+            ],
+        };
+
+        let mut matrix = [[0.0; 4]; 4];
+        for (row, weights) in matrix.iter_mut().zip(rgb) {
+            row[..3].copy_from_slice(&weights);
+        }
+        matrix[3][3] = 1.0;
+
+        Self {
+            matrix,
+            offsets: [0.0; 4],
+            alpha: false,
+        }
+    }
+}
+
+impl Default for ChannelMixer {
+    fn default() -> Self {
+        Self::preset(MixerPreset::Identity)
+    }
+}
+
+/// Mixes the channels of an RGBA buffer in place.
+pub fn mix<S: Sample>(data: &mut [S], mixer: &ChannelMixer) {
+    let channels = if mixer.alpha { 4 } else { 3 };
+
+    for pixel in data.chunks_exact_mut(4) {
+        let input = [0, 1, 2, 3].map(|c| pixel[c].to_unit() * 255.0);
+        for (value, (row, offset)) in pixel
+            .iter_mut()
+            .zip(mixer.matrix.iter().zip(mixer.offsets))
+            .take(channels)
+        {
+            let sum: f32 = row[..channels]
+                .iter()
+                .zip(input)
+                .map(|(weight, input)| weight * input)
+                .sum();
+            *value = S::from_unit(((sum + offset) / 255.0).clamp(0.0, 1.0));
+        }
+    }
+}
+
+/// Returns one channel of an RGBA buffer as an opaque grayscale image.
+pub fn split<S: Sample>(data: &[S], channel: usize) -> Vec<S> {
+    data.chunks_exact(4)
+        .flat_map(|pixel| {
+            let value = pixel[channel];
+            [value, value, value, S::from_unit(1.0)]
+        })
+        .collect()
+}
+
+/// Replaces each channel that has a source with the luminance of that
+/// source, an RGBA buffer of the same size. Channels without one are kept.
+pub fn merge<S: Sample>(data: &mut [S], sources: [Option<&[u8]>; 4]) {
+    for (channel, source) in sources.iter().enumerate() {
+        let source = match source {
+            Some(source) => source,
+            None => continue,
+        };
+
+        for (pixel, gray) in data.chunks_exact_mut(4).zip(source.chunks_exact(4)) {
+            let luminance =
+                gray[0] as f32 * 0.2126 + gray[1] as f32 * 0.7152 + gray[2] as f32 * 0.0722;
+            pixel[channel] = S::from_unit(luminance / 255.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::Buffer;
+
+    fn scale(factor: f32) -> ChannelMixer {
+        let mut mixer = ChannelMixer::default();
+        for (channel, row) in mixer.matrix.iter_mut().enumerate().take(3) {
+            row[channel] = factor;
+        }
+        mixer
+    }
+
+    #[test]
+    fn chained_mixes_do_not_drift() {
+        let source = Buffer {
+            data: (0..4096u32).map(|i| (i * 16 + 7) as u16).collect(),
+            width: 32,
+            height: 32,
+        };
+        let mut working = source.convert::<f32>();
+
+        for _ in 0..20 {
+            mix(&mut working.data, &scale(0.3));
+            mix(&mut working.data, &scale(1.0 / 0.3));
+        }
+
+        assert!(working.convert::<u16>() == source);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::buffer::Sample;
+use crate::color;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ChromaKey {
+    pub key: [u8; 3],
+    /// Lab distance (ΔE*76) from the key up to which pixels become fully
+    /// transparent.
+    pub tolerance: f32,
+    /// Width of the band beyond the tolerance over which alpha ramps back
+    /// up to opaque.
+    pub softness: f32,
+    /// Share of the key colour's tint removed from the pixels that remain,
+    /// from 0 (off) to 1.
+    pub spill: f32,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            key: [0, 255, 0],
+            tolerance: 40.0,
+            softness: 20.0,
+            spill: 0.0,
+        }
+    }
+}
+
+/// Makes colours near the key transparent, scaling the existing alpha by
+/// the matte, and optionally pulls the key's tint out of the rest.
+pub fn key<S: Sample>(data: &mut [S], params: &ChromaKey) {
+    let [r, g, b] = params.key.map(|c| c as f32);
+    let key = color::rgb_to_lab(r, g, b);
+    let key_chroma = key.1.hypot(key.2);
+    // Direction of the key's tint in the a*b* plane; greys have none.
+    let tint = (key_chroma > 1.0).then(|| (key.1 / key_chroma, key.2 / key_chroma));
+    let spill = params.spill.clamp(0.0, 1.0);
+
+    for pixel in data.chunks_exact_mut(4) {
+        let [r, g, b] = [0, 1, 2].map(|c| pixel[c].to_unit() * 255.0);
+        let (l, a, b) = color::rgb_to_lab(r, g, b);
+        let distance = ((l - key.0).powi(2) + (a - key.1).powi(2) + (b - key.2).powi(2)).sqrt();
+        let matte = if params.softness > 0.0 {
+            ((distance - params.tolerance) / params.softness).clamp(0.0, 1.0)
+        } else if distance > params.tolerance {
+            1.0
+        } else {
+            0.0
+        };
+        pixel[3] = S::from_unit(pixel[3].to_unit() * matte);
+
+        if let Some((tint_a, tint_b)) = tint.filter(|_| spill > 0.0) {
+            let along = a * tint_a + b * tint_b;
+            if along > 0.0 {
+                let removed = along * spill;
+                let (r, g, b) = color::lab_to_rgb(l, a - removed * tint_a, b - removed * tint_b);
+                for (value, level) in pixel[..3].iter_mut().zip([r, g, b]) {
+                    *value = S::from_unit((level / 255.0).clamp(0.0, 1.0));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// D65 reference white used for the Lab conversion.
+const WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+pub fn to_hex(r: u8, g: u8, b: u8) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Parses a `#rrggbb` string as produced by colour inputs.
+pub fn from_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Converts channels in 0..=255 to hue, saturation and value, all in 0..=1.
+pub fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let r = r / 255.0;
+    let g = g / 255.0;
+    let b = b / 255.0;
+
+    let max = r.max(g.max(b));
+    let min = r.min(g.min(b));
+    let d = max - min;
+
+    let s = if max > 0.0 { d / max } else { 0.0 };
+    let mut h = 0.0;
+
+    if d > 0.0 {
+        if max == r {
+            h = (g - b) / d + if g < b { 6.0 } else { 0.0 };
+        } else if max == g {
+            h = (b - r) / d + 2.0;
+        } else {
+            h = (r - g) / d + 4.0;
+        }
+
+        h /= 6.0;
+    }
+
+    (h, s, max)
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    let c = c / 255.0;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts sRGB channels in 0..=255 to CIE L*a*b* under a D65 white point.
+pub fn rgb_to_lab(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let (r, g, b) = (srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b));
+
+    let xyz = [
+        0.4124 * r + 0.3576 * g + 0.1805 * b,
+        0.2126 * r + 0.7152 * g + 0.0722 * b,
+        0.0193 * r + 0.1192 * g + 0.9505 * b,
+    ];
+    let [fx, fy, fz] = [0, 1, 2].map(|i| {
+        let t = xyz[i] / WHITE[i];
+
+        if t > 216.0 / 24389.0 {
+            t.cbrt()
+        } else {
+            (24389.0 / 27.0 * t + 16.0) / 116.0
+        }
+    });
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).clamp(0.0, 255.0)
+}
+
+/// Inverse of `rgb_to_lab`, clamping colours outside the sRGB gamut.
+pub fn lab_to_rgb(l: f32, a: f32, b: f32) -> (f32, f32, f32) {
+    let fy = (l + 16.0) / 116.0;
+    let f = [fy + a / 500.0, fy, fy - b / 200.0];
+    let [x, y, z] = [0, 1, 2].map(|i| {
+        let t = if f[i] > 6.0 / 29.0 {
+            f[i] * f[i] * f[i]
+        } else {
+            (116.0 * f[i] - 16.0) * 27.0 / 24389.0
+        };
+
+        t * WHITE[i]
+    });
+
+    (
+        linear_to_srgb(3.2406 * x - 1.5372 * y - 0.4986 * z),
+        linear_to_srgb(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+        linear_to_srgb(0.0557 * x - 0.2040 * y + 1.0570 * z),
+    )
+}
+
+/// Averages the RGBA values of the `size` x `size` square centred on
+/// `(x, y)`, clipped to the image.
+pub fn average_area(data: &[u8], width: u32, height: u32, x: u32, y: u32, size: u32) -> [u8; 4] {
+    let radius = size / 2;
+    let (left, top) = (x.saturating_sub(radius), y.saturating_sub(radius));
+    let right = (x + radius).min(width - 1);
+    let bottom = (y + radius).min(height - 1);
+
+    let mut sum = [0u32; 4];
+    for row in top..=bottom {
+        for column in left..=right {
+            let index = ((row * width + column) * 4) as usize;
+            for (total, value) in sum.iter_mut().zip(&data[index..index + 4]) {
+                *total += *value as u32;
+            }
+        }
+    }
+
+    let count = (right - left + 1) * (bottom - top + 1);
+    sum.map(|total| ((total + count / 2) / count) as u8)
+}
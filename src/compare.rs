@@ -0,0 +1,83 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompareMode {
+    Off,
+    SideBySide,
+    Split,
+    Difference,
+}
+
+/// Colour stops of the heatmap, from no difference to the largest one.
+const HEATMAP: [[f32; 3]; 5] = [
+    [0.0, 0.0, 0.0],
+    [0.0, 0.0, 255.0],
+    [255.0, 0.0, 0.0],
+    [255.0, 255.0, 0.0],
+    [255.0, 255.0, 255.0],
+];
+
+fn heatmap_color(t: f32) -> [u8; 3] {
+    let scaled = t.clamp(0.0, 1.0) * (HEATMAP.len() - 1) as f32;
+    let index = (scaled as usize).min(HEATMAP.len() - 2);
+    let fraction = scaled - index as f32;
+    let (from, to) = (HEATMAP[index], HEATMAP[index + 1]);
+
+    [0, 1, 2].map(|c| (from[c] + (to[c] - from[c]) * fraction).round() as u8)
+}
+
+/// Renders the per-pixel difference between two equally sized RGBA buffers
+/// as an opaque heatmap, normalised to the largest channel difference.
+/// Also returns that largest difference.
+pub fn difference_heatmap(current: &[u8], original: &[u8]) -> (Vec<u8>, u8) {
+    let differences: Vec<u8> = current
+        .chunks_exact(4)
+        .zip(original.chunks_exact(4))
+        .map(|(a, b)| (0..4).map(|c| a[c].abs_diff(b[c])).max().unwrap())
+        .collect();
+    let max = differences.iter().copied().max().unwrap_or(0);
+
+    let mut heatmap = Vec::with_capacity(current.len());
+    for difference in differences {
+        let t = if max == 0 {
+            0.0
+        } else {
+            difference as f32 / max as f32
+        };
+        heatmap.extend_from_slice(&heatmap_color(t));
+        heatmap.push(255);
+    }
+
+    (heatmap, max)
+}
+
+/// Shows `current` left of column `split` and `original` from there on.
+pub fn split_view(current: &[u8], original: &[u8], width: u32, split: u32) -> Vec<u8> {
+    let mut output = current.to_vec();
+    let row_length = (width * 4) as usize;
+    let split = (split.min(width) * 4) as usize;
+
+    for (output_row, original_row) in output
+        .chunks_exact_mut(row_length)
+        .zip(original.chunks_exact(row_length))
+    {
+        output_row[split..].copy_from_slice(&original_row[split..]);
+    }
+
+    output
+}
+
+/// Places `current` and `original` next to each other in a buffer twice as
+/// wide, with the current image on the left.
+pub fn side_by_side(current: &[u8], original: &[u8], width: u32) -> Vec<u8> {
+    let row_length = (width * 4) as usize;
+    let mut output = Vec::with_capacity(current.len() * 2);
+
+    for (current_row, original_row) in current
+        .chunks_exact(row_length)
+        .zip(original.chunks_exact(row_length))
+    {
+        output.extend_from_slice(current_row);
+        output.extend_from_slice(original_row);
+    }
+
+    output
+}
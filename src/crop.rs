@@ -0,0 +1,494 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum AspectRatio {
+    Free,
+    Square,
+    FourThree,
+    SixteenNine,
+    Custom(f64, f64),
+}
+
+impl AspectRatio {
+    pub fn ratio(&self) -> Option<f64> {
+        match *self {
+            AspectRatio::Free => None,
+            AspectRatio::Square => Some(1.0),
+            AspectRatio::FourThree => Some(4.0 / 3.0),
+            AspectRatio::SixteenNine => Some(16.0 / 9.0),
+            AspectRatio::Custom(w, h) if w > 0.0 && h > 0.0 => Some(w / h),
+            AspectRatio::Custom(_, _) => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Handle {
+    Move,
+    North,
+    South,
+    East,
+    West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Handle {
+    const ALL: [Handle; 8] = [
+        Handle::NorthWest,
+        Handle::North,
+        Handle::NorthEast,
+        Handle::East,
+        Handle::SouthEast,
+        Handle::South,
+        Handle::SouthWest,
+        Handle::West,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl CropRect {
+    pub fn handle_position(&self, handle: Handle) -> (f64, f64) {
+        let left = self.x;
+        let right = self.x + self.width;
+        let top = self.y;
+        let bottom = self.y + self.height;
+        let center_x = self.x + self.width / 2.0;
+        let center_y = self.y + self.height / 2.0;
+
+        match handle {
+            Handle::Move => (center_x, center_y),
+            Handle::North => (center_x, top),
+            Handle::South => (center_x, bottom),
+            Handle::East => (right, center_y),
+            Handle::West => (left, center_y),
+            Handle::NorthEast => (right, top),
+            Handle::NorthWest => (left, top),
+            Handle::SouthEast => (right, bottom),
+            Handle::SouthWest => (left, bottom),
+        }
+    }
+
+    pub fn handles(&self) -> Vec<(f64, f64)> {
+        Handle::ALL
+            .iter()
+            .map(|handle| self.handle_position(*handle))
+            .collect()
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    /// Rounds the rectangle to whole pixels and clips it to the image.
+    pub fn to_pixels(self, image_width: u32, image_height: u32) -> (u32, u32, u32, u32) {
+        let x = (self.x.round().max(0.0) as u32).min(image_width.saturating_sub(1));
+        let y = (self.y.round().max(0.0) as u32).min(image_height.saturating_sub(1));
+        let width = (self.width.round().max(1.0) as u32).min(image_width - x);
+        let height = (self.height.round().max(1.0) as u32).min(image_height - y);
+
+        (x, y, width, height)
+    }
+}
+
+struct Drag {
+    handle: Handle,
+    start_x: f64,
+    start_y: f64,
+    start_rect: CropRect,
+}
+
+pub struct CropTool {
+    pub rect: Option<CropRect>,
+    pub aspect: AspectRatio,
+    bounds: (f64, f64),
+    drag: Option<Drag>,
+}
+
+impl CropTool {
+    pub fn new(image_width: u32, image_height: u32) -> Self {
+        Self {
+            rect: None,
+            aspect: AspectRatio::Free,
+            bounds: (image_width as f64, image_height as f64),
+            drag: None,
+        }
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Starts dragging a handle, moving the selection or drawing a new one
+    /// depending on what lies under the pointer. `tolerance` is the handle
+    /// hit radius in image pixels.
+    pub fn pointer_down(&mut self, x: f64, y: f64, tolerance: f64) {
+        let (x, y) = self.clamp_point(x, y);
+
+        if let Some(rect) = self.rect {
+            let hit = Handle::ALL.iter().find(|handle| {
+                let (hx, hy) = rect.handle_position(**handle);
+                (hx - x).abs() <= tolerance && (hy - y).abs() <= tolerance
+            });
+
+            let handle = match hit {
+                Some(handle) => Some(*handle),
+                None if rect.contains(x, y) => Some(Handle::Move),
+                None => None,
+            };
+
+            if let Some(handle) = handle {
+                self.drag = Some(Drag {
+                    handle,
+                    start_x: x,
+                    start_y: y,
+                    start_rect: rect,
+                });
+                return;
+            }
+        }
+
+        let rect = CropRect {
+            x,
+            y,
+            width: 0.0,
+            height: 0.0,
+        };
+        self.rect = Some(rect);
+        self.drag = Some(Drag {
+            handle: Handle::SouthEast,
+            start_x: x,
+            start_y: y,
+            start_rect: rect,
+        });
+    }
+
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        let drag = match &self.drag {
+            Some(drag) => drag,
+            None => return,
+        };
+
+        let (x, y) = self.clamp_point(x, y);
+        let start = drag.start_rect;
+        let ratio = self.aspect.ratio();
+
+        let rect = match drag.handle {
+            Handle::Move => {
+                let (width, height) = self.bounds;
+                let new_x = (start.x + x - drag.start_x).clamp(0.0, width - start.width);
+                let new_y = (start.y + y - drag.start_y).clamp(0.0, height - start.height);
+
+                CropRect {
+                    x: new_x,
+                    y: new_y,
+                    ..start
+                }
+            }
+            Handle::NorthWest | Handle::NorthEast | Handle::SouthWest | Handle::SouthEast => {
+                let (anchor_x, anchor_y) = start.handle_position(Self::opposite(drag.handle));
+                self.corner_rect(anchor_x, anchor_y, x - anchor_x, y - anchor_y, ratio)
+            }
+            Handle::East | Handle::West => {
+                let anchor_x = match drag.handle {
+                    Handle::East => start.x,
+                    _ => start.x + start.width,
+                };
+                let center_y = start.y + start.height / 2.0;
+                let width = x - anchor_x;
+                let height = ratio.map_or(start.height, |r| width.abs() / r);
+
+                self.edge_rect(anchor_x, center_y, width, height, true)
+            }
+            Handle::North | Handle::South => {
+                let anchor_y = match drag.handle {
+                    Handle::South => start.y,
+                    _ => start.y + start.height,
+                };
+                let center_x = start.x + start.width / 2.0;
+                let height = y - anchor_y;
+                let width = ratio.map_or(start.width, |r| height.abs() * r);
+
+                self.edge_rect(anchor_y, center_x, height, width, false)
+            }
+        };
+
+        self.rect = Some(rect);
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.drag = None;
+
+        if let Some(rect) = self.rect {
+            if rect.width < 1.0 || rect.height < 1.0 {
+                self.rect = None;
+            }
+        }
+    }
+
+    pub fn set_aspect(&mut self, aspect: AspectRatio) {
+        self.aspect = aspect;
+
+        if let (Some(rect), Some(ratio)) = (self.rect, aspect.ratio()) {
+            self.rect =
+                Some(self.corner_rect(rect.x, rect.y, rect.width, rect.width / ratio, Some(ratio)));
+        }
+    }
+
+    /// Replaces the selection with exact pixel values typed in by the user.
+    /// When an aspect ratio is locked the height follows the width.
+    pub fn set_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let (bound_width, bound_height) = self.bounds;
+        let x = x.clamp(0.0, bound_width - 1.0);
+        let y = y.clamp(0.0, bound_height - 1.0);
+        let width = width.max(1.0);
+        let height = match self.aspect.ratio() {
+            Some(ratio) => width / ratio,
+            None => height.max(1.0),
+        };
+
+        self.rect = Some(self.corner_rect(x, y, width, height, self.aspect.ratio()));
+    }
+
+    fn clamp_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (x.clamp(0.0, self.bounds.0), y.clamp(0.0, self.bounds.1))
+    }
+
+    fn opposite(handle: Handle) -> Handle {
+        match handle {
+            Handle::NorthWest => Handle::SouthEast,
+            Handle::NorthEast => Handle::SouthWest,
+            Handle::SouthWest => Handle::NorthEast,
+            Handle::SouthEast => Handle::NorthWest,
+            Handle::North => Handle::South,
+            Handle::South => Handle::North,
+            Handle::East => Handle::West,
+            Handle::West => Handle::East,
+            Handle::Move => Handle::Move,
+        }
+    }
+
+    /// Builds a rectangle spanning from a fixed anchor by a signed extent,
+    /// forcing the aspect ratio and shrinking it to stay inside the image.
+    fn corner_rect(
+        &self,
+        anchor_x: f64,
+        anchor_y: f64,
+        width: f64,
+        height: f64,
+        ratio: Option<f64>,
+    ) -> CropRect {
+        let (mut width, mut height) = (width, height);
+
+        if let Some(ratio) = ratio {
+            if width.abs() / ratio > height.abs() {
+                height = height.signum() * width.abs() / ratio;
+            } else {
+                width = width.signum() * height.abs() * ratio;
+            }
+        }
+
+        let (bound_width, bound_height) = self.bounds;
+        let max_width = if width >= 0.0 {
+            bound_width - anchor_x
+        } else {
+            anchor_x
+        };
+        let max_height = if height >= 0.0 {
+            bound_height - anchor_y
+        } else {
+            anchor_y
+        };
+
+        let mut scale_x = if width.abs() > max_width {
+            max_width / width.abs()
+        } else {
+            1.0
+        };
+        let mut scale_y = if height.abs() > max_height {
+            max_height / height.abs()
+        } else {
+            1.0
+        };
+        if ratio.is_some() {
+            scale_x = scale_x.min(scale_y);
+            scale_y = scale_x;
+        }
+
+        width *= scale_x;
+        height *= scale_y;
+
+        CropRect {
+            x: anchor_x.min(anchor_x + width),
+            y: anchor_y.min(anchor_y + height),
+            width: width.abs(),
+            height: height.abs(),
+        }
+    }
+
+    /// Builds a rectangle for an edge drag: `extent` is measured from the fixed
+    /// edge at `anchor` and `span` is centred on `center` along the other axis.
+    fn edge_rect(
+        &self,
+        anchor: f64,
+        center: f64,
+        extent: f64,
+        span: f64,
+        horizontal: bool,
+    ) -> CropRect {
+        let (bound_along, bound_across) = if horizontal {
+            (self.bounds.0, self.bounds.1)
+        } else {
+            (self.bounds.1, self.bounds.0)
+        };
+
+        let max_extent = if extent >= 0.0 {
+            bound_along - anchor
+        } else {
+            anchor
+        };
+        let max_span = 2.0 * center.min(bound_across - center);
+        let scale = (max_extent / extent.abs().max(f64::EPSILON))
+            .min(max_span / span.max(f64::EPSILON))
+            .min(1.0);
+        let (extent, span) = if self.aspect.ratio().is_some() {
+            (extent * scale, span * scale)
+        } else {
+            (
+                extent.clamp(-anchor, bound_along - anchor),
+                span.min(bound_across),
+            )
+        };
+
+        let along = anchor.min(anchor + extent);
+        let across = (center - span / 2.0).clamp(0.0, bound_across - span);
+
+        if horizontal {
+            CropRect {
+                x: along,
+                y: across,
+                width: extent.abs(),
+                height: span,
+            }
+        } else {
+            CropRect {
+                x: across,
+                y: along,
+                width: span,
+                height: extent.abs(),
+            }
+        }
+    }
+}
+
+/// Copies the given region out of an RGBA buffer.
+pub fn crop_rgba<T: Copy>(
+    data: &[T],
+    image_width: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Vec<T> {
+    let mut cropped = Vec::with_capacity(width as usize * height as usize * 4);
+
+    for row in y..y + height {
+        let start = (row as usize * image_width as usize + x as usize) * 4;
+        let end = start + width as usize * 4;
+        cropped.extend_from_slice(&data[start..end]);
+    }
+
+    cropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 200;
+    const HEIGHT: u32 = 100;
+
+    fn tool(aspect: AspectRatio) -> CropTool {
+        let mut tool = CropTool::new(WIDTH, HEIGHT);
+        tool.set_rect(50.0, 25.0, 100.0, 50.0);
+        tool.set_aspect(aspect);
+        tool
+    }
+
+    /// Drags `handle` far outside the image in the direction it faces.
+    fn drag_out(tool: &mut CropTool, handle: Handle) -> CropRect {
+        let rect = tool.rect.unwrap();
+        let (hx, hy) = rect.handle_position(handle);
+        let (cx, cy) = rect.handle_position(Handle::Move);
+        let (dx, dy) = match handle {
+            Handle::Move => (1.0, 1.0),
+            _ => ((hx - cx).signum(), (hy - cy).signum()),
+        };
+
+        tool.pointer_down(hx, hy, 1.0);
+        tool.pointer_move(hx + dx * 1000.0, hy + dy * 1000.0);
+        tool.pointer_up();
+        tool.rect.unwrap()
+    }
+
+    fn assert_inside(rect: CropRect) {
+        let epsilon = 1e-9;
+        assert!(rect.x >= -epsilon && rect.y >= -epsilon);
+        assert!(rect.x + rect.width <= WIDTH as f64 + epsilon);
+        assert!(rect.y + rect.height <= HEIGHT as f64 + epsilon);
+    }
+
+    #[test]
+    fn dragging_past_the_edges_stays_inside() {
+        for handle in Handle::ALL.into_iter().chain([Handle::Move]) {
+            assert_inside(drag_out(&mut tool(AspectRatio::Free), handle));
+        }
+    }
+
+    #[test]
+    fn locked_ratio_survives_clamping() {
+        for aspect in [
+            AspectRatio::Square,
+            AspectRatio::FourThree,
+            AspectRatio::SixteenNine,
+        ] {
+            let ratio = aspect.ratio().unwrap();
+            for handle in Handle::ALL {
+                let rect = drag_out(&mut tool(aspect), handle);
+
+                assert_inside(rect);
+                assert!((rect.width / rect.height - ratio).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn to_pixels_clips_at_the_right_and_bottom_edges() {
+        let rect = |x, y, width, height| CropRect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        assert_eq!(
+            rect(150.4, 80.2, 49.6, 19.8).to_pixels(WIDTH, HEIGHT),
+            (150, 80, 50, 20)
+        );
+        assert_eq!(
+            rect(150.0, 80.0, 80.0, 40.0).to_pixels(WIDTH, HEIGHT),
+            (150, 80, 50, 20)
+        );
+        assert_eq!(
+            rect(199.6, 99.7, 10.0, 10.0).to_pixels(WIDTH, HEIGHT),
+            (199, 99, 1, 1)
+        );
+    }
+}
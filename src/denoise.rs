@@ -0,0 +1,405 @@
+use crate::blur;
+
+/// Number of sectors the anisotropic Kuwahara filter splits its ellipse
+/// into.
+const SECTORS: usize = 8;
+/// Exponent that controls how strongly low-variance sectors dominate the
+/// anisotropic Kuwahara result.
+const SECTOR_SHARPNESS: f32 = 8.0;
+/// Smoothing applied to the structure tensor before reading orientations.
+const TENSOR_SIGMA: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Denoiser {
+    /// Sigmas in pixels and in levels respectively.
+    Bilateral {
+        spatial_sigma: f32,
+        range_sigma: f32,
+    },
+    Kuwahara {
+        radius: u32,
+    },
+    /// Kuwahara over an ellipse that follows the local edge direction.
+    AnisotropicKuwahara {
+        radius: u32,
+    },
+    /// `strength` is the patch distance, in levels, at which a candidate's
+    /// weight falls to 1/e.
+    NonLocalMeans {
+        search_radius: u32,
+        patch_radius: u32,
+        strength: f32,
+    },
+}
+
+impl Denoiser {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Denoiser::Bilateral { .. } => "Bilateral",
+            Denoiser::Kuwahara { .. } => "Kuwahara",
+            Denoiser::AnisotropicKuwahara { .. } => "Anisotropic Kuwahara",
+            Denoiser::NonLocalMeans { .. } => "Non-local means",
+        }
+    }
+}
+
+/// Local edge orientation and how pronounced it is, per pixel.
+struct Orientation {
+    angle: Vec<f32>,
+    /// 0.0 for flat or isotropic areas, approaching 1.0 along strong edges.
+    anisotropy: Vec<f32>,
+}
+
+/// A denoiser run that is processed a row at a time, so that the caller
+/// can report progress and keep the page responsive between rows. Works on
+/// normalised RGBA samples; the filters themselves measure in levels.
+pub struct DenoiseJob {
+    denoiser: Denoiser,
+    source: Vec<f32>,
+    output: Vec<f32>,
+    width: u32,
+    height: u32,
+    next_row: u32,
+    orientation: Option<Orientation>,
+}
+
+impl DenoiseJob {
+    pub fn new(data: &[f32], width: u32, height: u32, denoiser: Denoiser) -> Self {
+        let orientation = match denoiser {
+            Denoiser::AnisotropicKuwahara { .. } => Some(orientation(data, width, height)),
+            _ => None,
+        };
+
+        Self {
+            denoiser,
+            source: data.to_vec(),
+            output: data.to_vec(),
+            width,
+            height,
+            next_row: 0,
+            orientation,
+        }
+    }
+
+    pub fn denoiser(&self) -> Denoiser {
+        self.denoiser
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next_row >= self.height
+    }
+
+    /// Share of rows processed so far, from 0.0 to 1.0.
+    pub fn progress(&self) -> f64 {
+        if self.height == 0 {
+            1.0
+        } else {
+            self.next_row as f64 / self.height as f64
+        }
+    }
+
+    /// Filters the next row. Does nothing once the job is done.
+    pub fn step(&mut self) {
+        if self.is_done() {
+            return;
+        }
+
+        let y = self.next_row;
+        for x in 0..self.width {
+            let color = match self.denoiser {
+                Denoiser::Bilateral {
+                    spatial_sigma,
+                    range_sigma,
+                } => self.bilateral(x, y, spatial_sigma, range_sigma),
+                Denoiser::Kuwahara { radius } => self.kuwahara(x, y, radius),
+                Denoiser::AnisotropicKuwahara { radius } => self.anisotropic_kuwahara(x, y, radius),
+                Denoiser::NonLocalMeans {
+                    search_radius,
+                    patch_radius,
+                    strength,
+                } => self.non_local_means(x, y, search_radius, patch_radius, strength),
+            };
+
+            let index = ((y * self.width + x) * 4) as usize;
+            for (value, channel) in self.output[index..index + 3].iter_mut().zip(color) {
+                *value = (channel / 255.0).clamp(0.0, 1.0);
+            }
+        }
+
+        self.next_row += 1;
+    }
+
+    /// Whether `data` is still the image the job started from.
+    pub fn matches(&self, data: &[f32]) -> bool {
+        self.source == data
+    }
+
+    /// The filtered image; rows not processed yet are left unchanged.
+    pub fn finish(self) -> Vec<f32> {
+        self.output
+    }
+
+    /// Colour at `(x, y)`, repeating edge pixels past the border.
+    fn pixel(&self, x: i32, y: i32) -> [f32; 3] {
+        let x = x.clamp(0, self.width as i32 - 1) as u32;
+        let y = y.clamp(0, self.height as i32 - 1) as u32;
+        let index = ((y * self.width + x) * 4) as usize;
+
+        [0, 1, 2].map(|c| self.source[index + c] * 255.0)
+    }
+
+    fn bilateral(&self, x: u32, y: u32, spatial_sigma: f32, range_sigma: f32) -> [f32; 3] {
+        let (x, y) = (x as i32, y as i32);
+        let radius = (spatial_sigma * 2.0).ceil().max(1.0) as i32;
+        let spatial = -0.5 / (spatial_sigma * spatial_sigma).max(f32::EPSILON);
+        let range = -0.5 / (range_sigma * range_sigma).max(f32::EPSILON);
+        let center = self.pixel(x, y);
+
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let color = self.pixel(x + dx, y + dy);
+                let distance = color_distance(color, center);
+                let weight = ((dx * dx + dy * dy) as f32 * spatial + distance * range).exp();
+
+                for c in 0..3 {
+                    sum[c] += color[c] * weight;
+                }
+                total += weight;
+            }
+        }
+
+        sum.map(|value| value / total)
+    }
+
+    /// Mean of whichever of the four `(radius + 1)`-square quadrants around
+    /// the pixel has the lowest variance.
+    fn kuwahara(&self, x: u32, y: u32, radius: u32) -> [f32; 3] {
+        let (x, y, radius) = (x as i32, y as i32, radius as i32);
+        let mut best = ([0.0; 3], f32::INFINITY);
+
+        for (sx, sy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            let mut statistics = Statistics::default();
+            for i in 0..=radius {
+                for j in 0..=radius {
+                    statistics.add(self.pixel(x + sx * i, y + sy * j), 1.0);
+                }
+            }
+
+            let (mean, variance) = statistics.mean_variance();
+            if variance < best.1 {
+                best = (mean, variance);
+            }
+        }
+
+        best.0
+    }
+
+    /// Simplified form of Kyprianidis et al., "Image and Video Abstraction
+    /// by Anisotropic Kuwahara Filtering": the neighbourhood is an ellipse
+    /// stretched along the edge, split into sectors whose means are blended
+    /// with weights favouring the sectors of lowest variance.
+    fn anisotropic_kuwahara(&self, x: u32, y: u32, radius: u32) -> [f32; 3] {
+        let orientation = self.orientation.as_ref().unwrap();
+        let index = (y * self.width + x) as usize;
+        let (angle, anisotropy) = (orientation.angle[index], orientation.anisotropy[index]);
+
+        let radius = radius.max(1) as f32;
+        let major = radius * (1.0 + anisotropy);
+        let minor = radius / (1.0 + anisotropy);
+        let (sin, cos) = angle.sin_cos();
+        let extent = major.ceil() as i32;
+
+        let mut sectors: [Statistics; SECTORS] = Default::default();
+        for dy in -extent..=extent {
+            for dx in -extent..=extent {
+                // Offset in the ellipse's frame, scaled to the unit disc.
+                let u = (dx as f32 * cos + dy as f32 * sin) / major;
+                let v = (-dx as f32 * sin + dy as f32 * cos) / minor;
+                let distance = u * u + v * v;
+                if distance > 1.0 {
+                    continue;
+                }
+
+                let color = self.pixel(x as i32 + dx, y as i32 + dy);
+                // Gaussian falloff, down to e^-2 at the rim.
+                let weight = (-2.0 * distance).exp();
+
+                if dx == 0 && dy == 0 {
+                    for sector in sectors.iter_mut() {
+                        sector.add(color, weight);
+                    }
+                    continue;
+                }
+
+                let sector_angle = v.atan2(u).rem_euclid(std::f32::consts::TAU);
+                let sector = (sector_angle / std::f32::consts::TAU * SECTORS as f32) as usize;
+                sectors[sector.min(SECTORS - 1)].add(color, weight);
+            }
+        }
+
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+        for sector in &sectors {
+            if sector.weight <= 0.0 {
+                continue;
+            }
+
+            let (mean, variance) = sector.mean_variance();
+            let weight = 1.0 / (1.0 + variance.sqrt().powf(SECTOR_SHARPNESS));
+
+            for c in 0..3 {
+                sum[c] += mean[c] * weight;
+            }
+            total += weight;
+        }
+
+        sum.map(|value| value / total)
+    }
+
+    fn non_local_means(
+        &self,
+        x: u32,
+        y: u32,
+        search_radius: u32,
+        patch_radius: u32,
+        strength: f32,
+    ) -> [f32; 3] {
+        let (x, y) = (x as i32, y as i32);
+        let (search, patch) = (search_radius as i32, patch_radius as i32);
+        let patch_size = ((2 * patch + 1) * (2 * patch + 1) * 3) as f32;
+        let scale = -1.0 / (strength * strength).max(f32::EPSILON);
+
+        let mut sum = [0.0; 3];
+        let mut total = 0.0;
+        let mut max_weight: f32 = 0.0;
+        for sy in -search..=search {
+            for sx in -search..=search {
+                if sx == 0 && sy == 0 {
+                    continue;
+                }
+
+                let mut distance = 0.0;
+                for py in -patch..=patch {
+                    for px in -patch..=patch {
+                        let a = self.pixel(x + px, y + py);
+                        let b = self.pixel(x + sx + px, y + sy + py);
+                        distance += color_distance(a, b);
+                    }
+                }
+
+                let weight = (distance / patch_size * scale).exp();
+                let color = self.pixel(x + sx, y + sy);
+                for c in 0..3 {
+                    sum[c] += color[c] * weight;
+                }
+                total += weight;
+                max_weight = max_weight.max(weight);
+            }
+        }
+
+        // The pixel itself would always match perfectly, so it is given the
+        // weight of the best other candidate instead.
+        let center = self.pixel(x, y);
+        let max_weight = if total > 0.0 { max_weight } else { 1.0 };
+        for c in 0..3 {
+            sum[c] += center[c] * max_weight;
+        }
+        total += max_weight;
+
+        sum.map(|value| value / total)
+    }
+}
+
+/// Squared Euclidean distance between two colours.
+fn color_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Weighted running sums for the mean and variance of a set of colours.
+#[derive(Default)]
+struct Statistics {
+    sum: [f32; 3],
+    squares: [f32; 3],
+    weight: f32,
+}
+
+impl Statistics {
+    fn add(&mut self, color: [f32; 3], weight: f32) {
+        for (c, value) in color.iter().enumerate() {
+            self.sum[c] += value * weight;
+            self.squares[c] += value * value * weight;
+        }
+        self.weight += weight;
+    }
+
+    /// Mean colour and the variance summed over the channels.
+    fn mean_variance(&self) -> ([f32; 3], f32) {
+        let mean = self.sum.map(|sum| sum / self.weight);
+        let variance = (0..3)
+            .map(|c| (self.squares[c] / self.weight - mean[c] * mean[c]).max(0.0))
+            .sum();
+
+        (mean, variance)
+    }
+}
+
+/// Estimates edge orientation from the smoothed structure tensor of the
+/// luminance. The angle is that of the edge itself, across the gradient.
+fn orientation(data: &[f32], width: u32, height: u32) -> Orientation {
+    let luminance: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|p| (0.299 * p[0] + 0.587 * p[1] + 0.114 * p[2]) * 255.0)
+        .collect();
+    let at = |x: i32, y: i32| {
+        let x = x.clamp(0, width as i32 - 1);
+        let y = y.clamp(0, height as i32 - 1);
+        luminance[(y * width as i32 + x) as usize]
+    };
+
+    let size = luminance.len();
+    let (mut xx, mut xy, mut yy) = (vec![0.0; size], vec![0.0; size], vec![0.0; size]);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x - 1, y)
+                - at(x - 1, y + 1))
+                / 8.0;
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                - at(x - 1, y - 1)
+                - 2.0 * at(x, y - 1)
+                - at(x + 1, y - 1))
+                / 8.0;
+            let index = (y * width as i32 + x) as usize;
+
+            xx[index] = gx * gx;
+            xy[index] = gx * gy;
+            yy[index] = gy * gy;
+        }
+    }
+
+    let xx = blur::gaussian_blur_plane(&xx, width, height, TENSOR_SIGMA);
+    let xy = blur::gaussian_blur_plane(&xy, width, height, TENSOR_SIGMA);
+    let yy = blur::gaussian_blur_plane(&yy, width, height, TENSOR_SIGMA);
+
+    let mut angle = Vec::with_capacity(size);
+    let mut anisotropy = Vec::with_capacity(size);
+    for i in 0..size {
+        let (e, f, g) = (xx[i], xy[i], yy[i]);
+        let root = ((e - g) * (e - g) + 4.0 * f * f).sqrt();
+        let major = (e + g + root) / 2.0;
+        let minor = (e + g - root) / 2.0;
+
+        // Direction of the smaller eigenvector, i.e. along the edge.
+        angle.push((-f).atan2(major - e).rem_euclid(std::f32::consts::PI));
+        anisotropy.push(if major + minor > 0.0 {
+            (major - minor) / (major + minor)
+        } else {
+            0.0
+        });
+    }
+
+    Orientation { angle, anisotropy }
+}
@@ -0,0 +1,415 @@
+use std::sync::OnceLock;
+
+use crate::rng::Rng;
+
+/// Side of the tiled blue-noise threshold map.
+const BLUE_NOISE_SIZE: usize = 64;
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// The blue-noise map is slow to build, so it is built on first use and
+/// shared by every later dither.
+static BLUE_NOISE: OnceLock<Vec<u32>> = OnceLock::new();
+
+pub const GAME_BOY_PALETTE: [[u8; 3]; 4] =
+    [[15, 56, 15], [48, 98, 48], [139, 172, 15], [155, 188, 15]];
+
+pub const CGA_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [0, 0, 170],
+    [0, 170, 0],
+    [0, 170, 170],
+    [170, 0, 0],
+    [170, 0, 170],
+    [170, 85, 0],
+    [170, 170, 170],
+    [85, 85, 85],
+    [85, 85, 255],
+    [85, 255, 85],
+    [85, 255, 255],
+    [255, 85, 85],
+    [255, 85, 255],
+    [255, 255, 85],
+    [255, 255, 255],
+];
+
+/// Colours the image is reduced to.
+#[derive(Clone, PartialEq)]
+pub enum DitherTarget {
+    /// Pure black and white, from the luminance.
+    BlackWhite,
+    /// Evenly spaced levels per channel.
+    Levels(u32),
+    Palette(Vec<[u8; 3]>),
+}
+
+impl DitherTarget {
+    fn nearest(&self, color: [f32; 3]) -> [f32; 3] {
+        match self {
+            DitherTarget::BlackWhite => {
+                let value = if luminance(color) < 127.5 { 0.0 } else { 255.0 };
+                [value; 3]
+            }
+            DitherTarget::Levels(levels) => {
+                let step = 255.0 / ((*levels).max(2) - 1) as f32;
+                color.map(|c| ((c / step).round() * step).clamp(0.0, 255.0))
+            }
+            DitherTarget::Palette(palette) => nearest_color(palette, color)
+                .map(|index| palette[index].map(|c| c as f32))
+                .unwrap_or(color),
+        }
+    }
+
+    /// Typical distance between neighbouring target colours, which scales
+    /// the threshold maps of ordered dithering.
+    fn spread(&self) -> f32 {
+        match self {
+            DitherTarget::BlackWhite => 255.0,
+            DitherTarget::Levels(levels) => 255.0 / ((*levels).max(2) - 1) as f32,
+            DitherTarget::Palette(palette) => 255.0 / (palette.len().max(2) as f32).cbrt(),
+        }
+    }
+}
+
+/// Index of the palette entry closest to `color` in RGB space.
+pub fn nearest_color(palette: &[[u8; 3]], color: [f32; 3]) -> Option<usize> {
+    palette
+        .iter()
+        .map(|entry| {
+            (0..3)
+                .map(|c| (entry[c] as f32 - color[c]).powi(2))
+                .sum::<f32>()
+        })
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+fn luminance(color: [f32; 3]) -> f32 {
+    0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2]
+}
+
+/// Share of the quantisation error passed to the pixel at `(dx, dy)`,
+/// before dividing by the kernel's divisor.
+type KernelEntry = (i32, i32, f32);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DitherMethod {
+    /// Plain nearest-colour mapping.
+    None,
+    FloydSteinberg,
+    JarvisJudiceNinke,
+    Stucki,
+    Atkinson,
+    Sierra,
+    /// Ordered dithering with a Bayer matrix of the given size (2, 4 or 8).
+    Bayer(usize),
+    BlueNoise,
+}
+
+impl DitherMethod {
+    /// Error diffusion kernel as `(dx, dy, weight)` entries and the divisor.
+    /// Atkinson deliberately spreads only three quarters of the error.
+    fn kernel(&self) -> Option<(&'static [KernelEntry], f32)> {
+        match self {
+            DitherMethod::FloydSteinberg => {
+                Some((&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0))
+            }
+            DitherMethod::JarvisJudiceNinke => Some((
+                &[
+                    (1, 0, 7.0),
+                    (2, 0, 5.0),
+                    (-2, 1, 3.0),
+                    (-1, 1, 5.0),
+                    (0, 1, 7.0),
+                    (1, 1, 5.0),
+                    (2, 1, 3.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 3.0),
+                    (0, 2, 5.0),
+                    (1, 2, 3.0),
+                    (2, 2, 1.0),
+                ],
+                48.0,
+            )),
+            DitherMethod::Stucki => Some((
+                &[
+                    (1, 0, 8.0),
+                    (2, 0, 4.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 8.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 4.0),
+                    (1, 2, 2.0),
+                    (2, 2, 1.0),
+                ],
+                42.0,
+            )),
+            DitherMethod::Atkinson => Some((
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            )),
+            DitherMethod::Sierra => Some((
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Reduces the colour channels of an RGBA buffer to `target` in place. With
+/// `serpentine` set, error diffusion runs right to left on every other row.
+pub fn dither(
+    data: &mut [u8],
+    width: u32,
+    height: u32,
+    target: &DitherTarget,
+    method: DitherMethod,
+    serpentine: bool,
+) {
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels: Vec<[f32; 3]> = data
+        .chunks_exact(4)
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+        .collect();
+
+    if *target == DitherTarget::BlackWhite {
+        for pixel in pixels.iter_mut() {
+            *pixel = [luminance(*pixel); 3];
+        }
+    }
+
+    match method {
+        DitherMethod::Bayer(size) => {
+            let matrix = bayer_matrix(size);
+            let cells = (size * size) as f32;
+            ordered(&mut pixels, width, target, |x, y| {
+                (matrix[(y % size) * size + x % size] as f32 + 0.5) / cells - 0.5
+            });
+        }
+        DitherMethod::BlueNoise => {
+            let ranks = BLUE_NOISE.get_or_init(|| blue_noise(BLUE_NOISE_SIZE, 0));
+            let cells = ranks.len() as f32;
+            ordered(&mut pixels, width, target, |x, y| {
+                let index = (y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE;
+                (ranks[index] as f32 + 0.5) / cells - 0.5
+            });
+        }
+        _ => diffuse(&mut pixels, width, height, target, method, serpentine),
+    }
+
+    for (pixel, color) in data.chunks_exact_mut(4).zip(pixels) {
+        for (value, channel) in pixel.iter_mut().zip(color) {
+            *value = channel.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn diffuse(
+    pixels: &mut [[f32; 3]],
+    width: usize,
+    height: usize,
+    target: &DitherTarget,
+    method: DitherMethod,
+    serpentine: bool,
+) {
+    let (kernel, divisor) = method.kernel().unwrap_or((&[], 1.0));
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        let direction = if reverse { -1 } else { 1 };
+
+        for i in 0..width {
+            let x = if reverse { width - 1 - i } else { i };
+            let old = pixels[y * width + x];
+            let new = target.nearest(old);
+            pixels[y * width + x] = new;
+
+            for (dx, dy, weight) in kernel {
+                let nx = x as i32 + dx * direction;
+                let ny = y + *dy as usize;
+                if nx < 0 || nx >= width as i32 || ny >= height {
+                    continue;
+                }
+
+                let neighbour = &mut pixels[ny * width + nx as usize];
+                for c in 0..3 {
+                    neighbour[c] += (old[c] - new[c]) * weight / divisor;
+                }
+            }
+        }
+    }
+}
+
+/// Offsets each pixel by a threshold in -0.5..0.5, scaled to the gap
+/// between target colours, before mapping it to the nearest one.
+fn ordered(
+    pixels: &mut [[f32; 3]],
+    width: usize,
+    target: &DitherTarget,
+    threshold: impl Fn(usize, usize) -> f32,
+) {
+    let spread = target.spread();
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let offset = threshold(i % width, i / width) * spread;
+        *pixel = target.nearest(pixel.map(|c| c + offset));
+    }
+}
+
+/// Bayer index matrix of a power-of-two size, built recursively from the
+/// 2x2 one.
+fn bayer_matrix(size: usize) -> Vec<u32> {
+    let mut matrix = vec![0];
+    let mut current = 1;
+
+    while current < size {
+        let next = current * 2;
+        let mut expanded = vec![0; next * next];
+        for y in 0..next {
+            for x in 0..next {
+                let base = 4 * matrix[(y % current) * current + x % current];
+                let quadrant = match (x / current, y / current) {
+                    (0, 0) => 0,
+                    (1, 1) => 1,
+                    (1, 0) => 2,
+                    _ => 3,
+                };
+                expanded[y * next + x] = base + quadrant;
+            }
+        }
+
+        matrix = expanded;
+        current = next;
+    }
+
+    matrix
+}
+
+/// Rank of every cell of a tileable blue-noise map, built with Ulichney's
+/// void-and-cluster method. Cells are ranked by when they would turn on as
+/// the threshold rises, so the ranks spread evenly at every density.
+fn blue_noise(size: usize, seed: u64) -> Vec<u32> {
+    let count = size * size;
+
+    // Gaussian energy contributed by a set cell to each toroidal offset.
+    let kernel: Vec<f32> = (0..count)
+        .map(|i| {
+            let distance = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (distance(i % size), distance(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+        })
+        .collect();
+
+    let mut energy = vec![0.0; count];
+    let toggle = |energy: &mut [f32], cell: usize, sign: f32| {
+        let (cx, cy) = (cell % size, cell / size);
+        for (i, value) in energy.iter_mut().enumerate() {
+            let dx = (i % size + size - cx) % size;
+            let dy = (i / size + size - cy) % size;
+            *value += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |pattern: &[bool], energy: &[f32]| {
+        (0..count)
+            .filter(|i| pattern[*i])
+            .max_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+            .unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..count)
+            .filter(|i| !pattern[*i])
+            .min_by(|a, b| energy[*a].total_cmp(&energy[*b]))
+            .unwrap()
+    };
+
+    // Start from a random tenth of the cells and move cells from clusters
+    // into voids until the pattern settles.
+    let mut rng = Rng::new(seed);
+    let mut pattern = vec![false; count];
+    let initial = (count / 10).max(1);
+    let mut placed = 0;
+    while placed < initial {
+        let cell = ((rng.next_f64() * count as f64) as usize).min(count - 1);
+        if !pattern[cell] {
+            pattern[cell] = true;
+            toggle(&mut energy, cell, 1.0);
+            placed += 1;
+        }
+    }
+
+    for _ in 0..count {
+        let cluster = tightest_cluster(&pattern, &energy);
+        pattern[cluster] = false;
+        toggle(&mut energy, cluster, -1.0);
+
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        toggle(&mut energy, void, 1.0);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+
+    // Rank the initial cells by removing them from the tightest cluster.
+    let (mut ones, mut ones_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&ones, &ones_energy);
+        ones[cluster] = false;
+        toggle(&mut ones_energy, cluster, -1.0);
+        ranks[cluster] = rank as u32;
+    }
+
+    // Rank the rest by filling the largest void each time.
+    for rank in initial..count {
+        let void = largest_void(&pattern, &energy);
+        pattern[void] = true;
+        toggle(&mut energy, void, 1.0);
+        ranks[void] = rank as u32;
+    }
+
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bayer_4x4_matches_the_standard_matrix() {
+        #[rustfmt::skip]
+        let expected = [
+            0, 8, 2, 10,
+            12, 4, 14, 6,
+            3, 11, 1, 9,
+            15, 7, 13, 5,
+        ];
+
+        assert_eq!(bayer_matrix(4), expected);
+    }
+}
@@ -0,0 +1,450 @@
+#[derive(Clone, Copy, PartialEq)]
+pub enum DrawTool {
+    Brush,
+    /// Paints transparency instead of colour.
+    Eraser,
+    Line,
+    Rectangle,
+    Ellipse,
+    Fill,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct DrawSettings {
+    pub tool: DrawTool,
+    /// Brush diameter in pixels.
+    pub size: f32,
+    /// Share of the brush radius painted at full strength before the edge
+    /// starts to fade.
+    pub hardness: f32,
+    pub opacity: f32,
+    /// Fill rectangles and ellipses instead of outlining them.
+    pub filled: bool,
+    /// Largest per-channel difference from the clicked pixel that a bucket
+    /// fill spreads into.
+    pub tolerance: u8,
+    pub connectivity: Connectivity,
+}
+
+impl Default for DrawSettings {
+    fn default() -> Self {
+        Self {
+            tool: DrawTool::Brush,
+            size: 8.0,
+            hardness: 0.8,
+            opacity: 1.0,
+            filled: false,
+            tolerance: 32,
+            connectivity: Connectivity::Four,
+        }
+    }
+}
+
+/// Pixel rectangle, left and top inclusive, right and bottom exclusive.
+type Bounds = (u32, u32, u32, u32);
+
+fn union(a: Option<Bounds>, b: Bounds) -> Bounds {
+    match a {
+        Some(a) => (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)),
+        None => b,
+    }
+}
+
+fn segment_distance(x: f64, y: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((x - from.0) * dx + (y - from.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (x - from.0 - t * dx).hypot(y - from.1 - t * dy)
+}
+
+/// One brush stroke, shape or fill in progress. Coverage is accumulated
+/// separately and composited over a snapshot of the image taken when the
+/// stroke began, so overlapping dabs do not build up past the opacity and
+/// shapes can be redrawn as the pointer moves.
+pub struct Stroke {
+    settings: DrawSettings,
+    color: [u8; 4],
+    width: u32,
+    height: u32,
+    /// Normalised RGBA samples from before the stroke.
+    original: Vec<f32>,
+    coverage: Vec<f32>,
+    start: (f64, f64),
+    last: (f64, f64),
+    /// Area holding non-zero coverage.
+    bounds: Option<Bounds>,
+    /// Area changed since the last composite.
+    dirty: Option<Bounds>,
+}
+
+/// The pixels a finished stroke changed, as they were before it, so the
+/// stroke can be undone.
+pub struct Patch {
+    bounds: Bounds,
+    data: Vec<f32>,
+}
+
+impl Patch {
+    /// Memory held by the saved pixels.
+    pub fn size(&self) -> usize {
+        self.data.len() * std::mem::size_of::<f32>()
+    }
+
+    /// Writes the saved pixels back into an RGBA buffer `width` pixels
+    /// wide.
+    pub fn restore(&self, data: &mut [f32], width: u32) {
+        let (left, top, right, _) = self.bounds;
+        let row_len = ((right - left) * 4) as usize;
+
+        for (y, row) in (top..).zip(self.data.chunks_exact(row_len)) {
+            let start = ((y * width + left) * 4) as usize;
+            data[start..start + row_len].copy_from_slice(row);
+        }
+    }
+}
+
+impl Stroke {
+    pub fn new(
+        data: &[f32],
+        width: u32,
+        height: u32,
+        settings: DrawSettings,
+        color: [u8; 4],
+        x: f64,
+        y: f64,
+    ) -> Self {
+        let mut stroke = Self {
+            settings,
+            color,
+            width,
+            height,
+            original: data.to_vec(),
+            coverage: vec![0.0; (width * height) as usize],
+            start: (x, y),
+            last: (x, y),
+            bounds: None,
+            dirty: None,
+        };
+
+        match settings.tool {
+            DrawTool::Fill => stroke.flood_fill(x, y),
+            _ => stroke.pointer_move(x, y),
+        }
+
+        stroke
+    }
+
+    /// Extends a freehand stroke to `(x, y)`, or redraws a shape from the
+    /// starting point to it.
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        let point = (x, y);
+
+        match self.settings.tool {
+            DrawTool::Brush | DrawTool::Eraser => self.cover_segment(self.last, point),
+            DrawTool::Line => {
+                self.clear();
+                self.cover_segment(self.start, point);
+            }
+            DrawTool::Rectangle => {
+                self.clear();
+                self.cover_rectangle(self.start, point);
+            }
+            DrawTool::Ellipse => {
+                self.clear();
+                self.cover_ellipse(self.start, point);
+            }
+            DrawTool::Fill => {}
+        }
+
+        self.last = point;
+    }
+
+    /// Writes the changed part of the stroke into `data`, optionally
+    /// limited by a selection mask with one coverage value per pixel.
+    pub fn composite(&mut self, data: &mut [f32], mask: Option<&[f32]>) {
+        let (left, top, right, bottom) = match self.dirty.take() {
+            Some(dirty) => dirty,
+            None => return,
+        };
+        let erase = self.settings.tool == DrawTool::Eraser;
+        let opacity = self.settings.opacity.clamp(0.0, 1.0);
+        let source_alpha = self.color[3] as f32 / 255.0;
+
+        for y in top..bottom {
+            for x in left..right {
+                let pixel_index = (y * self.width + x) as usize;
+                let index = pixel_index * 4;
+                let mut amount = self.coverage[pixel_index] * opacity;
+                if let Some(mask) = mask {
+                    amount *= mask[pixel_index];
+                }
+
+                let original = &self.original[index..index + 4];
+                let pixel = &mut data[index..index + 4];
+                pixel.copy_from_slice(original);
+                if amount <= 0.0 {
+                    continue;
+                }
+
+                if erase {
+                    pixel[3] = original[3] * (1.0 - amount);
+                    continue;
+                }
+
+                let alpha = source_alpha * amount;
+                let backdrop_alpha = original[3];
+                let out_alpha = alpha + backdrop_alpha * (1.0 - alpha);
+                if out_alpha <= 0.0 {
+                    continue;
+                }
+                for c in 0..3 {
+                    let value = self.color[c] as f32 / 255.0 * alpha
+                        + original[c] * backdrop_alpha * (1.0 - alpha);
+                    pixel[c] = (value / out_alpha).clamp(0.0, 1.0);
+                }
+                pixel[3] = out_alpha;
+            }
+        }
+    }
+
+    /// Ends the stroke, returning the pixels it changed in `data` as they
+    /// were before, or `None` if it left the image as it was.
+    pub fn into_patch(self, data: &[f32]) -> Option<Patch> {
+        let (left, top, right, bottom) = self.bounds?;
+        let row_len = ((right - left) * 4) as usize;
+        let rows = (top..bottom).map(|y| ((y * self.width + left) * 4) as usize);
+
+        if rows
+            .clone()
+            .all(|start| data[start..start + row_len] == self.original[start..start + row_len])
+        {
+            return None;
+        }
+
+        Some(Patch {
+            bounds: (left, top, right, bottom),
+            data: rows
+                .flat_map(|start| &self.original[start..start + row_len])
+                .copied()
+                .collect(),
+        })
+    }
+
+    /// Removes all coverage, marking its area for the next composite.
+    fn clear(&mut self) {
+        if let Some((left, top, right, bottom)) = self.bounds.take() {
+            for y in top..bottom {
+                let row = (y * self.width) as usize;
+                self.coverage[row + left as usize..row + right as usize].fill(0.0);
+            }
+            self.dirty = Some(union(self.dirty, (left, top, right, bottom)));
+        }
+    }
+
+    /// Raises coverage to `coverage(x, y)`, evaluated at pixel centres
+    /// inside the given area in image coordinates.
+    fn cover(&mut self, area: (f64, f64, f64, f64), coverage: impl Fn(f64, f64) -> f32) {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let left = area.0.floor().clamp(0.0, width) as u32;
+        let top = area.1.floor().clamp(0.0, height) as u32;
+        let right = area.2.ceil().clamp(0.0, width) as u32;
+        let bottom = area.3.ceil().clamp(0.0, height) as u32;
+        if left >= right || top >= bottom {
+            return;
+        }
+
+        for y in top..bottom {
+            for x in left..right {
+                let value = coverage(x as f64 + 0.5, y as f64 + 0.5);
+                let index = (y * self.width + x) as usize;
+                if value > self.coverage[index] {
+                    self.coverage[index] = value;
+                }
+            }
+        }
+
+        let bounds = (left, top, right, bottom);
+        self.bounds = Some(union(self.bounds, bounds));
+        self.dirty = Some(union(self.dirty, bounds));
+    }
+
+    fn radius(&self) -> f64 {
+        (self.settings.size as f64 / 2.0).max(0.5)
+    }
+
+    fn cover_segment(&mut self, from: (f64, f64), to: (f64, f64)) {
+        let (radius, hardness) = (self.radius(), self.settings.hardness);
+        let reach = radius + 1.0;
+        let area = (
+            from.0.min(to.0) - reach,
+            from.1.min(to.1) - reach,
+            from.0.max(to.0) + reach,
+            from.1.max(to.1) + reach,
+        );
+
+        self.cover(area, |x, y| {
+            brush_coverage(segment_distance(x, y, from, to), radius, hardness)
+        });
+    }
+
+    fn cover_rectangle(&mut self, from: (f64, f64), to: (f64, f64)) {
+        let (left, right) = (from.0.min(to.0), from.0.max(to.0));
+        let (top, bottom) = (from.1.min(to.1), from.1.max(to.1));
+
+        if self.settings.filled {
+            let (center_x, center_y) = ((left + right) / 2.0, (top + bottom) / 2.0);
+            let (half_width, half_height) = ((right - left) / 2.0, (bottom - top) / 2.0);
+            self.cover(
+                (left - 1.0, top - 1.0, right + 1.0, bottom + 1.0),
+                |x, y| {
+                    let distance =
+                        ((x - center_x).abs() - half_width).max((y - center_y).abs() - half_height);
+                    (0.5 - distance).clamp(0.0, 1.0) as f32
+                },
+            );
+            return;
+        }
+
+        let (radius, hardness) = (self.radius(), self.settings.hardness);
+        let reach = radius + 1.0;
+        let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+        self.cover(
+            (left - reach, top - reach, right + reach, bottom + reach),
+            |x, y| {
+                let distance = (0..4)
+                    .map(|i| segment_distance(x, y, corners[i], corners[(i + 1) % 4]))
+                    .fold(f64::INFINITY, f64::min);
+                brush_coverage(distance, radius, hardness)
+            },
+        );
+    }
+
+    /// Covers the ellipse inscribed in the rectangle between the two
+    /// points.
+    fn cover_ellipse(&mut self, from: (f64, f64), to: (f64, f64)) {
+        let (center_x, center_y) = ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0);
+        let radius_x = ((to.0 - from.0).abs() / 2.0).max(0.5);
+        let radius_y = ((to.1 - from.1).abs() / 2.0).max(0.5);
+        // Signed distance to the outline, approximated by the implicit
+        // function over its gradient, which is accurate near the curve.
+        let distance = move |x: f64, y: f64| {
+            let (dx, dy) = (x - center_x, y - center_y);
+            let value = dx * dx / (radius_x * radius_x) + dy * dy / (radius_y * radius_y) - 1.0;
+            let gradient = (2.0 * dx / (radius_x * radius_x))
+                .hypot(2.0 * dy / (radius_y * radius_y))
+                .max(f64::EPSILON);
+            value / gradient
+        };
+
+        let (radius, hardness) = (self.radius(), self.settings.hardness);
+        let (filled, reach) = if self.settings.filled {
+            (true, 1.0)
+        } else {
+            (false, radius + 1.0)
+        };
+        let area = (
+            center_x - radius_x - reach,
+            center_y - radius_y - reach,
+            center_x + radius_x + reach,
+            center_y + radius_y + reach,
+        );
+
+        self.cover(area, |x, y| {
+            if filled {
+                (0.5 - distance(x, y)).clamp(0.0, 1.0) as f32
+            } else {
+                brush_coverage(distance(x, y).abs(), radius, hardness)
+            }
+        });
+    }
+
+    /// Covers the region of similar colour connected to the clicked pixel.
+    fn flood_fill(&mut self, x: f64, y: f64) {
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return;
+        }
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let seed_index = (y as i64 * width + x as i64) as usize;
+        let seed: [f32; 4] = self.original[seed_index * 4..seed_index * 4 + 4]
+            .try_into()
+            .unwrap();
+        // Tolerance is in levels, the samples are normalised.
+        let tolerance = self.settings.tolerance as f32 / 255.0;
+        let offsets: &[(i64, i64)] = match self.settings.connectivity {
+            Connectivity::Four => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+            Connectivity::Eight => &[
+                (1, 0),
+                (-1, 0),
+                (0, 1),
+                (0, -1),
+                (1, 1),
+                (1, -1),
+                (-1, 1),
+                (-1, -1),
+            ],
+        };
+
+        let mut bounds = (x as u32, y as u32, x as u32 + 1, y as u32 + 1);
+        let mut stack = vec![(x as i64, y as i64)];
+        self.coverage[seed_index] = 1.0;
+        while let Some((x, y)) = stack.pop() {
+            bounds = union(
+                Some(bounds),
+                (x as u32, y as u32, x as u32 + 1, y as u32 + 1),
+            );
+
+            for (dx, dy) in offsets {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+
+                let index = (ny * width + nx) as usize;
+                if self.coverage[index] > 0.0 {
+                    continue;
+                }
+
+                let pixel = &self.original[index * 4..index * 4 + 4];
+                let similar = pixel
+                    .iter()
+                    .zip(seed)
+                    .all(|(value, seed)| (value - seed).abs() <= tolerance);
+                if similar {
+                    self.coverage[index] = 1.0;
+                    stack.push((nx, ny));
+                }
+            }
+        }
+
+        self.bounds = Some(bounds);
+        self.dirty = Some(bounds);
+    }
+}
+
+/// Brush strength at `distance` from the brush centre: full inside the
+/// hard core, fading smoothly to the rim, with a one-pixel anti-aliased
+/// edge.
+fn brush_coverage(distance: f64, radius: f64, hardness: f32) -> f32 {
+    let core = radius * hardness.clamp(0.0, 1.0) as f64;
+    let edge = (radius + 0.5 - distance).clamp(0.0, 1.0);
+    let soft = if distance <= core || radius <= core {
+        1.0
+    } else {
+        let t = ((distance - core) / (radius - core)).min(1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    };
+
+    edge.min(soft) as f32
+}
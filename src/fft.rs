@@ -0,0 +1,541 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+use crate::buffer::Sample;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// `e^(i * angle)`.
+    pub fn from_angle(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin)
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Self::new(self.re * factor, self.im * factor)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+/// Unnormalised discrete Fourier transform of `buffer` in place. The
+/// inverse transform uses a positive exponent and is not divided by the
+/// length. Power-of-two lengths use radix-2 Cooley-Tukey, anything else
+/// Bluestein's algorithm.
+pub fn fft(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    if n <= 1 {
+        return;
+    }
+
+    if n.is_power_of_two() {
+        radix2(buffer, inverse);
+    } else {
+        bluestein(buffer, inverse);
+    }
+}
+
+fn radix2(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    let bits = n.trailing_zeros();
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut length = 2;
+    while length <= n {
+        let step = Complex::from_angle(sign * 2.0 * PI / length as f64);
+
+        for start in (0..n).step_by(length) {
+            let mut twiddle = Complex::new(1.0, 0.0);
+            for k in 0..length / 2 {
+                let even = buffer[start + k];
+                let odd = buffer[start + k + length / 2] * twiddle;
+
+                buffer[start + k] = even + odd;
+                buffer[start + k + length / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+
+        length *= 2;
+    }
+}
+
+/// Expresses a transform of any length as a convolution, which is then
+/// computed with power-of-two transforms.
+fn bluestein(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    let m = (2 * n - 1).next_power_of_two();
+    let sign = if inverse { 1.0 } else { -1.0 };
+
+    // `k * k` is reduced modulo `2n` to keep the angles small and precise.
+    let chirp: Vec<Complex> = (0..n)
+        .map(|k| {
+            let k2 = (k as u64 * k as u64 % (2 * n as u64)) as f64;
+            Complex::from_angle(sign * PI * k2 / n as f64)
+        })
+        .collect();
+
+    let mut a = vec![Complex::default(); m];
+    for k in 0..n {
+        a[k] = buffer[k] * chirp[k];
+    }
+
+    let mut b = vec![Complex::default(); m];
+    b[0] = chirp[0].conj();
+    for k in 1..n {
+        b[k] = chirp[k].conj();
+        b[m - k] = chirp[k].conj();
+    }
+
+    radix2(&mut a, false);
+    radix2(&mut b, false);
+    for (x, y) in a.iter_mut().zip(&b) {
+        *x = *x * *y;
+    }
+    radix2(&mut a, true);
+
+    let scale = 1.0 / m as f64;
+    for (k, value) in buffer.iter_mut().enumerate() {
+        *value = a[k].scale(scale) * chirp[k];
+    }
+}
+
+/// Two-dimensional transform of a row-major plane in place. The inverse is
+/// normalised, so a forward and inverse transform round-trip.
+pub fn fft_2d(plane: &mut [Complex], width: u32, height: u32, inverse: bool) {
+    let (width, height) = (width as usize, height as usize);
+
+    for row in plane.chunks_exact_mut(width) {
+        fft(row, inverse);
+    }
+
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = plane[y * width + x];
+        }
+        fft(&mut column, inverse);
+        for (y, value) in column.iter().enumerate() {
+            plane[y * width + x] = *value;
+        }
+    }
+
+    if inverse {
+        let scale = 1.0 / (width * height) as f64;
+        for value in plane.iter_mut() {
+            *value = value.scale(scale);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterKind {
+    None,
+    LowPass,
+    HighPass,
+    BandPass,
+    BandStop,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterShape {
+    Ideal,
+    Butterworth,
+    Gaussian,
+}
+
+/// Radial filter over the spectrum. Frequencies are in cycles per pixel,
+/// from 0.0 at DC to 0.5 at the Nyquist limit along each axis.
+#[derive(Clone, Copy, PartialEq)]
+pub struct FrequencyFilter {
+    pub kind: FilterKind,
+    pub shape: FilterShape,
+    /// Cut-off frequency, or the centre of the band for band filters.
+    pub cutoff: f64,
+    pub band_width: f64,
+    /// Order of the Butterworth filter.
+    pub order: u32,
+}
+
+impl Default for FrequencyFilter {
+    fn default() -> Self {
+        Self {
+            kind: FilterKind::None,
+            shape: FilterShape::Gaussian,
+            cutoff: 0.1,
+            band_width: 0.05,
+            order: 2,
+        }
+    }
+}
+
+impl FrequencyFilter {
+    fn low_pass(&self, distance: f64) -> f64 {
+        let cutoff = self.cutoff.max(f64::EPSILON);
+
+        match self.shape {
+            FilterShape::Ideal => (distance <= cutoff) as u8 as f64,
+            FilterShape::Butterworth => {
+                1.0 / (1.0 + (distance / cutoff).powi(2 * self.order as i32))
+            }
+            FilterShape::Gaussian => (-distance * distance / (2.0 * cutoff * cutoff)).exp(),
+        }
+    }
+
+    fn band_stop(&self, distance: f64) -> f64 {
+        let (center, width) = (self.cutoff, self.band_width.max(f64::EPSILON));
+        let offset = distance * distance - center * center;
+
+        match self.shape {
+            FilterShape::Ideal => ((distance - center).abs() > width / 2.0) as u8 as f64,
+            FilterShape::Butterworth => {
+                if offset == 0.0 {
+                    0.0
+                } else {
+                    1.0 / (1.0 + (distance * width / offset).powi(2 * self.order as i32))
+                }
+            }
+            FilterShape::Gaussian => {
+                if distance == 0.0 {
+                    1.0
+                } else {
+                    1.0 - (-(offset / (distance * width)).powi(2)).exp()
+                }
+            }
+        }
+    }
+
+    /// Gain applied at the given radial frequency.
+    pub fn gain(&self, distance: f64) -> f64 {
+        match self.kind {
+            FilterKind::None => 1.0,
+            FilterKind::LowPass => self.low_pass(distance),
+            FilterKind::HighPass => 1.0 - self.low_pass(distance),
+            FilterKind::BandPass => 1.0 - self.band_stop(distance),
+            FilterKind::BandStop => self.band_stop(distance),
+        }
+    }
+}
+
+/// Signed frequency index of coefficient `index` in a transform of length
+/// `size`.
+fn frequency(index: usize, size: usize) -> f64 {
+    if index <= size / 2 {
+        index as f64
+    } else {
+        index as f64 - size as f64
+    }
+}
+
+/// Maps between transform order, with DC at index 0, and display order,
+/// with DC in the centre.
+pub fn shift(index: u32, size: u32) -> u32 {
+    (index + size / 2) % size
+}
+
+pub fn unshift(index: u32, size: u32) -> u32 {
+    (index + size - size / 2) % size
+}
+
+/// Per-channel spectrum of an RGBA image. The alpha channel is kept as is
+/// and restored by the inverse transform.
+pub struct Spectrum {
+    pub width: u32,
+    pub height: u32,
+    channels: [Vec<Complex>; 3],
+    /// Alpha as a fraction of full scale.
+    alpha: Vec<f32>,
+}
+
+impl Spectrum {
+    /// Transforms each colour channel, measured in levels from 0 to 255
+    /// whatever the sample type.
+    pub fn new<S: Sample>(data: &[S], width: u32, height: u32) -> Self {
+        let channels = [0, 1, 2].map(|channel| {
+            let mut plane: Vec<Complex> = data
+                .iter()
+                .skip(channel)
+                .step_by(4)
+                .map(|value| Complex::new(value.to_unit() as f64 * 255.0, 0.0))
+                .collect();
+            fft_2d(&mut plane, width, height, false);
+            plane
+        });
+
+        Self {
+            width,
+            height,
+            channels,
+            alpha: data
+                .iter()
+                .skip(3)
+                .step_by(4)
+                .map(|value| value.to_unit())
+                .collect(),
+        }
+    }
+
+    /// Filter gain times mask value for every coefficient, in transform
+    /// order.
+    pub fn gains(&self, filter: &FrequencyFilter, mask: &[f32]) -> Vec<f64> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut gains = Vec::with_capacity(width * height);
+
+        for v in 0..height {
+            let fv = frequency(v, height) / height as f64;
+            for u in 0..width {
+                let fu = frequency(u, width) / width as f64;
+                gains.push(filter.gain(fu.hypot(fv)) * mask[v * width + u] as f64);
+            }
+        }
+
+        gains
+    }
+
+    /// Transforms back to an RGBA image after scaling each coefficient by
+    /// the matching gain.
+    pub fn inverse<S: Sample>(&self, gains: &[f64]) -> Vec<S> {
+        let mut data = vec![S::default(); self.alpha.len() * 4];
+
+        for (channel, coefficients) in self.channels.iter().enumerate() {
+            let mut plane: Vec<Complex> = coefficients
+                .iter()
+                .zip(gains)
+                .map(|(value, gain)| value.scale(*gain))
+                .collect();
+            fft_2d(&mut plane, self.width, self.height, true);
+
+            for (i, value) in plane.iter().enumerate() {
+                data[i * 4 + channel] = S::from_unit((value.re / 255.0).clamp(0.0, 1.0) as f32);
+            }
+        }
+        for (i, alpha) in self.alpha.iter().enumerate() {
+            data[i * 4 + 3] = S::from_unit(*alpha);
+        }
+
+        data
+    }
+
+    /// Renders `log(1 + |F|)` per channel, with gains applied and DC in the
+    /// centre, normalised to the largest value.
+    pub fn magnitude_image(&self, gains: &[f64]) -> Vec<u8> {
+        let log_magnitude: Vec<[f64; 3]> = (0..gains.len())
+            .map(|i| [0, 1, 2].map(|c| (self.channels[c][i].norm() * gains[i]).ln_1p()))
+            .collect();
+        let max = log_magnitude
+            .iter()
+            .flatten()
+            .fold(0.0f64, |max, value| max.max(*value))
+            .max(f64::EPSILON);
+
+        self.render(|i| log_magnitude[i].map(|value| value / max))
+    }
+
+    /// Renders the phase per channel, mapping -pi..pi onto the full range.
+    pub fn phase_image(&self) -> Vec<u8> {
+        self.render(|i| [0, 1, 2].map(|c| (self.channels[c][i].arg() + PI) / (2.0 * PI)))
+    }
+
+    /// Builds a shifted RGBA image from per-coefficient colours in 0.0..=1.0.
+    fn render(&self, color: impl Fn(usize) -> [f64; 3]) -> Vec<u8> {
+        let mut data = vec![255; (self.width * self.height * 4) as usize];
+
+        for v in 0..self.height {
+            for u in 0..self.width {
+                let source = (v * self.width + u) as usize;
+                let target =
+                    ((shift(v, self.height) * self.width + shift(u, self.width)) * 4) as usize;
+
+                for (value, channel) in data[target..target + 3].iter_mut().zip(color(source)) {
+                    *value = (channel * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+
+        data
+    }
+}
+
+/// Clears a disc of the spectral mask, given in display coordinates, along
+/// with its mirror image through DC so the filtered image stays real.
+pub fn paint_mask(mask: &mut [f32], width: u32, height: u32, x: f64, y: f64, radius: f64) {
+    let extent = radius.ceil() as i64;
+    let (cx, cy) = (x.floor() as i64, y.floor() as i64);
+
+    for dy in -extent..=extent {
+        for dx in -extent..=extent {
+            if ((dx * dx + dy * dy) as f64) > radius * radius {
+                continue;
+            }
+
+            let (px, py) = (cx + dx, cy + dy);
+            if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+                continue;
+            }
+
+            let u = unshift(px as u32, width);
+            let v = unshift(py as u32, height);
+            mask[(v * width + u) as usize] = 0.0;
+
+            let (mirror_u, mirror_v) = ((width - u) % width, (height - v) % height);
+            mask[(mirror_v * width + mirror_u) as usize] = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A signal with no symmetry, so mistakes in ordering show up.
+    fn signal(n: usize) -> Vec<Complex> {
+        (0..n)
+            .map(|i| Complex::new((i * i % 7) as f64 - 3.0, (i % 3) as f64))
+            .collect()
+    }
+
+    fn naive_dft(input: &[Complex]) -> Vec<Complex> {
+        let n = input.len();
+        (0..n)
+            .map(|k| {
+                input
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |sum, (i, value)| {
+                        let angle = -2.0 * PI * (i * k) as f64 / n as f64;
+                        sum + *value * Complex::from_angle(angle)
+                    })
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[Complex], expected: &[Complex]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, b) in actual.iter().zip(expected) {
+            assert!((*a - *b).norm() < 1e-9, "{}+{}i", a.re - b.re, a.im - b.im);
+        }
+    }
+
+    fn round_trip(n: usize) {
+        let input = signal(n);
+        let mut buffer = input.clone();
+        fft(&mut buffer, false);
+        fft(&mut buffer, true);
+
+        let output: Vec<Complex> = buffer
+            .iter()
+            .map(|value| value.scale(1.0 / n as f64))
+            .collect();
+        assert_close(&output, &input);
+    }
+
+    #[test]
+    fn power_of_two_round_trip() {
+        round_trip(16);
+    }
+
+    #[test]
+    fn other_length_round_trip() {
+        round_trip(12);
+    }
+
+    #[test]
+    fn radix2_matches_naive_dft() {
+        let input = signal(8);
+        let mut buffer = input.clone();
+        fft(&mut buffer, false);
+
+        assert_close(&buffer, &naive_dft(&input));
+    }
+
+    #[test]
+    fn bluestein_matches_naive_dft() {
+        for n in [3, 5, 12] {
+            let input = signal(n);
+            let mut buffer = input.clone();
+            fft(&mut buffer, false);
+
+            assert_close(&buffer, &naive_dft(&input));
+        }
+    }
+
+    #[test]
+    fn fft_2d_round_trip() {
+        let (width, height) = (6, 4);
+        let input = signal(width * height);
+        let mut plane = input.clone();
+        fft_2d(&mut plane, width as u32, height as u32, false);
+        fft_2d(&mut plane, width as u32, height as u32, true);
+
+        // The inverse is normalised.
+        assert_close(&plane, &input);
+    }
+
+    #[test]
+    fn paint_mask_clears_the_conjugate_too() {
+        let (width, height) = (8, 6);
+        let mut mask = vec![1.0; width * height];
+        paint_mask(&mut mask, width as u32, height as u32, 5.5, 4.5, 0.0);
+
+        let u = unshift(5, width as u32) as usize;
+        let v = unshift(4, height as u32) as usize;
+        let (mirror_u, mirror_v) = ((width - u) % width, (height - v) % height);
+        for (index, value) in mask.iter().enumerate() {
+            let cleared = index == v * width + u || index == mirror_v * width + mirror_u;
+            assert_eq!(*value, if cleared { 0.0 } else { 1.0 }, "index {index}");
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use gloo_events::EventListener;
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, Event, File, HtmlAnchorElement, HtmlInputElement, Url};
+use yew::Callback;
+
+/// Returns the first file picked in the `<input type="file">` that fired
+/// `event`, if any.
+pub fn selected_file(event: &Event) -> Option<File> {
+    let target = event.target()?;
+    let target: HtmlInputElement = target.dyn_into().ok()?;
+
+    target.files()?.get(0)
+}
+
+/// Reads the whole file and emits its bytes once loading finishes.
+pub fn read_file(file: &File, callback: Callback<Vec<u8>>) {
+    let file_reader = web_sys::FileReader::new().unwrap();
+    file_reader.read_as_array_buffer(file).unwrap();
+    let listener = EventListener::new(&file_reader, "load", move |event| {
+        let target = event.target().unwrap();
+        let target: web_sys::FileReader = target.dyn_into().unwrap();
+        let result = target.result().unwrap();
+        let array = Uint8Array::new(&result);
+
+        callback.emit(array.to_vec());
+    });
+    listener.forget();
+}
+
+/// Offers `bytes` to the user as a download named `name`.
+pub fn download(name: &str, mime: &str, bytes: &[u8]) {
+    let parts = Array::of1(&Uint8Array::from(bytes));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options).unwrap();
+    let url = Url::create_object_url_with_blob(&blob).unwrap();
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let anchor: HtmlAnchorElement = document.create_element("a").unwrap().dyn_into().unwrap();
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+
+    Url::revoke_object_url(&url).unwrap();
+}
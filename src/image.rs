@@ -1,16 +1,57 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
     io::Cursor,
+    mem,
     ops::{Add, Div, Mul, Sub},
 };
 
-use image::{io::Reader, DynamicImage};
-use wasm_bindgen::{Clamped, JsCast};
+use gloo_events::{EventListener, EventListenerOptions};
+use image::{io::Reader, DynamicImage, ImageOutputFormat, RgbaImage};
+use wasm_bindgen::{closure::Closure, Clamped, JsCast, JsValue};
 use web_sys::{
-    CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement, HtmlSelectElement, ImageData,
+    CanvasPattern, CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement,
+    HtmlSelectElement, HtmlTextAreaElement, ImageData, Touch, WheelEvent,
 };
 use yew::prelude::*;
 
+use crate::alpha;
+use crate::arithmetic::{self, ImageOperation, OperandFit};
+use crate::bitplane;
+use crate::blur::{self, UnsharpMask};
+use crate::buffer::{self, Buffer};
+use crate::channels::{self, ChannelMixer, MixerPreset};
+use crate::chroma::{self, ChromaKey};
+use crate::color;
+use crate::compare::{self, CompareMode};
+use crate::crop::{self, AspectRatio, CropRect, CropTool};
+use crate::denoise::{DenoiseJob, Denoiser};
+use crate::dither::{self, DitherMethod, DitherTarget};
+use crate::draw::{Connectivity, DrawSettings, DrawTool, Patch, Stroke};
+use crate::fft::{self, FilterKind, FilterShape, FrequencyFilter, Spectrum};
+use crate::file;
+use crate::layers::{self, BlendMode, Layer};
+use crate::lut::{self, GradientStop, LutEffect, LutParams};
+use crate::metrics::{self, QualityReport};
+use crate::noise::{self, NoiseKind, NoiseParams};
+use crate::perspective::PerspectiveTool;
+use crate::quantize::{self, QuantizeMethod};
+use crate::selection::{Mask, SelectionMode, SelectionShape, SelectionTool};
+use crate::stego::{self, Payload, StegoError, StegoParams};
+use crate::transform::{self, Affine, AffineParams, Interpolation, Matrix3};
+use crate::viewport::{self, Viewport};
+use crate::watermark::{self, Placement, Stamp, WatermarkParams};
+
+const VIEWPORT_WIDTH: u32 = 960;
+const VIEWPORT_HEIGHT: u32 = 640;
+/// Zoom change per pixel of wheel scrolling, on an exponential scale.
+const WHEEL_ZOOM_SPEED: f64 = 0.002;
+/// How long a slow filter may block the page before yielding, in ms.
+const STEP_BUDGET: f64 = 30.0;
+/// Memory the stroke history may hold, in bytes. The oldest strokes are
+/// forgotten first.
+const HISTORY_BYTES: usize = 64 << 20;
+
 #[derive(Hash, PartialEq, Eq)]
 pub enum ColorComponent {
     Red,
@@ -19,6 +60,129 @@ pub enum ColorComponent {
     Alpha,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tool {
+    None,
+    Crop,
+    Perspective,
+    Affine,
+    Select,
+    Eyedropper,
+    SpectralMask,
+    Draw,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpectrumView {
+    Off,
+    Magnitude,
+    Phase,
+}
+
+#[derive(Clone, Copy)]
+pub enum FrequencyParam {
+    Cutoff,
+    BandWidth,
+    Order,
+}
+
+#[derive(Clone, Copy)]
+pub enum LutParam {
+    Levels,
+    Threshold,
+}
+
+#[derive(Clone, Copy)]
+pub enum LutColor {
+    Below,
+    Above,
+    Shadow,
+    Highlight,
+}
+
+#[derive(Clone, Copy)]
+pub enum WatermarkParam {
+    Size,
+    Scale,
+    Opacity,
+    Rotation,
+    Margin,
+}
+
+#[derive(Clone, Copy)]
+pub enum DrawParam {
+    Size,
+    Hardness,
+    Opacity,
+    Tolerance,
+}
+
+#[derive(Clone, Copy)]
+pub enum ChromaParam {
+    Tolerance,
+    Softness,
+    Spill,
+}
+
+#[derive(Clone, Copy)]
+pub enum PaletteFormat {
+    Gpl,
+    Aco,
+    Json,
+}
+
+#[derive(Clone, Copy)]
+pub enum CropField {
+    X,
+    Y,
+    Width,
+    Height,
+    RatioWidth,
+    RatioHeight,
+}
+
+/// Whether the affine transform is edited through its decomposed
+/// parameters or as a raw matrix.
+#[derive(Clone, Copy, PartialEq)]
+pub enum AffineMode {
+    Parameters,
+    Matrix,
+}
+
+#[derive(Clone, Copy)]
+pub enum AffineParam {
+    ScaleX,
+    ScaleY,
+    Rotation,
+    ShearX,
+    ShearY,
+    TranslateX,
+    TranslateY,
+}
+
+#[derive(Clone, Copy)]
+pub enum UnsharpParam {
+    Amount,
+    Radius,
+    Threshold,
+}
+
+#[derive(Clone, Copy)]
+pub enum DenoiserParam {
+    SpatialSigma,
+    RangeSigma,
+    Radius,
+    SearchRadius,
+    PatchRadius,
+    Strength,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MetricsSource {
+    OriginalVsCurrent,
+    CurrentVsOperand,
+}
+
 pub enum Msg {
     ApplyOperation,
     ValueChanged(Event),
@@ -29,7 +193,150 @@ pub enum Msg {
     FilterMedian,
     FilterEdgeDetection,
     FilterSharpen,
-    FilterGaussianBlur
+    FilterGaussianBlur,
+    PremultipliedFilteringChanged(Event),
+    FilterAlphaChanged(Event),
+    UnsharpParamChanged(UnsharpParam, Event),
+    ApplyUnsharpMask,
+    SelectTool(Tool),
+    CanvasPointerDown(i32, i32),
+    CanvasPointerMove(i32, i32),
+    CanvasPointerUp,
+    CanvasPointerLeave,
+    CanvasWheel(f64, i32, i32),
+    CanvasPinchStart(f64),
+    CanvasPinch(f64, i32, i32),
+    ZoomFit,
+    ZoomActualSize,
+    ZoomIn,
+    ZoomOut,
+    EyedropperSizeChanged(Event),
+    ActiveColorChanged(Event),
+    ExportImage,
+    NoiseKindChanged(Event),
+    NoiseAmountChanged(Event),
+    NoiseMonochromeChanged(Event),
+    NoiseSeedChanged(Event),
+    AddNoise,
+    DenoiserChanged(Event),
+    DenoiserParamChanged(DenoiserParam, Event),
+    StartDenoise,
+    DenoiseStep,
+    CancelDenoise,
+    SpectrumViewChanged(Event),
+    FilterKindChanged(Event),
+    FilterShapeChanged(Event),
+    FrequencyParamChanged(FrequencyParam, Event),
+    MaskBrushChanged(Event),
+    ClearSpectralMask,
+    ApplyFrequencyFilter,
+    DitherTargetChanged(Event),
+    DitherLevelsChanged(Event),
+    DitherMethodChanged(Event),
+    DitherSerpentineChanged(Event),
+    ApplyDither,
+    LutEffectChanged(Event),
+    InvertChannelChanged(usize, Event),
+    LutParamChanged(LutParam, Event),
+    LutColorChanged(LutColor, Event),
+    SepiaPreset,
+    GradientStopColorChanged(usize, Event),
+    GradientStopPositionChanged(usize, Event),
+    AddGradientStop,
+    RemoveGradientStop(usize),
+    ApplyLut,
+    MixerPresetChanged(Event),
+    MixerWeightChanged(usize, usize, Event),
+    MixerOffsetChanged(usize, Event),
+    MixerAlphaChanged(Event),
+    ApplyChannelMixer,
+    SplitChannels,
+    MergeSourceChanged(usize, Event),
+    MergeChannels,
+    BitPlaneChannelChanged(Event),
+    BitPlaneChanged(Event),
+    ShowBitPlane,
+    KeptPlaneChanged(u32, Event),
+    KeepBitPlanes,
+    BitDepthChanged(usize, Event),
+    ReduceBitDepth,
+    StegoChannelChanged(usize, Event),
+    StegoBitsChanged(Event),
+    StegoPasswordChanged(Event),
+    StegoMessageChanged(Event),
+    StegoFileChosen(Event),
+    StegoFileLoaded(String, Vec<u8>),
+    EmbedText,
+    EmbedFile,
+    ExtractPayload,
+    DownloadExtracted,
+    WatermarkTextChanged(Event),
+    WatermarkParamChanged(WatermarkParam, Event),
+    WatermarkColorChanged(Event),
+    WatermarkPlacementChanged(Event),
+    WatermarkImageUpload(Event),
+    WatermarkImageLoaded(String, Vec<u8>),
+    StampText,
+    StampImage,
+    DrawToolChanged(Event),
+    DrawParamChanged(DrawParam, Event),
+    DrawFilledChanged(Event),
+    FillConnectivityChanged(Event),
+    Undo,
+    ChromaKeyColorChanged(Event),
+    ChromaKeyFromActiveColor,
+    ChromaParamChanged(ChromaParam, Event),
+    ApplyChromaKey,
+    QuantizeMethodChanged(Event),
+    QuantizeColorsChanged(Event),
+    QuantizeSeedChanged(Event),
+    QuantizeDitherChanged(Event),
+    ApplyQuantize,
+    ExportPalette(PaletteFormat),
+    CropAspectChanged(Event),
+    CropFieldChanged(CropField, Event),
+    ApplyCrop,
+    PerspectiveAutoSizeChanged(Event),
+    PerspectiveWidthChanged(Event),
+    PerspectiveHeightChanged(Event),
+    ResetPerspective,
+    ApplyPerspective,
+    AffineParamChanged(AffineParam, Event),
+    AffineMatrixChanged(usize, usize, Event),
+    AffineModeChanged(Event),
+    AffineInterpolationChanged(Event),
+    ResetAffine,
+    ApplyAffine,
+    SelectionShapeChanged(Event),
+    SelectionModeChanged(Event),
+    SelectionToleranceChanged(Event),
+    SelectionRadiusChanged(Event),
+    ClosePolygonSelection,
+    SelectAll,
+    Deselect,
+    InvertSelection,
+    GrowSelection,
+    ShrinkSelection,
+    FeatherSelection,
+    AddLayer(Event),
+    LayerLoaded(String, Vec<u8>),
+    LayerOpacityChanged(usize, Event),
+    LayerBlendModeChanged(usize, Event),
+    LayerVisibilityChanged(usize, Event),
+    MoveLayerUp(usize),
+    MoveLayerDown(usize),
+    RemoveLayer(usize),
+    FlattenLayers,
+    OperandUpload(Event),
+    OperandLoaded(String, Vec<u8>),
+    ImageOperationChanged(Event),
+    BlendWeightChanged(Event),
+    OperandFitChanged(Event),
+    ApplyImageOperation,
+    CompareModeChanged(Event),
+    ShowOriginal(bool),
+    MetricsSourceChanged(Event),
+    ComputeMetrics,
 }
 
 #[derive(Properties, PartialEq)]
@@ -38,34 +345,216 @@ pub struct Props {
 }
 
 pub struct Image {
+    /// Working copy of the image at full precision, normalised to 0.0 to
+    /// 1.0. Operations run on it in floating point.
+    pixels: Buffer<f32>,
+    /// `pixels` rounded to 8 bits, for display and for the operations that
+    /// still work on 8-bit data.
     bitmap_data: Vec<u8>,
     width: u32,
     height: u32,
+    /// Bits per channel of the decoded file.
+    source_depth: u32,
+    /// The image as it was decoded, kept for comparison. Cropped and
+    /// warped along with the image so the two stay aligned.
+    original: RgbaImage,
 
     canvas_ref: NodeRef,
     canvas_ctx: Option<CanvasRenderingContext2d>,
+    /// Holds the pixels at image scale so they can be drawn zoomed.
+    offscreen_ctx: Option<CanvasRenderingContext2d>,
+    /// Drawn behind the image so transparent pixels are visible.
+    checkerboard: Option<CanvasPattern>,
+    wheel_listener: Option<EventListener>,
+    viewport: Viewport,
+    /// Last pointer position while dragging the view around.
+    pan_origin: Option<(i32, i32)>,
+    /// Last distance between the two fingers of a pinch gesture.
+    pinch_distance: Option<f64>,
     color_select_ref: NodeRef,
     operation_select_ref: NodeRef,
     input_value: f32,
-    brigthness_scale: f32
+    brigthness_scale: f32,
+    /// Run colour filters on premultiplied alpha so transparent pixels
+    /// don't darken their neighbours.
+    premultiplied_filtering: bool,
+    /// Let those filters change the alpha channel as well.
+    filter_alpha: bool,
+
+    tool: Tool,
+    crop_tool: CropTool,
+    perspective_tool: PerspectiveTool,
+    affine_params: AffineParams,
+    affine_matrix: Affine,
+    affine_mode: AffineMode,
+    affine_interpolation: Interpolation,
+    affine_preview: Option<Vec<u8>>,
+    selection: Option<Mask>,
+    selection_tool: SelectionTool,
+    /// Layers stacked above the image, bottom first.
+    layers: Vec<Layer>,
+    /// Second image used as the right-hand operand of image operations.
+    operand: Option<(String, RgbaImage)>,
+    image_operation: ImageOperation,
+    operand_fit: OperandFit,
+    compare_mode: CompareMode,
+    /// Position of the split line as a fraction of the image width.
+    split_position: f64,
+    split_dragging: bool,
+    show_original: bool,
+    metrics_source: MetricsSource,
+    metrics: Option<QualityReport>,
+    /// Image pixel under the pointer, shown in the status bar.
+    hovered: Option<(u32, u32)>,
+    /// Colour picked with the eyedropper, used by colour-based tools.
+    active_color: [u8; 4],
+    /// Side of the square averaged by the eyedropper.
+    eyedropper_size: u32,
+    unsharp_mask: UnsharpMask,
+    noise_params: NoiseParams,
+    denoiser: Denoiser,
+    /// Slow filter in progress, advanced a few rows per message.
+    denoise_job: Option<DenoiseJob>,
+    spectrum_view: SpectrumView,
+    /// Spectrum of `spectrum_source`, recomputed when the image changes.
+    spectrum: Option<Spectrum>,
+    spectrum_source: Vec<u8>,
+    /// Hand-painted gain per coefficient, in transform order.
+    spectral_mask: Vec<f32>,
+    frequency_filter: FrequencyFilter,
+    mask_brush: f64,
+    painting_mask: bool,
+    dither_target: DitherTarget,
+    dither_method: DitherMethod,
+    dither_serpentine: bool,
+    lut_params: LutParams,
+    channel_mixer: ChannelMixer,
+    /// Layer whose luminance replaces each channel when merging, if any.
+    merge_sources: [Option<usize>; 4],
+    bit_plane_channel: usize,
+    bit_plane: u32,
+    /// Bit planes kept when reconstructing, one bit per plane.
+    kept_planes: u8,
+    bit_depths: [u32; 4],
+    stego_params: StegoParams,
+    stego_message: String,
+    stego_file: Option<(String, Vec<u8>)>,
+    /// Outcome of the last embed or extract, shown next to the controls.
+    stego_status: Option<String>,
+    extracted: Option<Payload>,
+    watermark: WatermarkParams,
+    watermark_image: Option<(String, RgbaImage)>,
+    draw_settings: DrawSettings,
+    /// Stroke being painted while the pointer is held down.
+    stroke: Option<Stroke>,
+    /// What each finished stroke painted over, most recent last. Cleared
+    /// whenever anything else changes the image.
+    history: Vec<Patch>,
+    chroma_key: ChromaKey,
+    quantize_method: QuantizeMethod,
+    quantize_colors: u32,
+    quantize_seed: u64,
+    /// Remap with the dithering method chosen for dithering.
+    quantize_dither: bool,
+    /// Palette picked by the last quantization.
+    palette: Vec<[u8; 3]>,
 }
 
 impl Image {
     pub fn new_with_data(data: Vec<u8>) -> Self {
         let image = Self::decode_data(data);
+        let original = image.to_rgba8();
 
-        Self {
-            bitmap_data: image.to_rgba8().into_vec(),
+        let mut image = Self {
+            pixels: Buffer::decode(&image),
+            bitmap_data: original.as_raw().clone(),
             width: image.width(),
             height: image.height(),
+            source_depth: buffer::depth(&image),
+            original,
 
             canvas_ref: NodeRef::default(),
             canvas_ctx: None,
+            offscreen_ctx: None,
+            checkerboard: None,
+            wheel_listener: None,
+            viewport: Viewport::new(VIEWPORT_WIDTH, VIEWPORT_HEIGHT),
+            pan_origin: None,
+            pinch_distance: None,
             color_select_ref: NodeRef::default(),
             operation_select_ref: NodeRef::default(),
             input_value: 0.0,
-            brigthness_scale: 0.0
-        }
+            brigthness_scale: 0.0,
+            premultiplied_filtering: true,
+            filter_alpha: true,
+
+            tool: Tool::None,
+            crop_tool: CropTool::new(image.width(), image.height()),
+            perspective_tool: PerspectiveTool::default(),
+            affine_params: AffineParams::default(),
+            affine_matrix: transform::IDENTITY_AFFINE,
+            affine_mode: AffineMode::Parameters,
+            affine_interpolation: Interpolation::Bilinear,
+            affine_preview: None,
+            selection: None,
+            selection_tool: SelectionTool::default(),
+            layers: Vec::new(),
+            operand: None,
+            image_operation: ImageOperation::Add,
+            operand_fit: OperandFit::Resize,
+            compare_mode: CompareMode::Off,
+            split_position: 0.5,
+            split_dragging: false,
+            show_original: false,
+            metrics_source: MetricsSource::OriginalVsCurrent,
+            metrics: None,
+            hovered: None,
+            active_color: [0, 0, 0, 255],
+            eyedropper_size: 1,
+            unsharp_mask: UnsharpMask::default(),
+            noise_params: NoiseParams::default(),
+            denoiser: Denoiser::Bilateral {
+                spatial_sigma: 3.0,
+                range_sigma: 30.0,
+            },
+            denoise_job: None,
+            spectrum_view: SpectrumView::Off,
+            spectrum: None,
+            spectrum_source: Vec::new(),
+            spectral_mask: Vec::new(),
+            frequency_filter: FrequencyFilter::default(),
+            mask_brush: 3.0,
+            painting_mask: false,
+            dither_target: DitherTarget::BlackWhite,
+            dither_method: DitherMethod::FloydSteinberg,
+            dither_serpentine: true,
+            lut_params: LutParams::default(),
+            channel_mixer: ChannelMixer::default(),
+            merge_sources: [None; 4],
+            bit_plane_channel: 0,
+            bit_plane: 7,
+            kept_planes: 0xf0,
+            bit_depths: [8; 4],
+            stego_params: StegoParams::default(),
+            stego_message: String::new(),
+            stego_file: None,
+            stego_status: None,
+            extracted: None,
+            watermark: WatermarkParams::default(),
+            watermark_image: None,
+            draw_settings: DrawSettings::default(),
+            stroke: None,
+            history: Vec::new(),
+            chroma_key: ChromaKey::default(),
+            quantize_method: QuantizeMethod::MedianCut,
+            quantize_colors: 16,
+            quantize_seed: 0,
+            quantize_dither: false,
+            palette: Vec::new(),
+        };
+        image.reset_view();
+
+        image
     }
 
     pub fn apply_point_fn(
@@ -86,47 +575,53 @@ impl Image {
             ColorComponent::Alpha => 3,
         };
 
-        while index < self.bitmap_data.len() {
-            let color = self.bitmap_data[index + offset] as f32;
-            let new_color = func(color, value);
+        while index < self.pixels.data.len() {
+            let color = self.pixels.data[index + offset] * 255.0;
+            let new_color = func(color, value).clamp(0.0, 255.0);
 
-            self.bitmap_data[index + offset] = new_color as u8;
+            self.pixels.data[index + offset] = new_color / 255.0;
 
             index += 4;
         }
     }
 
+    /// Combines the image with a second one of the same size, channel by
+    /// channel.
+    pub fn apply_image_fn(&mut self, operand: &[u8], operation: ImageOperation) {
+        arithmetic::combine(&mut self.pixels.data, operand, operation);
+    }
+
     pub fn change_brightness(&mut self, brightness: f32) {
         let brightness = brightness / 2.0;
-        for i in 0..self.bitmap_data.len() {
+        for i in 0..self.pixels.data.len() {
             if i % 4 == 3 {
                 continue;
             }
 
-            let norm_val = self.bitmap_data[i] as f32 / 255.0;
+            let norm_val = self.pixels.data[i];
             let new_val = if brightness < 0.0 {
                 norm_val * (1.0 + brightness)
             } else {
                 norm_val + brightness * (1.0 - norm_val)
             };
 
-            self.bitmap_data[i] = (new_val * 255.0) as u8;
+            self.pixels.data[i] = new_val;
         }
     }
 
     pub fn to_grayscale_avg(&mut self) {
         let mut index = 0;
 
-        while index < self.bitmap_data.len() {
-            let red = self.bitmap_data[index] as f32;
-            let green = self.bitmap_data[index + 1] as f32;
-            let blue = self.bitmap_data[index + 2] as f32;
+        while index < self.pixels.data.len() {
+            let red = self.pixels.data[index];
+            let green = self.pixels.data[index + 1];
+            let blue = self.pixels.data[index + 2];
 
             let avg = (red + green + blue) / 3.0;
 
-            self.bitmap_data[index] = avg as u8;
-            self.bitmap_data[index + 1] = avg as u8;
-            self.bitmap_data[index + 2] = avg as u8;
+            self.pixels.data[index] = avg;
+            self.pixels.data[index + 1] = avg;
+            self.pixels.data[index + 2] = avg;
 
             index += 4;
         }
@@ -135,16 +630,16 @@ impl Image {
     pub fn to_grayscale_avg_weighted(&mut self) {
         let mut index = 0;
 
-        while index < self.bitmap_data.len() {
-            let red = self.bitmap_data[index] as f32;
-            let green = self.bitmap_data[index + 1] as f32;
-            let blue = self.bitmap_data[index + 2] as f32;
+        while index < self.pixels.data.len() {
+            let red = self.pixels.data[index];
+            let green = self.pixels.data[index + 1];
+            let blue = self.pixels.data[index + 2];
 
-            let avg = (red * 0.2126 + green * 0.7152 + blue * 0.0722) as u8;
+            let avg = red * 0.2126 + green * 0.7152 + blue * 0.0722;
 
-            self.bitmap_data[index] = avg;
-            self.bitmap_data[index + 1] = avg;
-            self.bitmap_data[index + 2] = avg;
+            self.pixels.data[index] = avg;
+            self.pixels.data[index + 1] = avg;
+            self.pixels.data[index + 2] = avg;
 
             index += 4;
         }
@@ -152,12 +647,12 @@ impl Image {
 
     pub fn filter_smooth(&mut self) {
         let mut index = 0;
-        let mut new_bitmap_data = self.bitmap_data.clone();
+        let mut new_data = self.pixels.data.clone();
 
-        while index < self.bitmap_data.len() {
-            let mut red = 0;
-            let mut green = 0;
-            let mut blue = 0;
+        while index < self.pixels.data.len() {
+            let mut red = 0.0;
+            let mut green = 0.0;
+            let mut blue = 0.0;
 
             for i in 0..9 {
                 let x = i % 3;
@@ -165,33 +660,33 @@ impl Image {
 
                 let pixel_index = index + (x - 1) * 4 + (y - 1) * self.width as usize * 4;
 
-                if pixel_index < 0 || pixel_index >= self.bitmap_data.len() {
+                if pixel_index < 0 || pixel_index >= self.pixels.data.len() {
                     continue;
                 }
 
-                red += self.bitmap_data[pixel_index] as usize;
-                green += self.bitmap_data[pixel_index + 1] as usize;
-                blue += self.bitmap_data[pixel_index + 2] as usize;
+                red += self.pixels.data[pixel_index];
+                green += self.pixels.data[pixel_index + 1];
+                blue += self.pixels.data[pixel_index + 2];
             }
 
-            new_bitmap_data[index] = (red / 9) as u8;
-            new_bitmap_data[index + 1] = (green / 9) as u8;
-            new_bitmap_data[index + 2] = (blue / 9) as u8;
+            new_data[index] = red / 9.0;
+            new_data[index + 1] = green / 9.0;
+            new_data[index + 2] = blue / 9.0;
 
             index += 4;
         }
 
-        self.bitmap_data = new_bitmap_data;
+        self.pixels.data = new_data;
     }
 
     pub fn filter_median(&mut self) {
         let mut index = 0;
-        let mut new_bitmap_data = self.bitmap_data.clone();
+        let mut new_data = self.pixels.data.clone();
 
-        while index < self.bitmap_data.len() {
-            let mut red = [0; 9];
-            let mut green = [0; 9];
-            let mut blue = [0; 9];
+        while index < self.pixels.data.len() {
+            let mut red = [0.0; 9];
+            let mut green = [0.0; 9];
+            let mut blue = [0.0; 9];
 
             for i in 0..9 {
                 let x = i % 3;
@@ -199,41 +694,41 @@ impl Image {
 
                 let pixel_index = index + (x - 1) * 4 + (y - 1) * self.width as usize * 4;
 
-                if pixel_index < 0 || pixel_index >= self.bitmap_data.len() {
+                if pixel_index < 0 || pixel_index >= self.pixels.data.len() {
                     continue;
                 }
 
-                red[i] = self.bitmap_data[pixel_index] as u32;
-                green[i] = self.bitmap_data[pixel_index + 1] as u32;
-                blue[i] = self.bitmap_data[pixel_index + 2] as u32;
+                red[i] = self.pixels.data[pixel_index];
+                green[i] = self.pixels.data[pixel_index + 1];
+                blue[i] = self.pixels.data[pixel_index + 2];
             }
 
-            red.sort();
-            green.sort();
-            blue.sort();
+            red.sort_by(f32::total_cmp);
+            green.sort_by(f32::total_cmp);
+            blue.sort_by(f32::total_cmp);
 
-            new_bitmap_data[index] = red[4] as u8;
-            new_bitmap_data[index + 1] = green[4] as u8;
-            new_bitmap_data[index + 2] = blue[4] as u8;
+            new_data[index] = red[4];
+            new_data[index + 1] = green[4];
+            new_data[index + 2] = blue[4];
 
             index += 4;
         }
 
-        self.bitmap_data = new_bitmap_data;
+        self.pixels.data = new_data;
     }
 
     pub fn filter_sobel(&mut self) {
         let mut index = 0;
-        let mut new_bitmap_data = self.bitmap_data.clone();
+        let mut new_data = self.pixels.data.clone();
 
-        while index < self.bitmap_data.len() {
-            let mut red_x = 0;
-            let mut green_x = 0;
-            let mut blue_x = 0;
+        while index < self.pixels.data.len() {
+            let mut red_x = 0.0;
+            let mut green_x = 0.0;
+            let mut blue_x = 0.0;
 
-            let mut red_y = 0;
-            let mut green_y = 0;
-            let mut blue_y = 0;
+            let mut red_y = 0.0;
+            let mut green_y = 0.0;
+            let mut blue_y = 0.0;
 
             for i in 0..9 {
                 let x = i % 3;
@@ -241,25 +736,25 @@ impl Image {
 
                 let pixel_index = index + (x - 1) * 4 + (y - 1) * self.width as usize * 4;
 
-                if pixel_index < 0 || pixel_index >= self.bitmap_data.len() {
+                if pixel_index < 0 || pixel_index >= self.pixels.data.len() {
                     continue;
                 }
 
-                let red = self.bitmap_data[pixel_index] as i32;
-                let green = self.bitmap_data[pixel_index + 1] as i32;
-                let blue = self.bitmap_data[pixel_index + 2] as i32;
+                let red = self.pixels.data[pixel_index];
+                let green = self.pixels.data[pixel_index + 1];
+                let blue = self.pixels.data[pixel_index + 2];
 
                 let x_weight = match x {
-                    0 => -1,
-                    1 => 0,
-                    2 => 1,
+                    0 => -1.0,
+                    1 => 0.0,
+                    2 => 1.0,
                     _ => unreachable!(),
                 };
 
                 let y_weight = match y {
-                    0 => -1,
-                    1 => 0,
-                    2 => 1,
+                    0 => -1.0,
+                    1 => 0.0,
+                    2 => 1.0,
                     _ => unreachable!(),
                 };
 
@@ -272,25 +767,25 @@ impl Image {
                 blue_y += blue * y_weight;
             }
 
-            let red = ((red_x * red_x + red_y * red_y) as f32).sqrt() as u8;
-            let green = ((green_x * green_x + green_y * green_y) as f32).sqrt() as u8;
-            let blue = ((blue_x * blue_x + blue_y * blue_y) as f32).sqrt() as u8;
+            let red = (red_x * red_x + red_y * red_y).sqrt().min(1.0);
+            let green = (green_x * green_x + green_y * green_y).sqrt().min(1.0);
+            let blue = (blue_x * blue_x + blue_y * blue_y).sqrt().min(1.0);
 
-            new_bitmap_data[index] = red;
-            new_bitmap_data[index + 1] = green;
-            new_bitmap_data[index + 2] = blue;
+            new_data[index] = red;
+            new_data[index + 1] = green;
+            new_data[index + 2] = blue;
 
             index += 4;
         }
 
-        self.bitmap_data = new_bitmap_data;
+        self.pixels.data = new_data;
     }
 
     pub fn filter_highpass_sharpen(&mut self) {
-        let mut highpass_data = self.bitmap_data.clone();
+        let mut highpass_data = self.pixels.data.clone();
 
         let mut index = 0;
-        while index < self.bitmap_data.len() {
+        while index < self.pixels.data.len() {
             let mut red: f32 = 0.0;
             let mut green: f32 = 0.0;
             let mut blue: f32 = 0.0;
@@ -301,7 +796,7 @@ impl Image {
 
                 let pixel_index = index + (x - 1) * 4 + (y - 1) * self.width as usize * 4;
 
-                if pixel_index < 0 || pixel_index >= self.bitmap_data.len() {
+                if pixel_index < 0 || pixel_index >= self.pixels.data.len() {
                     continue;
                 }
 
@@ -310,28 +805,28 @@ impl Image {
                     _ => -1.0 / 9.0,
                 };
 
-                red += f32::from(self.bitmap_data[pixel_index]) * weight;
-                green += f32::from(self.bitmap_data[pixel_index + 1]) * weight;
-                blue += f32::from(self.bitmap_data[pixel_index + 2]) * weight;
+                red += self.pixels.data[pixel_index] * weight;
+                green += self.pixels.data[pixel_index + 1] * weight;
+                blue += self.pixels.data[pixel_index + 2] * weight;
             }
 
-            highpass_data[index] = red as u8;
-            highpass_data[index + 1] = green as u8;
-            highpass_data[index + 2] = blue as u8;
+            highpass_data[index] = red.clamp(0.0, 1.0);
+            highpass_data[index + 1] = green.clamp(0.0, 1.0);
+            highpass_data[index + 2] = blue.clamp(0.0, 1.0);
 
             index += 4;
         }
 
         index = 0;
 
-        while index < self.bitmap_data.len() {
-            let red = self.bitmap_data[index].saturating_add(highpass_data[index]);
-            let green = self.bitmap_data[index + 1].saturating_add(highpass_data[index + 1]);
-            let blue = self.bitmap_data[index + 2].saturating_add(highpass_data[index + 2]);
+        while index < self.pixels.data.len() {
+            let red = (self.pixels.data[index] + highpass_data[index]).min(1.0);
+            let green = (self.pixels.data[index + 1] + highpass_data[index + 1]).min(1.0);
+            let blue = (self.pixels.data[index + 2] + highpass_data[index + 2]).min(1.0);
 
-            self.bitmap_data[index] = red;
-            self.bitmap_data[index + 1] = green;
-            self.bitmap_data[index + 2] = blue;
+            self.pixels.data[index] = red;
+            self.pixels.data[index + 1] = green;
+            self.pixels.data[index + 2] = blue;
 
             index += 4;
         }
@@ -339,12 +834,12 @@ impl Image {
 
     pub fn filter_gaussian_blur(&mut self) {
         let mut index = 0;
-        let mut new_bitmap_data = self.bitmap_data.clone();
+        let mut new_data = self.pixels.data.clone();
 
-        while index < self.bitmap_data.len() {
-            let mut red = 0;
-            let mut green = 0;
-            let mut blue = 0;
+        while index < self.pixels.data.len() {
+            let mut red = 0.0;
+            let mut green = 0.0;
+            let mut blue = 0.0;
 
             for i in 0..9 {
                 let x = i % 3;
@@ -352,36 +847,480 @@ impl Image {
 
                 let pixel_index = index + (x - 1) * 4 + (y - 1) * self.width as usize * 4;
 
-                if pixel_index < 0 || pixel_index >= self.bitmap_data.len() {
+                if pixel_index < 0 || pixel_index >= self.pixels.data.len() {
                     continue;
                 }
 
                 let weight = match (x, y) {
-                    (0, 0) => 1,
-                    (1, 0) => 2,
-                    (2, 0) => 1,
-                    (0, 1) => 2,
-                    (1, 1) => 4,
-                    (2, 1) => 2,
-                    (0, 2) => 1,
-                    (1, 2) => 2,
-                    (2, 2) => 1,
+                    (0, 0) => 1.0,
+                    (1, 0) => 2.0,
+                    (2, 0) => 1.0,
+                    (0, 1) => 2.0,
+                    (1, 1) => 4.0,
+                    (2, 1) => 2.0,
+                    (0, 2) => 1.0,
+                    (1, 2) => 2.0,
+                    (2, 2) => 1.0,
                     _ => unreachable!(),
                 };
 
-                red += self.bitmap_data[pixel_index] as i32 * weight;
-                green += self.bitmap_data[pixel_index + 1] as i32 * weight;
-                blue += self.bitmap_data[pixel_index + 2] as i32 * weight;
+                red += self.pixels.data[pixel_index] * weight;
+                green += self.pixels.data[pixel_index + 1] * weight;
+                blue += self.pixels.data[pixel_index + 2] * weight;
             }
 
-            new_bitmap_data[index] = (red / 16) as u8;
-            new_bitmap_data[index + 1] = (green / 16) as u8;
-            new_bitmap_data[index + 2] = (blue / 16) as u8;
+            new_data[index] = red / 16.0;
+            new_data[index + 1] = green / 16.0;
+            new_data[index + 2] = blue / 16.0;
 
             index += 4;
         }
 
-        self.bitmap_data = new_bitmap_data;
+        self.pixels.data = new_data;
+    }
+
+    /// Runs a colour filter on premultiplied alpha, so the hidden colour of
+    /// transparent pixels doesn't bleed into their neighbours, and runs it
+    /// again on the alpha channel to get the coverage to divide back by.
+    /// Only linear filters qualify: their output divided by the filtered
+    /// alpha is still a weighted average of colours.
+    pub fn filter_premultiplied(&mut self, filter: impl Fn(&mut Self)) {
+        let opaque = self
+            .pixels
+            .data
+            .chunks_exact(4)
+            .all(|pixel| pixel[3] >= 1.0);
+        if !self.premultiplied_filtering || opaque {
+            filter(self);
+            return;
+        }
+
+        let original = self.pixels.data.clone();
+        alpha::premultiply(&mut self.pixels.data);
+        filter(self);
+
+        let premultiplied = mem::replace(&mut self.pixels.data, channels::split(&original, 3));
+        filter(self);
+
+        let coverage = mem::replace(&mut self.pixels.data, premultiplied);
+        alpha::unpremultiply(
+            &mut self.pixels.data,
+            &coverage,
+            &original,
+            self.filter_alpha,
+        );
+    }
+
+    pub fn unsharp_mask(&mut self, params: &UnsharpMask) {
+        blur::unsharp_mask(&mut self.pixels.data, self.width, self.height, params);
+    }
+
+    /// Filters the image in the frequency domain, scaling the spectrum by
+    /// `filter` and by `mask`, which holds one gain per coefficient.
+    pub fn filter_frequency(&mut self, filter: &FrequencyFilter, mask: &[f32]) {
+        let spectrum = Spectrum::new(&self.pixels.data, self.width, self.height);
+        let gains = spectrum.gains(filter, mask);
+
+        self.pixels.data = spectrum.inverse(&gains);
+    }
+
+    pub fn dither(&mut self, target: &DitherTarget, method: DitherMethod, serpentine: bool) {
+        self.apply_8bit(|image| {
+            dither::dither(
+                &mut image.bitmap_data,
+                image.width,
+                image.height,
+                target,
+                method,
+                serpentine,
+            )
+        });
+    }
+
+    pub fn apply_lut(&mut self, params: &LutParams) {
+        self.apply_8bit(|image| lut::apply(&mut image.bitmap_data, params));
+    }
+
+    pub fn mix_channels(&mut self, mixer: &ChannelMixer) {
+        channels::mix(&mut self.pixels.data, mixer);
+    }
+
+    /// Adds each channel as a hidden grayscale layer.
+    pub fn split_channels(&mut self) {
+        for (channel, name) in channels::CHANNEL_NAMES.iter().enumerate() {
+            let data = channels::split(&self.bitmap_data, channel);
+            let mut layer = Layer::new(name.to_string(), data, self.width, self.height);
+            layer.visible = false;
+            self.layers.push(layer);
+        }
+    }
+
+    /// Replaces channels with the luminance of the given layers. Layers of
+    /// another size are ignored.
+    pub fn merge_channels(&mut self, sources: [Option<usize>; 4]) {
+        let sources = sources.map(|source| {
+            source
+                .and_then(|index| self.layers.get(index))
+                .filter(|layer| layer.width == self.width && layer.height == self.height)
+                .map(|layer| layer.data.as_slice())
+        });
+
+        channels::merge(&mut self.pixels.data, sources);
+    }
+
+    /// Replaces the image with one bit plane of a channel.
+    pub fn show_bit_plane(&mut self, channel: usize, bit: u32) {
+        self.apply_8bit(|image| {
+            image.bitmap_data = bitplane::plane(&image.bitmap_data, channel, bit)
+        });
+    }
+
+    pub fn keep_bit_planes(&mut self, planes: u8) {
+        self.apply_8bit(|image| bitplane::keep_planes(&mut image.bitmap_data, planes));
+    }
+
+    pub fn reduce_bit_depth(&mut self, bits: [u32; 4]) {
+        self.apply_8bit(|image| bitplane::reduce_depth(&mut image.bitmap_data, bits));
+    }
+
+    /// Hides `payload` in the low bits of the image, leaving it untouched
+    /// if it does not fit.
+    pub fn embed_payload(
+        &mut self,
+        params: &StegoParams,
+        payload: &Payload,
+    ) -> Result<(), StegoError> {
+        let mut result = Ok(());
+        self.apply_8bit(|image| result = stego::embed(&mut image.bitmap_data, params, payload));
+
+        result
+    }
+
+    /// Writes the part of the stroke in progress that changed since the
+    /// last call into the image, limited to the selection.
+    fn composite_stroke(&mut self) {
+        if let Some(stroke) = &mut self.stroke {
+            let mask = self.selection.as_ref().map(|mask| mask.data.as_slice());
+            stroke.composite(&mut self.pixels.data, mask);
+            self.refresh_8bit();
+        }
+    }
+
+    /// Ends the stroke in progress, remembering what it painted over so it
+    /// can be undone.
+    fn finish_stroke(&mut self) {
+        let patch = match self.stroke.take() {
+            Some(stroke) => stroke.into_patch(&self.pixels.data),
+            None => return,
+        };
+
+        if let Some(patch) = patch.filter(|patch| patch.size() <= HISTORY_BYTES) {
+            let mut size = patch.size() + self.history.iter().map(Patch::size).sum::<usize>();
+            while size > HISTORY_BYTES {
+                size -= self.history.remove(0).size();
+            }
+            self.history.push(patch);
+        }
+    }
+
+    /// Restores the image from before the last stroke.
+    fn undo(&mut self) {
+        self.finish_stroke();
+        if let Some(patch) = self.history.pop() {
+            patch.restore(&mut self.pixels.data, self.width);
+            self.refresh_8bit();
+        }
+    }
+
+    fn embed_and_report(&mut self, payload: Payload) {
+        let params = self.stego_params.clone();
+        self.stego_status = Some(match self.embed_payload(&params, &payload) {
+            Ok(()) => format!("Hid {} bytes", payload.encoded_len()),
+            Err(error) => error.to_string(),
+        });
+    }
+
+    pub fn extract_payload(&self, params: &StegoParams) -> Result<Payload, StegoError> {
+        stego::extract(&self.bitmap_data, params)
+    }
+
+    pub fn stamp_text(&mut self, params: &WatermarkParams) {
+        let stamp = Stamp::text(&params.text, params.size, params.color);
+        watermark::apply(
+            &mut self.pixels.data,
+            self.width,
+            self.height,
+            &stamp,
+            1.0,
+            params,
+        );
+    }
+
+    pub fn stamp_image(&mut self, image: &RgbaImage, params: &WatermarkParams) {
+        let stamp = Stamp {
+            data: image.as_raw().clone(),
+            width: image.width(),
+            height: image.height(),
+        };
+        watermark::apply(
+            &mut self.pixels.data,
+            self.width,
+            self.height,
+            &stamp,
+            params.scale,
+            params,
+        );
+    }
+
+    pub fn chroma_key(&mut self, params: &ChromaKey) {
+        chroma::key(&mut self.pixels.data, params);
+    }
+
+    /// Reduces the image to `colors` colours picked with `method` and
+    /// returns the palette used.
+    pub fn quantize(
+        &mut self,
+        colors: u32,
+        method: QuantizeMethod,
+        seed: u64,
+        dither_method: DitherMethod,
+        serpentine: bool,
+    ) -> Vec<[u8; 3]> {
+        let palette = quantize::palette(&self.bitmap_data, colors as usize, method, seed);
+        let target = DitherTarget::Palette(palette.clone());
+        self.dither(&target, dither_method, serpentine);
+
+        palette
+    }
+
+    pub fn add_noise(&mut self, params: &NoiseParams) {
+        noise::add_noise(&mut self.pixels.data, params);
+    }
+
+    pub fn crop(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        let original = self.original_data();
+        let original = crop::crop_rgba(&original, self.width, x, y, width, height);
+        self.original = RgbaImage::from_raw(width, height, original).unwrap();
+        self.pixels = Buffer {
+            data: crop::crop_rgba(&self.pixels.data, self.width, x, y, width, height),
+            width,
+            height,
+        };
+        self.refresh_8bit();
+        self.width = width;
+        self.height = height;
+        self.crop_tool = CropTool::new(width, height);
+        self.selection = None;
+        self.hovered = None;
+        self.history.clear();
+        self.reset_view();
+    }
+
+    /// Replaces the image with one of the given size, where each output pixel
+    /// is taken from the source position that `inverse` maps it to.
+    pub fn warp_projective(&mut self, inverse: &Matrix3, width: u32, height: u32) {
+        let original = transform::warp_projective(
+            &self.original_data(),
+            self.width,
+            self.height,
+            inverse,
+            width,
+            height,
+            Interpolation::Bilinear,
+        );
+        self.original = RgbaImage::from_raw(width, height, original).unwrap();
+        self.pixels = Buffer {
+            data: transform::warp_projective(
+                &self.pixels.data,
+                self.width,
+                self.height,
+                inverse,
+                width,
+                height,
+                Interpolation::Bilinear,
+            ),
+            width,
+            height,
+        };
+        self.refresh_8bit();
+        self.width = width;
+        self.height = height;
+        self.crop_tool = CropTool::new(width, height);
+        self.selection = None;
+        self.hovered = None;
+        self.history.clear();
+        self.reset_view();
+    }
+
+    /// Rectifies the quadrilateral given by its top-left, top-right,
+    /// bottom-right and bottom-left corners into a `width` x `height` image.
+    pub fn correct_perspective(&mut self, corners: [(f64, f64); 4], width: u32, height: u32) {
+        if buffer::rgba_len(width, height).is_none() {
+            log::warn!("Output size {}x{} is too large", width, height);
+            return;
+        }
+
+        let (width_f, height_f) = (width as f64, height as f64);
+        let target = [
+            (0.0, 0.0),
+            (width_f, 0.0),
+            (width_f, height_f),
+            (0.0, height_f),
+        ];
+
+        match transform::homography(target, corners) {
+            Some(inverse) => self.warp_projective(&inverse, width, height),
+            None => log::warn!("Perspective corners are degenerate"),
+        }
+    }
+
+    /// Applies a forward affine matrix around the image origin, sampling
+    /// the source through its inverse. The image keeps its size.
+    pub fn transform_affine(&mut self, matrix: &Affine, interpolation: Interpolation) {
+        match transform::warp_affine(
+            &self.pixels.data,
+            self.width,
+            self.height,
+            matrix,
+            interpolation,
+        ) {
+            Some(data) => self.pixels.data = data,
+            None => log::warn!("Affine matrix is not invertible"),
+        }
+    }
+
+    /// Runs an operation on the whole image and then restores the pixels
+    /// outside the current selection, blending across soft edges. Does
+    /// nothing while the selection is empty.
+    pub fn apply_masked(&mut self, operation: impl FnOnce(&mut Self)) {
+        if self.selection.as_ref().is_some_and(Mask::is_empty) {
+            return;
+        }
+
+        let original = self.selection.as_ref().map(|_| self.pixels.data.clone());
+
+        operation(self);
+        self.history.clear();
+
+        if let (Some(mask), Some(original)) = (&self.selection, original) {
+            mask.apply(&original, &mut self.pixels.data);
+        }
+        self.refresh_8bit();
+    }
+
+    /// Runs an operation that works on the 8-bit data and folds what it
+    /// changed back into the working copy, so samples it left alone keep
+    /// their full precision. Samples it changes are rounded to 8 bits, so
+    /// this is only for operations defined on 8-bit values: lookup tables,
+    /// bit planes, palettes and steganography.
+    fn apply_8bit(&mut self, operation: impl FnOnce(&mut Self)) {
+        let before = self.bitmap_data.clone();
+
+        operation(self);
+
+        if self.bitmap_data.len() != before.len() {
+            self.pixels = Buffer::from_rgba8(&self.bitmap_data, self.width, self.height);
+            return;
+        }
+
+        for ((sample, value), before) in self
+            .pixels
+            .data
+            .iter_mut()
+            .zip(&self.bitmap_data)
+            .zip(before)
+        {
+            if *value != before {
+                *sample = *value as f32 / 255.0;
+            }
+        }
+    }
+
+    /// Rounds the working copy to the 8-bit data shown on the canvas.
+    fn refresh_8bit(&mut self) {
+        self.bitmap_data = self.pixels.to_rgba8();
+    }
+
+    /// Encodes the working copy as PNG, at 16 bits per channel when the
+    /// source had more than 8.
+    pub fn encode_png(&self) -> Vec<u8> {
+        let image = if self.source_depth > 8 {
+            self.pixels.convert::<u16>().to_image()
+        } else {
+            self.pixels.convert::<u8>().to_image()
+        };
+
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .expect("Unable to encode image.");
+
+        bytes
+    }
+
+    /// Merges a newly drawn selection into the current one. An empty result
+    /// is kept, so operations leave the image alone until the selection is
+    /// cleared.
+    pub fn combine_selection(&mut self, mask: Mask, mode: SelectionMode) {
+        let mut selection = self
+            .selection
+            .take()
+            .unwrap_or_else(|| Mask::new(self.width, self.height, 0.0));
+        selection.combine(&mask, mode);
+
+        self.selection = Some(selection);
+    }
+
+    pub fn add_layer(&mut self, name: String, data: Vec<u8>) {
+        let image = Self::decode_data(data);
+
+        self.layers.push(Layer::new(
+            name,
+            image.to_rgba8().into_vec(),
+            image.width(),
+            image.height(),
+        ));
+    }
+
+    /// Composites every visible layer into the image and empties the stack.
+    pub fn flatten_layers(&mut self) {
+        self.pixels.data =
+            layers::flatten(&self.pixels.data, self.width, self.height, &self.layers);
+        self.refresh_8bit();
+        self.layers.clear();
+        self.merge_sources = [None; 4];
+        self.history.clear();
+    }
+
+    /// Swaps two layers, keeping channel merge sources on the same layers.
+    fn swap_layers(&mut self, a: usize, b: usize) {
+        self.layers.swap(a, b);
+        for source in self.merge_sources.iter_mut() {
+            if *source == Some(a) {
+                *source = Some(b);
+            } else if *source == Some(b) {
+                *source = Some(a);
+            }
+        }
+    }
+
+    /// Removes a layer, clearing merge sources that used it and shifting
+    /// those above it down.
+    fn remove_layer(&mut self, index: usize) {
+        self.layers.remove(index);
+        for source in self.merge_sources.iter_mut() {
+            *source = match *source {
+                Some(layer) if layer == index => None,
+                Some(layer) if layer > index => Some(layer - 1),
+                other => other,
+            };
+        }
+    }
+
+    /// Measures how far the current image has drifted from `reference`,
+    /// which must have the same size.
+    pub fn quality_report(&self, reference: &[u8]) -> QualityReport {
+        metrics::compare(reference, &self.bitmap_data, self.width, self.height)
     }
 
     fn rgb_to_hsl(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
@@ -421,153 +1360,3232 @@ impl Image {
     fn update(&mut self, data: Vec<u8>) {
         let image = Self::decode_data(data);
 
-        self.bitmap_data = image.to_rgba8().into_vec();
+        self.original = image.to_rgba8();
+        self.pixels = Buffer::decode(&image);
+        self.bitmap_data = self.original.as_raw().clone();
         self.width = image.width();
         self.height = image.height();
+        self.source_depth = buffer::depth(&image);
+        self.tool = Tool::None;
+        self.crop_tool = CropTool::new(self.width, self.height);
+        self.perspective_tool = PerspectiveTool::default();
+        self.reset_affine();
+        self.selection = None;
+        self.selection_tool = SelectionTool::default();
+        self.layers.clear();
+        self.merge_sources = [None; 4];
+        self.history.clear();
+        self.hovered = None;
+        self.denoise_job = None;
+        self.reset_view();
     }
 
-    fn decode_data(data: Vec<u8>) -> DynamicImage {
-        let reader = Reader::new(Cursor::new(&data[..]))
-            .with_guessed_format()
-            .expect("Couldn't guess file format.");
+    /// Fits the image into the viewport, without enlarging small images.
+    fn reset_view(&mut self) {
+        let (width, height) = self.display_size();
 
-        reader.decode().expect("Unable to decode image.")
+        self.viewport.fit(width, height);
+        if self.viewport.zoom > 1.0 {
+            self.viewport.zoom = 1.0;
+            self.viewport.center(width, height);
+        }
     }
-}
-
-impl Component for Image {
-    type Message = Msg;
-    type Properties = Props;
 
-    fn create(ctx: &Context<Self>) -> Self {
-        Self::new_with_data(ctx.props().image_data.clone())
+    fn reset_affine(&mut self) {
+        self.affine_params = AffineParams::default();
+        self.affine_matrix = transform::IDENTITY_AFFINE;
+        self.affine_mode = AffineMode::Parameters;
+        self.affine_preview = None;
     }
 
-    fn view(&self, ctx: &Context<Self>) -> Html {
-        html! {
-            <>
-                <div>
-                    <div>
-                        <label>{"Color component"}</label>
-                        <select ref={self.color_select_ref.clone()}>
-                            <option value="red">{ "Red" }</option>
-                            <option value="green">{ "Green" }</option>
-                            <option value="blue">{ "Blue" }</option>
-                            <option value="alpha">{ "Alpha" }</option>
-                        </select>
-                        <label>{"Operation"}</label>
-                        <select ref={self.operation_select_ref.clone()}>
-                            <option value="add">{ "Add" }</option>
-                            <option value="subtract">{ "Subtract" }</option>
-                            <option value="multiply">{ "Multiply" }</option>
-                            <option value="divide">{ "Divide" }</option>
-                        </select>
-                        <input type="number" min="0" max="255" step="1" value={self.input_value.to_string()}
-                            onchange={ctx.link().callback(|event: Event| Msg::ValueChanged(event))} />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyOperation)} value="Apply" />
-                    </div>
-                    <div>
-                        <label>{"Brightness"}</label>
-                        <input type="range" min="-1" max="1" step="0.01" value="0"
-                            onchange={ctx.link().callback(|event: Event| Msg::BrightnessChanged(event))} />
-                    </div>
-                    <div>
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::ToGrayscaleAvg)} value="To grayscale (avg)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::ToGrayscaleAvgWeighted)} value="To grayscale (avg weighted)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterSmooth)} value="Filter (smooth)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterMedian)} value="Filter (median)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterEdgeDetection)} value="Filter (edge detection)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterSharpen)} value="Filter (sharpen)" />
-                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterGaussianBlur)} value="Filter (gaussian blur)" />
-                    </div>
-                </div>
-                <div>
-                    <canvas
-                        ref={self.canvas_ref.clone()}
-                        width={self.width.to_string()}
-                        height={self.height.to_string()}
-                    />
-                </div>
-            </>
+    fn update_affine_preview(&mut self) {
+        self.affine_preview = transform::warp_affine(
+            &self.bitmap_data,
+            self.width,
+            self.height,
+            &self.affine_matrix,
+            self.affine_interpolation,
+        );
+
+        if let (Some(mask), Some(preview)) = (&self.selection, &mut self.affine_preview) {
+            mask.apply(&self.bitmap_data, preview);
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
-        match msg {
-            Msg::ApplyOperation => {
-                let color_select = self.color_select_ref.cast::<HtmlSelectElement>().unwrap();
-                let color = match color_select.value().as_str() {
-                    "red" => ColorComponent::Red,
-                    "green" => ColorComponent::Green,
-                    "blue" => ColorComponent::Blue,
-                    "alpha" => ColorComponent::Alpha,
-                    _ => panic!("Invalid color selection"),
-                };
-
-                let op_select = self
-                    .operation_select_ref
-                    .cast::<HtmlSelectElement>()
-                    .unwrap();
-                let op: &dyn Fn(f32, f32) -> f32 = match op_select.value().as_str() {
-                    "add" => &f32::add,
-                    "subtract" => &f32::sub,
-                    "multiply" => &f32::mul,
-                    "divide" => &f32::div,
-                    _ => panic!("Invalid operation selection"),
-                };
+    fn finish_selection(&mut self, points: Vec<(f64, f64)>) {
+        let mask =
+            self.selection_tool
+                .build_mask(&points, &self.bitmap_data, self.width, self.height);
 
-                self.apply_point_fn(color, self.input_value, op);
+        if let Some(mask) = mask {
+            self.combine_selection(mask, self.selection_tool.mode);
+        }
+    }
+
+    /// The original image at the current image size. Crops and
+    /// perspective warps are applied to it as well, so it is only
+    /// stretched if something else has changed the dimensions since.
+    fn original_data(&self) -> Cow<'_, [u8]> {
+        if self.original.width() == self.width && self.original.height() == self.height {
+            Cow::Borrowed(self.original.as_raw())
+        } else {
+            Cow::Owned(arithmetic::fit_operand(
+                &self.original,
+                self.width,
+                self.height,
+                OperandFit::Resize,
+            ))
+        }
+    }
+
+    fn display_size(&self) -> (u32, u32) {
+        match self.compare_mode {
+            CompareMode::SideBySide if self.spectrum_view == SpectrumView::Off => {
+                (self.width * 2, self.height)
+            }
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// Resets the spectral mask to pass everything if the image size no
+    /// longer matches it.
+    fn ensure_spectral_mask(&mut self) {
+        let size = (self.width * self.height) as usize;
+
+        if self.spectral_mask.len() != size {
+            self.spectral_mask = vec![1.0; size];
+        }
+    }
+
+    /// Recomputes the spectrum if it is shown and the image has changed.
+    fn refresh_spectrum(&mut self) {
+        if self.spectrum_view == SpectrumView::Off {
+            return;
+        }
+
+        self.ensure_spectral_mask();
+        if self.spectrum.is_none() || self.spectrum_source != self.bitmap_data {
+            self.spectrum = Some(Spectrum::new(&self.bitmap_data, self.width, self.height));
+            self.spectrum_source = self.bitmap_data.clone();
+        }
+    }
+
+    fn spectrum_data(&self, spectrum: &Spectrum) -> Vec<u8> {
+        match self.spectrum_view {
+            SpectrumView::Phase => spectrum.phase_image(),
+            _ => spectrum
+                .magnitude_image(&spectrum.gains(&self.frequency_filter, &self.spectral_mask)),
+        }
+    }
+
+    /// Builds the pixels shown on the canvas: the image or a pending
+    /// preview, with layers composited on top, then either the comparison
+    /// view or the selection highlight.
+    fn display_data(&self) -> Cow<'_, [u8]> {
+        if let (SpectrumView::Magnitude | SpectrumView::Phase, Some(spectrum)) =
+            (self.spectrum_view, &self.spectrum)
+        {
+            return Cow::Owned(self.spectrum_data(spectrum));
+        }
+
+        let mut data = match (&self.tool, &self.affine_preview) {
+            (Tool::Affine, Some(preview)) => Cow::Borrowed(&preview[..]),
+            _ => Cow::Borrowed(&self.bitmap_data[..]),
+        };
+
+        if !self.layers.is_empty() {
+            data = Cow::Owned(layers::flatten(
+                &data,
+                self.width,
+                self.height,
+                &self.layers,
+            ));
+        }
+
+        if self.show_original {
+            return self.original_data();
+        }
+
+        match self.compare_mode {
+            CompareMode::Off => {}
+            CompareMode::SideBySide => {
+                return Cow::Owned(compare::side_by_side(
+                    &data,
+                    &self.original_data(),
+                    self.width,
+                ));
+            }
+            CompareMode::Split => {
+                let split = (self.split_position * self.width as f64).round() as u32;
+                return Cow::Owned(compare::split_view(
+                    &data,
+                    &self.original_data(),
+                    self.width,
+                    split,
+                ));
+            }
+            CompareMode::Difference => {
+                return Cow::Owned(compare::difference_heatmap(&data, &self.original_data()).0);
+            }
+        }
+
+        match &self.selection {
+            Some(mask) => Cow::Owned(mask.highlight(&data)),
+            None => data,
+        }
+    }
+
+    fn draw_split_line(&self, canvas_ctx: &CanvasRenderingContext2d) {
+        let x = self.split_position * self.width as f64;
+
+        canvas_ctx.set_line_width(2.0 * self.client_pixel_size());
+        canvas_ctx.set_stroke_style(&JsValue::from_str("white"));
+        canvas_ctx.begin_path();
+        canvas_ctx.move_to(x, 0.0);
+        canvas_ctx.line_to(x, self.height as f64);
+        canvas_ctx.stroke();
+    }
+
+    fn view_compare_controls(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+                <label>{"Compare with original"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::CompareModeChanged(event))}>
+                    <option value="off" selected={self.compare_mode == CompareMode::Off}>{ "Off" }</option>
+                    <option value="side-by-side" selected={self.compare_mode == CompareMode::SideBySide}>{ "Side by side" }</option>
+                    <option value="split" selected={self.compare_mode == CompareMode::Split}>{ "Split" }</option>
+                    <option value="difference" selected={self.compare_mode == CompareMode::Difference}>{ "Difference heatmap" }</option>
+                </select>
+                <input type="button" value="Hold to show original"
+                    onmousedown={ctx.link().callback(|_| Msg::ShowOriginal(true))}
+                    onmouseup={ctx.link().callback(|_| Msg::ShowOriginal(false))}
+                    onmouseleave={ctx.link().callback(|_| Msg::ShowOriginal(false))}
+                    ontouchstart={ctx.link().callback(|_| Msg::ShowOriginal(true))}
+                    ontouchend={ctx.link().callback(|_| Msg::ShowOriginal(false))} />
+            </div>
+        }
+    }
+
+    fn view_metrics(&self, ctx: &Context<Self>) -> Html {
+        let format_db = |value: f64| {
+            if value.is_infinite() {
+                "∞".to_string()
+            } else {
+                format!("{:.2} dB", value)
+            }
+        };
+        let rows = self.metrics.iter().flat_map(|report| {
+            let channels = ["Red", "Green", "Blue"]
+                .iter()
+                .zip(report.channels.iter())
+                .chain(std::iter::once((&"Overall", &report.overall)));
+
+            channels.map(|(name, quality)| {
+                html! {
+                    <tr>
+                        <td>{ *name }</td>
+                        <td>{ format!("{:.3}", quality.mse) }</td>
+                        <td>{ format_db(quality.psnr) }</td>
+                        <td>{ format!("{:.4}", quality.ssim) }</td>
+                        <td>{ format!("{:.4}", quality.ms_ssim) }</td>
+                    </tr>
+                }
+            })
+        });
+
+        html! {
+            <div>
+                <label>{"Metrics"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::MetricsSourceChanged(event))}>
+                    <option value="original" selected={self.metrics_source == MetricsSource::OriginalVsCurrent}>{ "Original vs current" }</option>
+                    <option value="operand" selected={self.metrics_source == MetricsSource::CurrentVsOperand}>{ "Second image vs current" }</option>
+                </select>
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ComputeMetrics)} value="Compute" />
+                if self.metrics.is_some() {
+                    <table>
+                        <tr>
+                            <th>{"Channel"}</th>
+                            <th>{"MSE"}</th>
+                            <th>{"PSNR"}</th>
+                            <th>{"SSIM"}</th>
+                            <th>{"MS-SSIM"}</th>
+                        </tr>
+                        { for rows }
+                    </table>
+                }
+            </div>
+        }
+    }
+
+    /// Maps a pointer position in client coordinates onto canvas pixels,
+    /// accounting for any CSS scaling of the canvas.
+    fn client_to_canvas(&self, client_x: i32, client_y: i32) -> (f64, f64) {
+        let rect = self
+            .canvas_ref
+            .cast::<HtmlCanvasElement>()
+            .unwrap()
+            .get_bounding_client_rect();
+        let x = (client_x as f64 - rect.left()) * self.viewport.width as f64 / rect.width();
+        let y = (client_y as f64 - rect.top()) * self.viewport.height as f64 / rect.height();
+
+        (x, y)
+    }
+
+    /// Maps a pointer position in client coordinates onto image pixels.
+    fn client_to_image(&self, client_x: i32, client_y: i32) -> (f64, f64) {
+        let (x, y) = self.client_to_canvas(client_x, client_y);
+
+        self.viewport.canvas_to_image(x, y)
+    }
+
+    /// Size of one CSS pixel in image pixels.
+    fn client_pixel_size(&self) -> f64 {
+        let rect = self
+            .canvas_ref
+            .cast::<HtmlCanvasElement>()
+            .unwrap()
+            .get_bounding_client_rect();
+
+        if rect.width() > 0.0 {
+            self.viewport.width as f64 / rect.width() / self.viewport.zoom
+        } else {
+            1.0 / self.viewport.zoom
+        }
+    }
+
+    fn zoom_at_client(&mut self, zoom: f64, client_x: i32, client_y: i32) {
+        let (x, y) = self.client_to_canvas(client_x, client_y);
+
+        self.viewport.zoom_at(zoom, x, y);
+    }
+
+    /// Outlines every visible pixel; only used at high zoom levels.
+    fn draw_pixel_grid(&self, canvas_ctx: &CanvasRenderingContext2d) {
+        let (width, height) = self.display_size();
+        let (left, top, right, bottom) = self.viewport.visible_pixels(width, height);
+
+        canvas_ctx.set_line_width(1.0 / self.viewport.zoom);
+        canvas_ctx.set_stroke_style(&JsValue::from_str("rgba(128, 128, 128, 0.6)"));
+        canvas_ctx.begin_path();
+        for x in left..=right {
+            canvas_ctx.move_to(x as f64, top as f64);
+            canvas_ctx.line_to(x as f64, bottom as f64);
+        }
+        for y in top..=bottom {
+            canvas_ctx.move_to(left as f64, y as f64);
+            canvas_ctx.line_to(right as f64, y as f64);
+        }
+        canvas_ctx.stroke();
+    }
+
+    fn view_zoom_controls(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+                <label>{"Zoom"}</label>
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ZoomOut)} value="-" />
+                <span>{ format!("{:.0}%", self.viewport.zoom * 100.0) }</span>
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ZoomIn)} value="+" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ZoomFit)} value="Fit" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ZoomActualSize)} value="100%" />
+            </div>
+        }
+    }
+
+    fn draw_crop_overlay(&self, canvas_ctx: &CanvasRenderingContext2d, rect: CropRect) {
+        let width = self.width as f64;
+        let height = self.height as f64;
+        let handle_size = 8.0 * self.client_pixel_size();
+
+        canvas_ctx.set_fill_style(&JsValue::from_str("rgba(0, 0, 0, 0.5)"));
+        canvas_ctx.fill_rect(0.0, 0.0, width, rect.y);
+        canvas_ctx.fill_rect(
+            0.0,
+            rect.y + rect.height,
+            width,
+            height - rect.y - rect.height,
+        );
+        canvas_ctx.fill_rect(0.0, rect.y, rect.x, rect.height);
+        canvas_ctx.fill_rect(
+            rect.x + rect.width,
+            rect.y,
+            width - rect.x - rect.width,
+            rect.height,
+        );
+
+        canvas_ctx.set_line_width(self.client_pixel_size());
+        canvas_ctx.set_stroke_style(&JsValue::from_str("white"));
+        canvas_ctx.stroke_rect(rect.x, rect.y, rect.width, rect.height);
+
+        canvas_ctx.set_fill_style(&JsValue::from_str("white"));
+        for (x, y) in rect.handles() {
+            canvas_ctx.fill_rect(
+                x - handle_size / 2.0,
+                y - handle_size / 2.0,
+                handle_size,
+                handle_size,
+            );
+        }
+    }
+
+    fn draw_perspective_overlay(&self, canvas_ctx: &CanvasRenderingContext2d) {
+        let points = match self.perspective_tool.corners() {
+            Some(corners) => corners.to_vec(),
+            None => self.perspective_tool.points.clone(),
+        };
+        let pixel_size = self.client_pixel_size();
+
+        canvas_ctx.set_line_width(pixel_size);
+        canvas_ctx.set_stroke_style(&JsValue::from_str("white"));
+        canvas_ctx.begin_path();
+        for (i, (x, y)) in points.iter().enumerate() {
+            if i == 0 {
+                canvas_ctx.move_to(*x, *y);
+            } else {
+                canvas_ctx.line_to(*x, *y);
+            }
+        }
+        if points.len() == 4 {
+            canvas_ctx.close_path();
+        }
+        canvas_ctx.stroke();
+
+        canvas_ctx.set_fill_style(&JsValue::from_str("white"));
+        for (x, y) in points {
+            canvas_ctx.begin_path();
+            canvas_ctx
+                .arc(x, y, 5.0 * pixel_size, 0.0, std::f64::consts::TAU)
+                .unwrap();
+            canvas_ctx.fill();
+        }
+    }
+
+    fn view_perspective_controls(&self, ctx: &Context<Self>) -> Html {
+        let (width, height) = self.perspective_tool.target_size().unwrap_or((0, 0));
+
+        html! {
+            <div>
+                <span>{ format!("Corners placed: {}/4", self.perspective_tool.points.len()) }</span>
+                <label>{"Auto size"}</label>
+                <input type="checkbox" checked={self.perspective_tool.output_size.is_none()}
+                    onchange={ctx.link().callback(|event: Event| Msg::PerspectiveAutoSizeChanged(event))} />
+                <label>{"Width"}</label>
+                <input type="number" min="1" step="1" value={width.to_string()}
+                    disabled={self.perspective_tool.output_size.is_none()}
+                    onchange={ctx.link().callback(|event: Event| Msg::PerspectiveWidthChanged(event))} />
+                <label>{"Height"}</label>
+                <input type="number" min="1" step="1" value={height.to_string()}
+                    disabled={self.perspective_tool.output_size.is_none()}
+                    onchange={ctx.link().callback(|event: Event| Msg::PerspectiveHeightChanged(event))} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyPerspective)} value="Apply perspective" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ResetPerspective)} value="Reset points" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Cancel" />
+            </div>
+        }
+    }
+
+    fn draw_selection_overlay(&self, canvas_ctx: &CanvasRenderingContext2d) {
+        let points = &self.selection_tool.points;
+        if points.is_empty() {
+            return;
+        }
+
+        canvas_ctx.set_line_width(self.client_pixel_size());
+        canvas_ctx.set_stroke_style(&JsValue::from_str("white"));
+        canvas_ctx.begin_path();
+
+        match self.selection_tool.shape {
+            SelectionShape::Rectangle if points.len() == 2 => {
+                let (from, to) = (points[0], points[1]);
+                canvas_ctx.rect(from.0, from.1, to.0 - from.0, to.1 - from.1);
+            }
+            SelectionShape::Ellipse if points.len() == 2 => {
+                let (from, to) = (points[0], points[1]);
+                canvas_ctx
+                    .ellipse(
+                        (from.0 + to.0) / 2.0,
+                        (from.1 + to.1) / 2.0,
+                        (to.0 - from.0).abs() / 2.0,
+                        (to.1 - from.1).abs() / 2.0,
+                        0.0,
+                        0.0,
+                        std::f64::consts::TAU,
+                    )
+                    .unwrap();
+            }
+            SelectionShape::Lasso | SelectionShape::Polygon => {
+                canvas_ctx.move_to(points[0].0, points[0].1);
+                for (x, y) in points.iter().skip(1) {
+                    canvas_ctx.line_to(*x, *y);
+                }
+            }
+            _ => {}
+        }
+
+        canvas_ctx.stroke();
+    }
+
+    fn view_layers(&self, ctx: &Context<Self>) -> Html {
+        let layer_count = self.layers.len();
+        let rows = self.layers.iter().enumerate().rev().map(|(index, layer)| {
+            html! {
+                <div>
+                    <input type="checkbox" checked={layer.visible}
+                        onchange={ctx.link().callback(move |event: Event| Msg::LayerVisibilityChanged(index, event))} />
+                    <span>{ &layer.name }</span>
+                    <label>{"Opacity"}</label>
+                    <input type="range" min="0" max="1" step="0.01" value={layer.opacity.to_string()}
+                        onchange={ctx.link().callback(move |event: Event| Msg::LayerOpacityChanged(index, event))} />
+                    <select onchange={ctx.link().callback(move |event: Event| Msg::LayerBlendModeChanged(index, event))}>
+                        { for BlendMode::ALL.iter().enumerate().map(|(i, mode)| html! {
+                            <option value={i.to_string()} selected={*mode == layer.blend_mode}>{ mode.name() }</option>
+                        }) }
+                    </select>
+                    <input type="button" disabled={index + 1 == layer_count}
+                        onclick={ctx.link().callback(move |_| Msg::MoveLayerUp(index))} value="Up" />
+                    <input type="button" disabled={index == 0}
+                        onclick={ctx.link().callback(move |_| Msg::MoveLayerDown(index))} value="Down" />
+                    <input type="button" onclick={ctx.link().callback(move |_| Msg::RemoveLayer(index))} value="Remove" />
+                </div>
+            }
+        });
+
+        html! {
+            <div>
+                <label>{"Add layer"}</label>
+                <input type="file" onchange={ctx.link().callback(|event: Event| Msg::AddLayer(event))} />
+                if !self.layers.is_empty() {
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::FlattenLayers)} value="Flatten" />
+                }
+                { for rows }
+            </div>
+        }
+    }
+
+    fn view_image_operation_controls(&self, ctx: &Context<Self>) -> Html {
+        let operation = self.image_operation;
+        let operations = [
+            ("add", "Add", ImageOperation::Add),
+            ("subtract", "Subtract", ImageOperation::Subtract),
+            ("multiply", "Multiply", ImageOperation::Multiply),
+            ("divide", "Divide", ImageOperation::Divide),
+            (
+                "difference",
+                "Absolute difference",
+                ImageOperation::AbsDifference,
+            ),
+            ("min", "Min", ImageOperation::Min),
+            ("max", "Max", ImageOperation::Max),
+            ("average", "Average", ImageOperation::Average),
+            ("blend", "Blend", ImageOperation::Blend(0.5)),
+            ("and", "AND", ImageOperation::And),
+            ("or", "OR", ImageOperation::Or),
+            ("xor", "XOR", ImageOperation::Xor),
+            ("not", "NOT", ImageOperation::Not),
+        ];
+        let is_selected = |candidate: &ImageOperation| match (candidate, &operation) {
+            (ImageOperation::Blend(_), ImageOperation::Blend(_)) => true,
+            _ => *candidate == operation,
+        };
+
+        html! {
+            <div>
+                <label>{"Second image"}</label>
+                <input type="file" onchange={ctx.link().callback(|event: Event| Msg::OperandUpload(event))} />
+                if let Some((name, operand)) = &self.operand {
+                    <span>{ format!("{} ({}x{})", name, operand.width(), operand.height()) }</span>
+                }
+                <label>{"Operation"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::ImageOperationChanged(event))}>
+                    { for operations.iter().map(|(value, label, candidate)| html! {
+                        <option value={*value} selected={is_selected(candidate)}>{ *label }</option>
+                    }) }
+                </select>
+                if let ImageOperation::Blend(weight) = operation {
+                    <label>{"Weight"}</label>
+                    <input type="range" min="0" max="1" step="0.01" value={weight.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::BlendWeightChanged(event))} />
+                }
+                <label>{"If sizes differ"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::OperandFitChanged(event))}>
+                    <option value="resize" selected={self.operand_fit == OperandFit::Resize}>{ "Resize" }</option>
+                    <option value="top-left" selected={self.operand_fit == OperandFit::AlignTopLeft}>{ "Align top-left" }</option>
+                    <option value="center" selected={self.operand_fit == OperandFit::AlignCenter}>{ "Align centre" }</option>
+                </select>
+                <input type="button" disabled={self.operand.is_none() && operation != ImageOperation::Not}
+                    onclick={ctx.link().callback(|_| Msg::ApplyImageOperation)} value="Apply" />
+            </div>
+        }
+    }
+
+    fn paint_spectral_mask(&mut self, x: f64, y: f64) {
+        fft::paint_mask(
+            &mut self.spectral_mask,
+            self.width,
+            self.height,
+            x,
+            y,
+            self.mask_brush,
+        );
+    }
+
+    /// Records the image pixel under the pointer. Returns whether it changed.
+    fn update_hovered(&mut self, x: f64, y: f64) -> bool {
+        let hovered = if x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64 {
+            Some((x as u32, y as u32))
+        } else {
+            None
+        };
+        let changed = hovered != self.hovered;
+        self.hovered = hovered;
+
+        changed
+    }
+
+    fn view_eyedropper_controls(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+                <label>{"Sample size"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::EyedropperSizeChanged(event))}>
+                    { for [1, 3, 5, 11, 31].iter().map(|size| html! {
+                        <option value={size.to_string()} selected={*size == self.eyedropper_size}>{ format!("{0}x{0}", size) }</option>
+                    }) }
+                </select>
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Done" />
+            </div>
+        }
+    }
+
+    fn view_draw_controls(&self, ctx: &Context<Self>) -> Html {
+        let settings = &self.draw_settings;
+        let param = |param: DrawParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::DrawParamChanged(param, event))
+        };
+        let tools = [
+            ("brush", "Brush", DrawTool::Brush),
+            ("eraser", "Eraser", DrawTool::Eraser),
+            ("line", "Line", DrawTool::Line),
+            ("rectangle", "Rectangle", DrawTool::Rectangle),
+            ("ellipse", "Ellipse", DrawTool::Ellipse),
+            ("fill", "Fill", DrawTool::Fill),
+        ];
+
+        html! {
+            <div>
+                <select onchange={ctx.link().callback(|event: Event| Msg::DrawToolChanged(event))}>
+                    { for tools.iter().map(|(value, label, tool)| html! {
+                        <option value={*value} selected={*tool == settings.tool}>{ *label }</option>
+                    }) }
+                </select>
+                if settings.tool != DrawTool::Fill {
+                    <label>{"Size"}</label>
+                    <input type="number" min="1" max="500" step="1" value={settings.size.to_string()}
+                        onchange={param(DrawParam::Size)} />
+                    <label>{"Hardness"}</label>
+                    <input type="range" min="0" max="1" step="0.01" value={settings.hardness.to_string()}
+                        onchange={param(DrawParam::Hardness)} />
+                }
+                <label>{"Opacity"}</label>
+                <input type="range" min="0" max="1" step="0.01" value={settings.opacity.to_string()}
+                    onchange={param(DrawParam::Opacity)} />
+                if matches!(settings.tool, DrawTool::Rectangle | DrawTool::Ellipse) {
+                    <label>{"Filled"}</label>
+                    <input type="checkbox" checked={settings.filled}
+                        onchange={ctx.link().callback(|event: Event| Msg::DrawFilledChanged(event))} />
+                }
+                if settings.tool == DrawTool::Fill {
+                    <label>{"Tolerance"}</label>
+                    <input type="number" min="0" max="255" step="1" value={settings.tolerance.to_string()}
+                        onchange={param(DrawParam::Tolerance)} />
+                    <select onchange={ctx.link().callback(|event: Event| Msg::FillConnectivityChanged(event))}>
+                        <option value="4" selected={settings.connectivity == Connectivity::Four}>{ "4-connected" }</option>
+                        <option value="8" selected={settings.connectivity == Connectivity::Eight}>{ "8-connected" }</option>
+                    </select>
+                }
+                <input type="button" onclick={ctx.link().callback(|_| Msg::Undo)} value="Undo"
+                    disabled={self.history.is_empty()} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Done" />
+            </div>
+        }
+    }
+
+    fn view_status_bar(&self, ctx: &Context<Self>) -> Html {
+        let [r, g, b, _] = self.active_color;
+        let inspector = self
+            .hovered
+            .filter(|(x, y)| *x < self.width && *y < self.height)
+            .map(|(x, y)| {
+                let index = (y as usize * self.width as usize + x as usize) * 4;
+                let pixel = &self.bitmap_data[index..index + 4];
+                let (fr, fg, fb) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+                let (h, s, l) = Self::rgb_to_hsl(fr, fg, fb);
+                let (hue, saturation, value) = color::rgb_to_hsv(fr, fg, fb);
+                let (lightness, a, lab_b) = color::rgb_to_lab(fr, fg, fb);
+
+                format!(
+                    "({}, {})  RGBA({}, {}, {}, {})  {}  HSL({:.0}°, {:.0}%, {:.0}%)  HSV({:.0}°, {:.0}%, {:.0}%)  Lab({:.1}, {:.1}, {:.1})",
+                    x,
+                    y,
+                    pixel[0],
+                    pixel[1],
+                    pixel[2],
+                    pixel[3],
+                    color::to_hex(pixel[0], pixel[1], pixel[2]),
+                    h * 360.0,
+                    s * 100.0,
+                    l * 100.0,
+                    hue * 360.0,
+                    saturation * 100.0,
+                    value * 100.0,
+                    lightness,
+                    a,
+                    lab_b,
+                )
+            });
+
+        html! {
+            <div>
+                <label>{"Active colour"}</label>
+                <input type="color" value={color::to_hex(r, g, b)}
+                    onchange={ctx.link().callback(|event: Event| Msg::ActiveColorChanged(event))} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Eyedropper))} value="Eyedropper" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ExportImage)} value="Export PNG" />
+                <span>{ format!("{}-bit", self.source_depth) }</span>
+                <span>{ inspector.unwrap_or_default() }</span>
+            </div>
+        }
+    }
+
+    fn view_selection_controls(&self, ctx: &Context<Self>) -> Html {
+        let tool = &self.selection_tool;
+
+        html! {
+            <div>
+                <div>
+                    <label>{"Shape"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::SelectionShapeChanged(event))}>
+                        <option value="rectangle" selected={tool.shape == SelectionShape::Rectangle}>{ "Rectangle" }</option>
+                        <option value="ellipse" selected={tool.shape == SelectionShape::Ellipse}>{ "Ellipse" }</option>
+                        <option value="lasso" selected={tool.shape == SelectionShape::Lasso}>{ "Lasso" }</option>
+                        <option value="polygon" selected={tool.shape == SelectionShape::Polygon}>{ "Polygon" }</option>
+                        <option value="magic-wand" selected={tool.shape == SelectionShape::MagicWand}>{ "Magic wand" }</option>
+                    </select>
+                    <label>{"Mode"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::SelectionModeChanged(event))}>
+                        <option value="replace" selected={tool.mode == SelectionMode::Replace}>{ "Replace" }</option>
+                        <option value="add" selected={tool.mode == SelectionMode::Add}>{ "Add" }</option>
+                        <option value="subtract" selected={tool.mode == SelectionMode::Subtract}>{ "Subtract" }</option>
+                        <option value="intersect" selected={tool.mode == SelectionMode::Intersect}>{ "Intersect" }</option>
+                    </select>
+                    if tool.shape == SelectionShape::MagicWand {
+                        <label>{"Tolerance"}</label>
+                        <input type="number" min="0" max="255" step="1" value={tool.tolerance.to_string()}
+                            onchange={ctx.link().callback(|event: Event| Msg::SelectionToleranceChanged(event))} />
+                    }
+                    if tool.shape == SelectionShape::Polygon {
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ClosePolygonSelection)} value="Close polygon" />
+                    }
+                </div>
+                <div>
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SelectAll)} value="Select all" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::Deselect)} value="Deselect" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::InvertSelection)} value="Invert" />
+                    <label>{"Radius"}</label>
+                    <input type="number" min="1" step="1" value={tool.radius.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::SelectionRadiusChanged(event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::GrowSelection)} value="Grow" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ShrinkSelection)} value="Shrink" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::FeatherSelection)} value="Feather" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Done" />
+                </div>
+            </div>
+        }
+    }
+
+    fn view_affine_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.affine_params;
+        let matrix_mode = self.affine_mode == AffineMode::Matrix;
+        let param = |label: &str, param: AffineParam, value: f64, step: &str| {
+            html! {
+                <>
+                    <label>{ label }</label>
+                    <input type="number" step={step.to_string()} value={value.to_string()} disabled={matrix_mode}
+                        oninput={ctx.link().callback(move |event: InputEvent| Msg::AffineParamChanged(param, event.into()))} />
+                </>
+            }
+        };
+        let cell = |row: usize, col: usize| {
+            // Shown as typed while editing, so live updates don't reformat
+            // the field under the cursor.
+            let value = if matrix_mode {
+                self.affine_matrix[row][col].to_string()
+            } else {
+                format!("{:.4}", self.affine_matrix[row][col])
+            };
+            html! {
+                <input type="number" step="0.01" value={value} disabled={!matrix_mode}
+                    oninput={ctx.link().callback(move |event: InputEvent| Msg::AffineMatrixChanged(row, col, event.into()))} />
+            }
+        };
+        let [[a, b, c], [d, e, f]] = self.affine_matrix;
+        let matrix_text = format!(
+            "[[{:.4}, {:.4}, {:.4}], [{:.4}, {:.4}, {:.4}]]",
+            a, b, c, d, e, f
+        );
+
+        html! {
+            <div>
+                <div>
+                    <label>{"Edit"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::AffineModeChanged(event))}>
+                        <option value="parameters" selected={!matrix_mode}>{ "Parameters" }</option>
+                        <option value="matrix" selected={matrix_mode}>{ "Matrix" }</option>
+                    </select>
+                    if matrix_mode {
+                        <span>{"Switching back to parameters replaces this matrix"}</span>
+                    }
+                </div>
+                <div>
+                    { param("Scale X", AffineParam::ScaleX, params.scale_x, "0.01") }
+                    { param("Scale Y", AffineParam::ScaleY, params.scale_y, "0.01") }
+                    { param("Rotation (deg)", AffineParam::Rotation, params.rotation, "1") }
+                    { param("Shear X (deg)", AffineParam::ShearX, params.shear_x, "1") }
+                    { param("Shear Y (deg)", AffineParam::ShearY, params.shear_y, "1") }
+                    { param("Translate X", AffineParam::TranslateX, params.translate_x, "1") }
+                    { param("Translate Y", AffineParam::TranslateY, params.translate_y, "1") }
+                </div>
+                <div>
+                    <label>{"Matrix"}</label>
+                    { cell(0, 0) }{ cell(0, 1) }{ cell(0, 2) }
+                </div>
+                <div>
+                    <label>{"Matrix"}</label>
+                    { cell(1, 0) }{ cell(1, 1) }{ cell(1, 2) }
+                </div>
+                <div>
+                    <input type="text" readonly=true size="60" value={matrix_text} />
+                    <label>{"Interpolation"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::AffineInterpolationChanged(event))}>
+                        <option value="bilinear" selected={self.affine_interpolation == Interpolation::Bilinear}>{ "Bilinear" }</option>
+                        <option value="nearest" selected={self.affine_interpolation == Interpolation::Nearest}>{ "Nearest neighbour" }</option>
+                    </select>
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyAffine)} value="Apply transform" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ResetAffine)} value="Reset" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Cancel" />
+                </div>
+            </div>
+        }
+    }
+
+    fn view_crop_controls(&self, ctx: &Context<Self>) -> Html {
+        let (x, y, width, height) = match self.crop_tool.rect {
+            Some(rect) => rect.to_pixels(self.width, self.height),
+            None => (0, 0, self.width, self.height),
+        };
+        let (ratio_width, ratio_height) = match self.crop_tool.aspect {
+            AspectRatio::Custom(w, h) => (w, h),
+            _ => (1.0, 1.0),
+        };
+        let field = |field: CropField| {
+            ctx.link()
+                .callback(move |event: Event| Msg::CropFieldChanged(field, event))
+        };
+
+        html! {
+            <div>
+                <label>{"Aspect ratio"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::CropAspectChanged(event))}>
+                    <option value="free" selected={self.crop_tool.aspect == AspectRatio::Free}>{ "Free" }</option>
+                    <option value="1:1" selected={self.crop_tool.aspect == AspectRatio::Square}>{ "1:1" }</option>
+                    <option value="4:3" selected={self.crop_tool.aspect == AspectRatio::FourThree}>{ "4:3" }</option>
+                    <option value="16:9" selected={self.crop_tool.aspect == AspectRatio::SixteenNine}>{ "16:9" }</option>
+                    <option value="custom" selected={matches!(self.crop_tool.aspect, AspectRatio::Custom(_, _))}>{ "Custom" }</option>
+                </select>
+                if matches!(self.crop_tool.aspect, AspectRatio::Custom(_, _)) {
+                    <input type="number" min="1" step="1" value={ratio_width.to_string()}
+                        onchange={field(CropField::RatioWidth)} />
+                    <span>{":"}</span>
+                    <input type="number" min="1" step="1" value={ratio_height.to_string()}
+                        onchange={field(CropField::RatioHeight)} />
+                }
+                <label>{"X"}</label>
+                <input type="number" min="0" step="1" value={x.to_string()} onchange={field(CropField::X)} />
+                <label>{"Y"}</label>
+                <input type="number" min="0" step="1" value={y.to_string()} onchange={field(CropField::Y)} />
+                <label>{"Width"}</label>
+                <input type="number" min="1" step="1" value={width.to_string()} onchange={field(CropField::Width)} />
+                <label>{"Height"}</label>
+                <input type="number" min="1" step="1" value={height.to_string()} onchange={field(CropField::Height)} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyCrop)} value="Apply crop" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Cancel" />
+            </div>
+        }
+    }
+
+    fn view_unsharp_controls(&self, ctx: &Context<Self>) -> Html {
+        let param = |param: UnsharpParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::UnsharpParamChanged(param, event))
+        };
+
+        html! {
+            <div>
+                <label>{"Unsharp mask: amount"}</label>
+                <input type="number" min="0" max="10" step="0.1" value={self.unsharp_mask.amount.to_string()}
+                    onchange={param(UnsharpParam::Amount)} />
+                <label>{"Radius"}</label>
+                <input type="number" min="0.1" max="50" step="0.1" value={self.unsharp_mask.radius.to_string()}
+                    onchange={param(UnsharpParam::Radius)} />
+                <label>{"Threshold"}</label>
+                <input type="number" min="0" max="255" step="1" value={self.unsharp_mask.threshold.to_string()}
+                    onchange={param(UnsharpParam::Threshold)} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyUnsharpMask)} value="Sharpen" />
+            </div>
+        }
+    }
+
+    fn view_frequency_controls(&self, ctx: &Context<Self>) -> Html {
+        let filter = &self.frequency_filter;
+        let param = |param: FrequencyParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::FrequencyParamChanged(param, event))
+        };
+        let is_band = matches!(filter.kind, FilterKind::BandPass | FilterKind::BandStop);
+
+        html! {
+            <div>
+                <label>{"Spectrum"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::SpectrumViewChanged(event))}>
+                    <option value="off" selected={self.spectrum_view == SpectrumView::Off}>{ "Hidden" }</option>
+                    <option value="magnitude" selected={self.spectrum_view == SpectrumView::Magnitude}>{ "Log magnitude" }</option>
+                    <option value="phase" selected={self.spectrum_view == SpectrumView::Phase}>{ "Phase" }</option>
+                </select>
+                <label>{"Filter"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::FilterKindChanged(event))}>
+                    <option value="none" selected={filter.kind == FilterKind::None}>{ "None" }</option>
+                    <option value="low-pass" selected={filter.kind == FilterKind::LowPass}>{ "Low-pass" }</option>
+                    <option value="high-pass" selected={filter.kind == FilterKind::HighPass}>{ "High-pass" }</option>
+                    <option value="band-pass" selected={filter.kind == FilterKind::BandPass}>{ "Band-pass" }</option>
+                    <option value="band-stop" selected={filter.kind == FilterKind::BandStop}>{ "Band-stop" }</option>
+                </select>
+                if filter.kind != FilterKind::None {
+                    <select onchange={ctx.link().callback(|event: Event| Msg::FilterShapeChanged(event))}>
+                        <option value="ideal" selected={filter.shape == FilterShape::Ideal}>{ "Ideal" }</option>
+                        <option value="butterworth" selected={filter.shape == FilterShape::Butterworth}>{ "Butterworth" }</option>
+                        <option value="gaussian" selected={filter.shape == FilterShape::Gaussian}>{ "Gaussian" }</option>
+                    </select>
+                    <label>{ if is_band { "Centre" } else { "Cut-off" } }</label>
+                    <input type="number" min="0" max="0.71" step="0.01" value={filter.cutoff.to_string()}
+                        onchange={param(FrequencyParam::Cutoff)} />
+                    if is_band {
+                        <label>{"Band width"}</label>
+                        <input type="number" min="0.01" max="0.71" step="0.01" value={filter.band_width.to_string()}
+                            onchange={param(FrequencyParam::BandWidth)} />
+                    }
+                    if filter.shape == FilterShape::Butterworth {
+                        <label>{"Order"}</label>
+                        <input type="number" min="1" max="10" step="1" value={filter.order.to_string()}
+                            onchange={param(FrequencyParam::Order)} />
+                    }
+                }
+                <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::SpectralMask))} value="Paint mask" />
+                if self.tool == Tool::SpectralMask {
+                    <label>{"Brush"}</label>
+                    <input type="number" min="1" step="1" value={self.mask_brush.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::MaskBrushChanged(event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::None))} value="Done" />
+                }
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ClearSpectralMask)} value="Clear mask" />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyFrequencyFilter)} value="Apply" />
+            </div>
+        }
+    }
+
+    fn view_dither_controls(&self, ctx: &Context<Self>) -> Html {
+        let target = &self.dither_target;
+        let method = self.dither_method;
+        let is_palette = |palette: &[[u8; 3]]| match target {
+            DitherTarget::Palette(colors) => colors[..] == *palette,
+            _ => false,
+        };
+        let methods = [
+            ("none", "None", DitherMethod::None),
+            (
+                "floyd-steinberg",
+                "Floyd-Steinberg",
+                DitherMethod::FloydSteinberg,
+            ),
+            (
+                "jarvis",
+                "Jarvis-Judice-Ninke",
+                DitherMethod::JarvisJudiceNinke,
+            ),
+            ("stucki", "Stucki", DitherMethod::Stucki),
+            ("atkinson", "Atkinson", DitherMethod::Atkinson),
+            ("sierra", "Sierra", DitherMethod::Sierra),
+            ("bayer-2", "Bayer 2x2", DitherMethod::Bayer(2)),
+            ("bayer-4", "Bayer 4x4", DitherMethod::Bayer(4)),
+            ("bayer-8", "Bayer 8x8", DitherMethod::Bayer(8)),
+            ("blue-noise", "Blue noise", DitherMethod::BlueNoise),
+        ];
+        let is_diffusion = !matches!(
+            method,
+            DitherMethod::None | DitherMethod::Bayer(_) | DitherMethod::BlueNoise
+        );
+
+        html! {
+            <div>
+                <label>{"Dither to"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::DitherTargetChanged(event))}>
+                    <option value="black-white" selected={*target == DitherTarget::BlackWhite}>{ "Black and white" }</option>
+                    <option value="levels" selected={matches!(target, DitherTarget::Levels(_))}>{ "Levels per channel" }</option>
+                    <option value="game-boy" selected={is_palette(&dither::GAME_BOY_PALETTE)}>{ "Game Boy palette" }</option>
+                    <option value="cga" selected={is_palette(&dither::CGA_PALETTE)}>{ "CGA palette" }</option>
+                </select>
+                if let DitherTarget::Levels(levels) = target {
+                    <input type="number" min="2" max="256" step="1" value={levels.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::DitherLevelsChanged(event))} />
+                }
+                <label>{"Method"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::DitherMethodChanged(event))}>
+                    { for methods.iter().map(|(value, label, candidate)| html! {
+                        <option value={*value} selected={*candidate == method}>{ *label }</option>
+                    }) }
+                </select>
+                if is_diffusion {
+                    <label>{"Serpentine"}</label>
+                    <input type="checkbox" checked={self.dither_serpentine}
+                        onchange={ctx.link().callback(|event: Event| Msg::DitherSerpentineChanged(event))} />
+                }
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyDither)} value="Dither" />
+            </div>
+        }
+    }
+
+    fn view_lut_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.lut_params;
+        let effect = params.effect;
+        let hex = |[r, g, b]: [u8; 3]| color::to_hex(r, g, b);
+
+        let fields = match effect {
+            LutEffect::Invert => html! {
+                { for ["Red", "Green", "Blue"].iter().enumerate().map(|(channel, label)| html! {
+                    <>
+                        <label>{ *label }</label>
+                        <input type="checkbox" checked={params.invert[channel]}
+                            onchange={ctx.link().callback(move |event: Event| Msg::InvertChannelChanged(channel, event))} />
+                    </>
+                }) }
+            },
+            LutEffect::Posterize => html! {
+                <>
+                    <label>{"Levels"}</label>
+                    <input type="number" min="2" max="256" step="1" value={params.levels.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutParamChanged(LutParam::Levels, event))} />
+                </>
+            },
+            LutEffect::Solarize => html! {
+                <>
+                    <label>{"Threshold"}</label>
+                    <input type="number" min="0" max="255" step="1" value={params.threshold.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutParamChanged(LutParam::Threshold, event))} />
+                </>
+            },
+            LutEffect::Threshold => html! {
+                <>
+                    <label>{"Threshold"}</label>
+                    <input type="number" min="0" max="255" step="1" value={params.threshold.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutParamChanged(LutParam::Threshold, event))} />
+                    <label>{"Below"}</label>
+                    <input type="color" value={hex(params.below)}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutColorChanged(LutColor::Below, event))} />
+                    <label>{"Above"}</label>
+                    <input type="color" value={hex(params.above)}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutColorChanged(LutColor::Above, event))} />
+                </>
+            },
+            LutEffect::Duotone => html! {
+                <>
+                    <label>{"Shadows"}</label>
+                    <input type="color" value={hex(params.shadow)}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutColorChanged(LutColor::Shadow, event))} />
+                    <label>{"Highlights"}</label>
+                    <input type="color" value={hex(params.highlight)}
+                        onchange={ctx.link().callback(|event: Event| Msg::LutColorChanged(LutColor::Highlight, event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SepiaPreset)} value="Sepia" />
+                </>
+            },
+            LutEffect::GradientMap => html! {
+                <>
+                    { for params.stops.iter().enumerate().map(|(index, stop)| html! {
+                        <div>
+                            <input type="color" value={hex(stop.color)}
+                                onchange={ctx.link().callback(move |event: Event| Msg::GradientStopColorChanged(index, event))} />
+                            <label>{"at"}</label>
+                            <input type="number" min="0" max="1" step="0.01" value={stop.position.to_string()}
+                                onchange={ctx.link().callback(move |event: Event| Msg::GradientStopPositionChanged(index, event))} />
+                            <input type="button" disabled={params.stops.len() <= 1}
+                                onclick={ctx.link().callback(move |_| Msg::RemoveGradientStop(index))} value="Remove" />
+                        </div>
+                    }) }
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::AddGradientStop)} value="Add stop" />
+                </>
+            },
+        };
+
+        html! {
+            <div>
+                <label>{"Effect"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::LutEffectChanged(event))}>
+                    <option value="invert" selected={effect == LutEffect::Invert}>{ "Invert" }</option>
+                    <option value="posterize" selected={effect == LutEffect::Posterize}>{ "Posterize" }</option>
+                    <option value="solarize" selected={effect == LutEffect::Solarize}>{ "Solarize" }</option>
+                    <option value="threshold" selected={effect == LutEffect::Threshold}>{ "Threshold" }</option>
+                    <option value="duotone" selected={effect == LutEffect::Duotone}>{ "Duotone" }</option>
+                    <option value="gradient-map" selected={effect == LutEffect::GradientMap}>{ "Gradient map" }</option>
+                </select>
+                { fields }
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyLut)} value="Apply effect" />
+            </div>
+        }
+    }
+
+    fn view_channel_controls(&self, ctx: &Context<Self>) -> Html {
+        let mixer = &self.channel_mixer;
+        let channel_count = if mixer.alpha { 4 } else { 3 };
+        let rows = (0..channel_count).map(|output| {
+            html! {
+                <div>
+                    <label>{ channels::CHANNEL_NAMES[output] }</label>
+                    { for (0..channel_count).map(|input| html! {
+                        <input type="number" step="0.01" value={mixer.matrix[output][input].to_string()}
+                            onchange={ctx.link().callback(move |event: Event| Msg::MixerWeightChanged(output, input, event))} />
+                    }) }
+                    <label>{"+"}</label>
+                    <input type="number" min="-255" max="255" step="1" value={mixer.offsets[output].to_string()}
+                        onchange={ctx.link().callback(move |event: Event| Msg::MixerOffsetChanged(output, event))} />
+                </div>
+            }
+        });
+        let is_preset = |preset: &MixerPreset| {
+            let candidate = ChannelMixer::preset(*preset);
+            candidate.matrix == mixer.matrix && candidate.offsets == mixer.offsets
+        };
+        let mergeable: Vec<(usize, &Layer)> = self
+            .layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.width == self.width && layer.height == self.height)
+            .collect();
+
+        html! {
+            <div>
+                <div>
+                    <label>{"Channel mixer"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::MixerPresetChanged(event))}>
+                        { for MixerPreset::ALL.iter().enumerate().map(|(i, preset)| html! {
+                            <option value={i.to_string()} selected={is_preset(preset)}>{ preset.name() }</option>
+                        }) }
+                        <option hidden={true} selected={!MixerPreset::ALL.iter().any(is_preset)}>{ "Custom" }</option>
+                    </select>
+                    <label>{"Mix alpha"}</label>
+                    <input type="checkbox" checked={mixer.alpha}
+                        onchange={ctx.link().callback(|event: Event| Msg::MixerAlphaChanged(event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyChannelMixer)} value="Mix" />
+                </div>
+                { for rows }
+                <div>
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::SplitChannels)} value="Split channels to layers" />
+                </div>
+                if !mergeable.is_empty() {
+                    <div>
+                        { for channels::CHANNEL_NAMES.iter().enumerate().map(|(channel, name)| html! {
+                            <>
+                                <label>{ *name }</label>
+                                <select onchange={ctx.link().callback(move |event: Event| Msg::MergeSourceChanged(channel, event))}>
+                                    <option value="" selected={self.merge_sources[channel].is_none()}>{ "Keep" }</option>
+                                    { for mergeable.iter().map(|(index, layer)| html! {
+                                        <option value={index.to_string()} selected={self.merge_sources[channel] == Some(*index)}>{ &layer.name }</option>
+                                    }) }
+                                </select>
+                            </>
+                        }) }
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::MergeChannels)} value="Merge channels" />
+                    </div>
+                }
+            </div>
+        }
+    }
+
+    fn view_bit_plane_controls(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div>
+                <div>
+                    <label>{"Bit plane"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::BitPlaneChannelChanged(event))}>
+                        { for channels::CHANNEL_NAMES.iter().enumerate().map(|(channel, name)| html! {
+                            <option value={channel.to_string()} selected={channel == self.bit_plane_channel}>{ *name }</option>
+                        }) }
+                    </select>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::BitPlaneChanged(event))}>
+                        { for (0..8).rev().map(|bit| html! {
+                            <option value={bit.to_string()} selected={bit == self.bit_plane}>{ format!("Bit {}", bit) }</option>
+                        }) }
+                    </select>
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ShowBitPlane)} value="Show plane" />
+                </div>
+                <div>
+                    <label>{"Keep planes"}</label>
+                    { for (0..8).rev().map(|bit| html! {
+                        <input type="checkbox" title={format!("Bit {}", bit)} checked={self.kept_planes >> bit & 1 == 1}
+                            onchange={ctx.link().callback(move |event: Event| Msg::KeptPlaneChanged(bit, event))} />
+                    }) }
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::KeepBitPlanes)} value="Reconstruct" />
+                </div>
+                <div>
+                    <label>{"Bit depth"}</label>
+                    { for channels::CHANNEL_NAMES.iter().enumerate().map(|(channel, name)| html! {
+                        <>
+                            <label>{ *name }</label>
+                            <input type="number" min="1" max="8" step="1" value={self.bit_depths[channel].to_string()}
+                                onchange={ctx.link().callback(move |event: Event| Msg::BitDepthChanged(channel, event))} />
+                        </>
+                    }) }
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ReduceBitDepth)} value="Reduce" />
+                </div>
+            </div>
+        }
+    }
+
+    fn view_stego_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.stego_params;
+        let capacity = stego::payload_capacity(self.bitmap_data.len(), params);
+        let extracted = match &self.extracted {
+            Some(Payload::Text(text)) => html! {
+                <textarea readonly={true} value={text.clone()} />
+            },
+            Some(Payload::File { name, .. }) => html! {
+                <input type="button" onclick={ctx.link().callback(|_| Msg::DownloadExtracted)}
+                    value={format!("Download {}", name)} />
+            },
+            None => html! {},
+        };
+
+        html! {
+            <div>
+                <div>
+                    <label>{"Hide in"}</label>
+                    { for channels::CHANNEL_NAMES.iter().enumerate().map(|(channel, name)| html! {
+                        <>
+                            <label>{ *name }</label>
+                            <input type="checkbox" checked={params.channels[channel]}
+                                onchange={ctx.link().callback(move |event: Event| Msg::StegoChannelChanged(channel, event))} />
+                        </>
+                    }) }
+                    <label>{"Bits"}</label>
+                    <input type="number" min="1" max="8" step="1" value={params.bits.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::StegoBitsChanged(event))} />
+                    <label>{"Password"}</label>
+                    <input type="password" value={params.password.clone()}
+                        onchange={ctx.link().callback(|event: Event| Msg::StegoPasswordChanged(event))} />
+                    <span>{ format!("Capacity: {} bytes", capacity) }</span>
+                </div>
+                <div>
+                    <textarea value={self.stego_message.clone()}
+                        onchange={ctx.link().callback(|event: Event| Msg::StegoMessageChanged(event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::EmbedText)} value="Hide message" />
+                    <input type="file" onchange={ctx.link().callback(|event: Event| Msg::StegoFileChosen(event))} />
+                    <input type="button" disabled={self.stego_file.is_none()}
+                        onclick={ctx.link().callback(|_| Msg::EmbedFile)} value="Hide file" />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::ExtractPayload)} value="Extract" />
+                </div>
+                if let Some(status) = &self.stego_status {
+                    <div>{ status }</div>
+                }
+                { extracted }
+            </div>
+        }
+    }
+
+    fn view_chroma_key_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.chroma_key;
+        let [r, g, b] = params.key;
+        let param = |param: ChromaParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::ChromaParamChanged(param, event))
+        };
+
+        html! {
+            <div>
+                <label>{"Chroma key"}</label>
+                <input type="color" value={color::to_hex(r, g, b)}
+                    onchange={ctx.link().callback(|event: Event| Msg::ChromaKeyColorChanged(event))} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ChromaKeyFromActiveColor)} value="Use active colour" />
+                <label>{"Tolerance"}</label>
+                <input type="number" min="0" max="200" step="1" value={params.tolerance.to_string()}
+                    onchange={param(ChromaParam::Tolerance)} />
+                <label>{"Softness"}</label>
+                <input type="number" min="0" max="200" step="1" value={params.softness.to_string()}
+                    onchange={param(ChromaParam::Softness)} />
+                <label>{"Spill suppression"}</label>
+                <input type="range" min="0" max="1" step="0.01" value={params.spill.to_string()}
+                    onchange={param(ChromaParam::Spill)} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyChromaKey)} value="Key out" />
+            </div>
+        }
+    }
+
+    fn view_watermark_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.watermark;
+        let param = |param: WatermarkParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::WatermarkParamChanged(param, event))
+        };
+        let [r, g, b] = params.color;
+        let placements = [
+            ("top-left", "Top left", Placement::TopLeft),
+            ("top-right", "Top right", Placement::TopRight),
+            ("center", "Centre", Placement::Center),
+            ("bottom-left", "Bottom left", Placement::BottomLeft),
+            ("bottom-right", "Bottom right", Placement::BottomRight),
+            ("tiled", "Tiled", Placement::Tiled),
+        ];
+
+        html! {
+            <div>
+                <div>
+                    <label>{"Watermark"}</label>
+                    <select onchange={ctx.link().callback(|event: Event| Msg::WatermarkPlacementChanged(event))}>
+                        { for placements.iter().map(|(value, label, placement)| html! {
+                            <option value={*value} selected={*placement == params.placement}>{ *label }</option>
+                        }) }
+                    </select>
+                    <label>{"Opacity"}</label>
+                    <input type="range" min="0" max="1" step="0.01" value={params.opacity.to_string()}
+                        onchange={param(WatermarkParam::Opacity)} />
+                    <label>{"Rotation"}</label>
+                    <input type="number" min="-180" max="180" step="1" value={params.rotation.to_string()}
+                        onchange={param(WatermarkParam::Rotation)} />
+                    <label>{"Margin"}</label>
+                    <input type="number" min="0" step="1" value={params.margin.to_string()}
+                        onchange={param(WatermarkParam::Margin)} />
+                </div>
+                <div>
+                    <input type="text" value={params.text.clone()}
+                        onchange={ctx.link().callback(|event: Event| Msg::WatermarkTextChanged(event))} />
+                    <label>{"Size"}</label>
+                    <input type="number" min="4" max="512" step="1" value={params.size.to_string()}
+                        onchange={param(WatermarkParam::Size)} />
+                    <input type="color" value={color::to_hex(r, g, b)}
+                        onchange={ctx.link().callback(|event: Event| Msg::WatermarkColorChanged(event))} />
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::StampText)} value="Stamp text" />
+                </div>
+                <div>
+                    <input type="file" onchange={ctx.link().callback(|event: Event| Msg::WatermarkImageUpload(event))} />
+                    <label>{"Scale"}</label>
+                    <input type="number" min="0.01" max="10" step="0.01" value={params.scale.to_string()}
+                        onchange={param(WatermarkParam::Scale)} />
+                    <input type="button" disabled={self.watermark_image.is_none()}
+                        onclick={ctx.link().callback(|_| Msg::StampImage)} value="Stamp image" />
+                </div>
+            </div>
+        }
+    }
+
+    fn view_quantize_controls(&self, ctx: &Context<Self>) -> Html {
+        let method = self.quantize_method;
+
+        html! {
+            <div>
+                <label>{"Quantize to"}</label>
+                <input type="number" min="1" max="256" step="1" value={self.quantize_colors.to_string()}
+                    onchange={ctx.link().callback(|event: Event| Msg::QuantizeColorsChanged(event))} />
+                <label>{"colors with"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::QuantizeMethodChanged(event))}>
+                    <option value="median-cut" selected={method == QuantizeMethod::MedianCut}>{ "Median cut" }</option>
+                    <option value="octree" selected={method == QuantizeMethod::Octree}>{ "Octree" }</option>
+                    <option value="k-means" selected={method == QuantizeMethod::KMeans}>{ "K-means" }</option>
+                </select>
+                if method == QuantizeMethod::KMeans {
+                    <label>{"Seed"}</label>
+                    <input type="number" min="0" step="1" value={self.quantize_seed.to_string()}
+                        onchange={ctx.link().callback(|event: Event| Msg::QuantizeSeedChanged(event))} />
+                }
+                <label>{"Dither"}</label>
+                <input type="checkbox" checked={self.quantize_dither}
+                    onchange={ctx.link().callback(|event: Event| Msg::QuantizeDitherChanged(event))} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyQuantize)} value="Quantize" />
+                if !self.palette.is_empty() {
+                    <div>
+                        { for self.palette.iter().map(|[r, g, b]| {
+                            let hex = color::to_hex(*r, *g, *b);
+                            html! {
+                                <span title={hex.clone()}
+                                    style={format!("display: inline-block; width: 16px; height: 16px; border: 1px solid #000; background: {}", hex)} />
+                            }
+                        }) }
+                    </div>
+                    <div>
+                        <label>{"Export palette"}</label>
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ExportPalette(PaletteFormat::Gpl))} value="GPL" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ExportPalette(PaletteFormat::Aco))} value="ACO" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ExportPalette(PaletteFormat::Json))} value="JSON" />
+                    </div>
+                }
+            </div>
+        }
+    }
+
+    fn view_noise_controls(&self, ctx: &Context<Self>) -> Html {
+        let params = &self.noise_params;
+        let (amount_label, max, step) = match params.kind {
+            NoiseKind::Gaussian => ("Sigma", "255", "1"),
+            NoiseKind::SaltAndPepper => ("Probability", "1", "0.01"),
+            NoiseKind::Speckle => ("Deviation", "1", "0.01"),
+            NoiseKind::Poisson => ("Scale", "64", "0.1"),
+            NoiseKind::Uniform => ("Range", "255", "1"),
+        };
+
+        html! {
+            <div>
+                <label>{"Noise"}</label>
+                <select onchange={ctx.link().callback(|event: Event| Msg::NoiseKindChanged(event))}>
+                    <option value="gaussian" selected={params.kind == NoiseKind::Gaussian}>{ "Gaussian" }</option>
+                    <option value="salt-and-pepper" selected={params.kind == NoiseKind::SaltAndPepper}>{ "Salt and pepper" }</option>
+                    <option value="speckle" selected={params.kind == NoiseKind::Speckle}>{ "Speckle" }</option>
+                    <option value="poisson" selected={params.kind == NoiseKind::Poisson}>{ "Poisson" }</option>
+                    <option value="uniform" selected={params.kind == NoiseKind::Uniform}>{ "Uniform" }</option>
+                </select>
+                <label>{ amount_label }</label>
+                <input type="number" min="0" max={max} step={step} value={params.amount.to_string()}
+                    onchange={ctx.link().callback(|event: Event| Msg::NoiseAmountChanged(event))} />
+                <label>{"Monochrome"}</label>
+                <input type="checkbox" checked={params.monochrome}
+                    onchange={ctx.link().callback(|event: Event| Msg::NoiseMonochromeChanged(event))} />
+                <label>{"Seed"}</label>
+                <input type="number" min="0" step="1" value={params.seed.to_string()}
+                    onchange={ctx.link().callback(|event: Event| Msg::NoiseSeedChanged(event))} />
+                <input type="button" onclick={ctx.link().callback(|_| Msg::AddNoise)} value="Add noise" />
+            </div>
+        }
+    }
+
+    fn view_denoise_controls(&self, ctx: &Context<Self>) -> Html {
+        let param = |param: DenoiserParam| {
+            ctx.link()
+                .callback(move |event: Event| Msg::DenoiserParamChanged(param, event))
+        };
+
+        let fields = match self.denoiser {
+            Denoiser::Bilateral {
+                spatial_sigma,
+                range_sigma,
+            } => html! {
+                <>
+                    <label>{"Spatial sigma"}</label>
+                    <input type="number" min="0.1" step="0.1" value={spatial_sigma.to_string()}
+                        onchange={param(DenoiserParam::SpatialSigma)} />
+                    <label>{"Range sigma"}</label>
+                    <input type="number" min="1" max="255" step="1" value={range_sigma.to_string()}
+                        onchange={param(DenoiserParam::RangeSigma)} />
+                </>
+            },
+            Denoiser::Kuwahara { radius } | Denoiser::AnisotropicKuwahara { radius } => html! {
+                <>
+                    <label>{"Radius"}</label>
+                    <input type="number" min="1" step="1" value={radius.to_string()}
+                        onchange={param(DenoiserParam::Radius)} />
+                </>
+            },
+            Denoiser::NonLocalMeans {
+                search_radius,
+                patch_radius,
+                strength,
+            } => html! {
+                <>
+                    <label>{"Search radius"}</label>
+                    <input type="number" min="1" step="1" value={search_radius.to_string()}
+                        onchange={param(DenoiserParam::SearchRadius)} />
+                    <label>{"Patch radius"}</label>
+                    <input type="number" min="0" step="1" value={patch_radius.to_string()}
+                        onchange={param(DenoiserParam::PatchRadius)} />
+                    <label>{"Strength"}</label>
+                    <input type="number" min="1" max="255" step="1" value={strength.to_string()}
+                        onchange={param(DenoiserParam::Strength)} />
+                </>
+            },
+        };
+        let is_selected = |name: &str| self.denoiser.name() == name;
+
+        html! {
+            <div>
+                <label>{"Denoise"}</label>
+                <select disabled={self.denoise_job.is_some()}
+                    onchange={ctx.link().callback(|event: Event| Msg::DenoiserChanged(event))}>
+                    <option value="bilateral" selected={is_selected("Bilateral")}>{ "Bilateral" }</option>
+                    <option value="kuwahara" selected={is_selected("Kuwahara")}>{ "Kuwahara" }</option>
+                    <option value="anisotropic-kuwahara" selected={is_selected("Anisotropic Kuwahara")}>{ "Anisotropic Kuwahara" }</option>
+                    <option value="non-local-means" selected={is_selected("Non-local means")}>{ "Non-local means" }</option>
+                </select>
+                { fields }
+                if let Some(job) = &self.denoise_job {
+                    <progress max="1" value={job.progress().to_string()} />
+                    <span>{ format!("{} {:.0}%", job.denoiser().name(), job.progress() * 100.0) }</span>
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::CancelDenoise)} value="Cancel" />
+                } else {
+                    <input type="button" onclick={ctx.link().callback(|_| Msg::StartDenoise)} value="Apply" />
+                }
+            </div>
+        }
+    }
+
+    fn decode_data(data: Vec<u8>) -> DynamicImage {
+        let reader = Reader::new(Cursor::new(&data[..]))
+            .with_guessed_format()
+            .expect("Couldn't guess file format.");
+
+        reader.decode().expect("Unable to decode image.")
+    }
+}
+
+impl Component for Image {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        Self::new_with_data(ctx.props().image_data.clone())
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+                <div>
+                    <div>
+                        <label>{"Color component"}</label>
+                        <select ref={self.color_select_ref.clone()}>
+                            <option value="red">{ "Red" }</option>
+                            <option value="green">{ "Green" }</option>
+                            <option value="blue">{ "Blue" }</option>
+                            <option value="alpha">{ "Alpha" }</option>
+                        </select>
+                        <label>{"Operation"}</label>
+                        <select ref={self.operation_select_ref.clone()}>
+                            <option value="add">{ "Add" }</option>
+                            <option value="subtract">{ "Subtract" }</option>
+                            <option value="multiply">{ "Multiply" }</option>
+                            <option value="divide">{ "Divide" }</option>
+                        </select>
+                        <input type="number" min="0" max="255" step="1" value={self.input_value.to_string()}
+                            onchange={ctx.link().callback(|event: Event| Msg::ValueChanged(event))} />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ApplyOperation)} value="Apply" />
+                    </div>
+                    <div>
+                        <label>{"Brightness"}</label>
+                        <input type="range" min="-1" max="1" step="0.01" value="0"
+                            onchange={ctx.link().callback(|event: Event| Msg::BrightnessChanged(event))} />
+                    </div>
+                    <div>
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ToGrayscaleAvg)} value="To grayscale (avg)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::ToGrayscaleAvgWeighted)} value="To grayscale (avg weighted)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterSmooth)} value="Filter (smooth)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterMedian)} value="Filter (median)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterEdgeDetection)} value="Filter (edge detection)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterSharpen)} value="Filter (sharpen)" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::FilterGaussianBlur)} value="Filter (gaussian blur)" />
+                        <label>{"Premultiplied alpha"}</label>
+                        <input type="checkbox" checked={self.premultiplied_filtering}
+                            onchange={ctx.link().callback(|event: Event| Msg::PremultipliedFilteringChanged(event))} />
+                        <label>{"Filter alpha"}</label>
+                        <input type="checkbox" checked={self.filter_alpha} disabled={!self.premultiplied_filtering}
+                            onchange={ctx.link().callback(|event: Event| Msg::FilterAlphaChanged(event))} />
+                    </div>
+                    <div>
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Crop))} value="Crop" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Perspective))} value="Perspective correction" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Affine))} value="Affine transform" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Select))} value="Select" />
+                        <input type="button" onclick={ctx.link().callback(|_| Msg::SelectTool(Tool::Draw))} value="Draw" />
+                    </div>
+                    { self.view_lut_controls(ctx) }
+                    { self.view_channel_controls(ctx) }
+                    { self.view_unsharp_controls(ctx) }
+                    { self.view_noise_controls(ctx) }
+                    { self.view_denoise_controls(ctx) }
+                    { self.view_frequency_controls(ctx) }
+                    { self.view_dither_controls(ctx) }
+                    { self.view_quantize_controls(ctx) }
+                    { self.view_bit_plane_controls(ctx) }
+                    { self.view_stego_controls(ctx) }
+                    { self.view_watermark_controls(ctx) }
+                    { self.view_chroma_key_controls(ctx) }
+                    { self.view_zoom_controls(ctx) }
+                    { self.view_layers(ctx) }
+                    { self.view_image_operation_controls(ctx) }
+                    { self.view_compare_controls(ctx) }
+                    { self.view_metrics(ctx) }
+                    if self.tool == Tool::Crop {
+                        { self.view_crop_controls(ctx) }
+                    }
+                    if self.tool == Tool::Perspective {
+                        { self.view_perspective_controls(ctx) }
+                    }
+                    if self.tool == Tool::Affine {
+                        { self.view_affine_controls(ctx) }
+                    }
+                    if self.tool == Tool::Select {
+                        { self.view_selection_controls(ctx) }
+                    }
+                    if self.tool == Tool::Eyedropper {
+                        { self.view_eyedropper_controls(ctx) }
+                    }
+                    if self.tool == Tool::Draw {
+                        { self.view_draw_controls(ctx) }
+                    }
+                </div>
+                <div>
+                    <canvas
+                        ref={self.canvas_ref.clone()}
+                        width={self.viewport.width.to_string()}
+                        height={self.viewport.height.to_string()}
+                        style="touch-action: none; max-width: 100%; background: #404040"
+                        onmousedown={ctx.link().callback(|event: MouseEvent| Msg::CanvasPointerDown(event.client_x(), event.client_y()))}
+                        onmousemove={ctx.link().callback(|event: MouseEvent| Msg::CanvasPointerMove(event.client_x(), event.client_y()))}
+                        onmouseup={ctx.link().callback(|_| Msg::CanvasPointerUp)}
+                        onmouseleave={ctx.link().batch_callback(|_| vec![Msg::CanvasPointerUp, Msg::CanvasPointerLeave])}
+                        ontouchstart={ctx.link().batch_callback(|event: TouchEvent| {
+                            event.prevent_default();
+                            let touches = event.touches();
+                            match (touches.get(0), touches.get(1)) {
+                                (Some(first), Some(second)) => Some(Msg::CanvasPinchStart(pinch(&first, &second).0)),
+                                (Some(touch), None) => Some(Msg::CanvasPointerDown(touch.client_x(), touch.client_y())),
+                                _ => None,
+                            }
+                        })}
+                        ontouchmove={ctx.link().batch_callback(|event: TouchEvent| {
+                            event.prevent_default();
+                            let touches = event.touches();
+                            match (touches.get(0), touches.get(1)) {
+                                (Some(first), Some(second)) => {
+                                    let (distance, x, y) = pinch(&first, &second);
+                                    Some(Msg::CanvasPinch(distance, x, y))
+                                }
+                                (Some(touch), None) => Some(Msg::CanvasPointerMove(touch.client_x(), touch.client_y())),
+                                _ => None,
+                            }
+                        })}
+                        ontouchend={ctx.link().callback(|_| Msg::CanvasPointerUp)}
+                    />
+                </div>
+                { self.view_status_bar(ctx) }
+            </>
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::ApplyOperation => {
+                let color_select = self.color_select_ref.cast::<HtmlSelectElement>().unwrap();
+                let color = match color_select.value().as_str() {
+                    "red" => ColorComponent::Red,
+                    "green" => ColorComponent::Green,
+                    "blue" => ColorComponent::Blue,
+                    "alpha" => ColorComponent::Alpha,
+                    _ => panic!("Invalid color selection"),
+                };
+
+                let op_select = self
+                    .operation_select_ref
+                    .cast::<HtmlSelectElement>()
+                    .unwrap();
+                let op: &dyn Fn(f32, f32) -> f32 = match op_select.value().as_str() {
+                    "add" => &f32::add,
+                    "subtract" => &f32::sub,
+                    "multiply" => &f32::mul,
+                    "divide" => &f32::div,
+                    _ => panic!("Invalid operation selection"),
+                };
+
+                let value = self.input_value;
+                self.apply_masked(|image| image.apply_point_fn(color, value, op));
+
+                true
+            }
+            Msg::ValueChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.input_value = input.value_as_number() as f32;
+
+                true
+            }
+            Msg::BrightnessChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.brigthness_scale = input.value_as_number() as f32;
+                let brightness = self.brigthness_scale;
+                self.apply_masked(|image| image.change_brightness(brightness));
+
+                true
+            }
+            Msg::ToGrayscaleAvg => {
+                self.apply_masked(Self::to_grayscale_avg);
+
+                true
+            },
+            Msg::ToGrayscaleAvgWeighted => {
+                self.apply_masked(Self::to_grayscale_avg_weighted);
+
+                true
+            },
+            Msg::FilterSmooth => {
+                self.apply_masked(|image| image.filter_premultiplied(Self::filter_smooth));
+
+                true
+            },
+            Msg::FilterMedian => {
+                self.apply_masked(Self::filter_median);
+
+                true
+            },
+            Msg::FilterEdgeDetection => {
+                self.apply_masked(Self::filter_sobel);
+
+                true
+            },
+            Msg::FilterSharpen => {
+                self.apply_masked(Self::filter_highpass_sharpen);
+
+                true
+            },
+            Msg::FilterGaussianBlur => {
+                self.apply_masked(|image| image.filter_premultiplied(Self::filter_gaussian_blur));
+
+                true
+            },
+            Msg::PremultipliedFilteringChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.premultiplied_filtering = input.checked();
+
+                true
+            }
+            Msg::FilterAlphaChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.filter_alpha = input.checked();
+
+                true
+            }
+            Msg::UnsharpParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return false;
+                }
+
+                match param {
+                    UnsharpParam::Amount => self.unsharp_mask.amount = value.max(0.0) as f32,
+                    UnsharpParam::Radius => self.unsharp_mask.radius = value.max(0.1) as f32,
+                    UnsharpParam::Threshold => {
+                        self.unsharp_mask.threshold = value.clamp(0.0, 255.0) as f32
+                    }
+                }
+
+                true
+            }
+            Msg::ApplyUnsharpMask => {
+                let params = self.unsharp_mask;
+                self.apply_masked(|image| image.unsharp_mask(&params));
+
+                true
+            }
+            Msg::SelectTool(tool) => {
+                self.tool = tool;
+                self.crop_tool = CropTool::new(self.width, self.height);
+                self.perspective_tool = PerspectiveTool::default();
+                self.selection_tool.points.clear();
+                self.finish_stroke();
+                self.reset_affine();
+                if tool == Tool::SpectralMask && self.spectrum_view == SpectrumView::Off {
+                    self.spectrum_view = SpectrumView::Magnitude;
+                }
+
+                true
+            }
+            Msg::CanvasPointerDown(client_x, client_y) => {
+                let (x, y) = self.client_to_image(client_x, client_y);
+
+                match self.tool {
+                    Tool::Crop => {
+                        let tolerance = 8.0 * self.client_pixel_size();
+                        self.crop_tool.pointer_down(x, y, tolerance);
+
+                        true
+                    }
+                    Tool::Perspective => {
+                        let tolerance = 8.0 * self.client_pixel_size();
+                        self.perspective_tool.pointer_down(x, y, tolerance);
+
+                        true
+                    }
+                    Tool::Select => {
+                        let tolerance = 8.0 * self.client_pixel_size();
+                        if let Some(points) = self.selection_tool.pointer_down(x, y, tolerance) {
+                            self.finish_selection(points);
+                        }
+
+                        true
+                    }
+                    Tool::Eyedropper => {
+                        self.update_hovered(x, y);
+                        if let Some((x, y)) = self.hovered {
+                            self.active_color = color::average_area(
+                                &self.bitmap_data,
+                                self.width,
+                                self.height,
+                                x,
+                                y,
+                                self.eyedropper_size,
+                            );
+                        }
+
+                        true
+                    }
+                    Tool::SpectralMask if self.spectrum.is_some() => {
+                        self.painting_mask = true;
+                        self.paint_spectral_mask(x, y);
+
+                        true
+                    }
+                    Tool::SpectralMask => false,
+                    Tool::Draw => {
+                        self.stroke = Some(Stroke::new(
+                            &self.pixels.data,
+                            self.width,
+                            self.height,
+                            self.draw_settings,
+                            self.active_color,
+                            x,
+                            y,
+                        ));
+                        self.composite_stroke();
+
+                        true
+                    }
+                    Tool::None
+                        if self.compare_mode == CompareMode::Split
+                            && (x - self.split_position * self.width as f64).abs()
+                                <= 8.0 * self.client_pixel_size() =>
+                    {
+                        self.split_dragging = true;
+
+                        true
+                    }
+                    Tool::Affine | Tool::None => {
+                        self.pan_origin = Some((client_x, client_y));
+
+                        false
+                    }
+                }
+            }
+            Msg::CanvasPointerMove(client_x, client_y) => {
+                if let Some((origin_x, origin_y)) = self.pan_origin {
+                    let (from_x, from_y) = self.client_to_canvas(origin_x, origin_y);
+                    let (to_x, to_y) = self.client_to_canvas(client_x, client_y);
+                    self.viewport.pan_by(to_x - from_x, to_y - from_y);
+                    self.pan_origin = Some((client_x, client_y));
+
+                    return true;
+                }
+
+                let (x, y) = self.client_to_image(client_x, client_y);
+                let hover_changed = self.update_hovered(x, y);
+
+                let tool_changed = match self.tool {
+                    Tool::Crop if self.crop_tool.is_dragging() => {
+                        self.crop_tool.pointer_move(x, y);
+
+                        true
+                    }
+                    Tool::Perspective if self.perspective_tool.is_dragging() => {
+                        self.perspective_tool.pointer_move(x, y);
+
+                        true
+                    }
+                    Tool::Select if self.selection_tool.is_drawing() => {
+                        self.selection_tool.pointer_move(x, y);
+
+                        true
+                    }
+                    Tool::SpectralMask if self.painting_mask => {
+                        self.paint_spectral_mask(x, y);
+
+                        true
+                    }
+                    Tool::Draw if self.stroke.is_some() => {
+                        if let Some(stroke) = &mut self.stroke {
+                            stroke.pointer_move(x, y);
+                        }
+                        self.composite_stroke();
+
+                        true
+                    }
+                    Tool::None if self.split_dragging => {
+                        self.split_position = (x / self.width as f64).clamp(0.0, 1.0);
+
+                        true
+                    }
+                    _ => false,
+                };
+
+                tool_changed || hover_changed
+            }
+            Msg::CanvasPointerUp => {
+                self.pan_origin = None;
+                self.pinch_distance = None;
+                self.painting_mask = false;
+                self.finish_stroke();
+
+                match self.tool {
+                    Tool::Crop if self.crop_tool.is_dragging() => {
+                        self.crop_tool.pointer_up();
+
+                        true
+                    }
+                    Tool::Perspective if self.perspective_tool.is_dragging() => {
+                        self.perspective_tool.pointer_up();
+
+                        true
+                    }
+                    Tool::Select if self.selection_tool.is_drawing() => {
+                        if let Some(points) = self.selection_tool.pointer_up() {
+                            self.finish_selection(points);
+                        }
+
+                        true
+                    }
+                    Tool::None if self.split_dragging => {
+                        self.split_dragging = false;
+
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Msg::CanvasPointerLeave => {
+                let changed = self.hovered.is_some();
+                self.hovered = None;
+
+                changed
+            }
+            Msg::CanvasWheel(delta, client_x, client_y) => {
+                let factor = (-delta.clamp(-100.0, 100.0) * WHEEL_ZOOM_SPEED).exp();
+                self.zoom_at_client(self.viewport.zoom * factor, client_x, client_y);
+
+                true
+            }
+            Msg::CanvasPinchStart(distance) => {
+                self.pan_origin = None;
+                self.pinch_distance = Some(distance);
+
+                false
+            }
+            Msg::CanvasPinch(distance, client_x, client_y) => {
+                if let Some(previous) = self.pinch_distance.filter(|previous| *previous > 0.0) {
+                    let zoom = self.viewport.zoom * distance / previous;
+                    self.zoom_at_client(zoom, client_x, client_y);
+                }
+                self.pinch_distance = Some(distance);
+
+                true
+            }
+            Msg::ZoomFit => {
+                let (width, height) = self.display_size();
+                self.viewport.fit(width, height);
+
+                true
+            }
+            Msg::ZoomActualSize => {
+                let (width, height) = self.display_size();
+                self.viewport.zoom = 1.0;
+                self.viewport.center(width, height);
+
+                true
+            }
+            Msg::ZoomIn => {
+                self.viewport.zoom_at_center(self.viewport.zoom * 2.0);
+
+                true
+            }
+            Msg::ZoomOut => {
+                self.viewport.zoom_at_center(self.viewport.zoom / 2.0);
+
+                true
+            }
+            Msg::EyedropperSizeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.eyedropper_size = select
+                    .value()
+                    .parse()
+                    .expect("Invalid sample size selection");
+
+                false
+            }
+            Msg::ActiveColorChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                match color::from_hex(&input.value()) {
+                    Some([r, g, b]) => self.active_color = [r, g, b, 255],
+                    None => log::warn!("Invalid colour {}", input.value()),
+                }
+
+                true
+            }
+            Msg::ExportImage => {
+                file::download("image.png", "image/png", &self.encode_png());
+
+                false
+            }
+            Msg::CropAspectChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                let aspect = match select.value().as_str() {
+                    "free" => AspectRatio::Free,
+                    "1:1" => AspectRatio::Square,
+                    "4:3" => AspectRatio::FourThree,
+                    "16:9" => AspectRatio::SixteenNine,
+                    "custom" => AspectRatio::Custom(1.0, 1.0),
+                    _ => panic!("Invalid aspect ratio selection"),
+                };
+                self.crop_tool.set_aspect(aspect);
+
+                true
+            }
+            Msg::CropFieldChanged(field, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return true;
+                }
+
+                let rect = self.crop_tool.rect.unwrap_or(CropRect {
+                    x: 0.0,
+                    y: 0.0,
+                    width: self.width as f64,
+                    height: self.height as f64,
+                });
+                let (ratio_width, ratio_height) = match self.crop_tool.aspect {
+                    AspectRatio::Custom(w, h) => (w, h),
+                    _ => (1.0, 1.0),
+                };
+
+                match field {
+                    CropField::X => self
+                        .crop_tool
+                        .set_rect(value, rect.y, rect.width, rect.height),
+                    CropField::Y => self
+                        .crop_tool
+                        .set_rect(rect.x, value, rect.width, rect.height),
+                    CropField::Width => self.crop_tool.set_rect(rect.x, rect.y, value, rect.height),
+                    CropField::Height => {
+                        // With a locked ratio the width is derived from the typed height.
+                        let width = self
+                            .crop_tool
+                            .aspect
+                            .ratio()
+                            .map_or(rect.width, |r| value * r);
+                        self.crop_tool.set_rect(rect.x, rect.y, width, value)
+                    }
+                    CropField::RatioWidth => self
+                        .crop_tool
+                        .set_aspect(AspectRatio::Custom(value, ratio_height)),
+                    CropField::RatioHeight => self
+                        .crop_tool
+                        .set_aspect(AspectRatio::Custom(ratio_width, value)),
+                }
+
+                true
+            }
+            Msg::ApplyCrop => {
+                if let Some(rect) = self.crop_tool.rect {
+                    let (x, y, width, height) = rect.to_pixels(self.width, self.height);
+                    self.crop(x, y, width, height);
+                }
+                self.tool = Tool::None;
+
+                true
+            }
+            Msg::PerspectiveAutoSizeChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.perspective_tool.output_size = if input.checked() {
+                    None
+                } else {
+                    Some(
+                        self.perspective_tool
+                            .target_size()
+                            .unwrap_or((self.width, self.height)),
+                    )
+                };
+
+                true
+            }
+            Msg::PerspectiveWidthChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if let Some((_, height)) = self.perspective_tool.output_size {
+                    if value >= 1.0 {
+                        self.perspective_tool.output_size = Some((value as u32, height));
+                    }
+                }
+
+                true
+            }
+            Msg::PerspectiveHeightChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if let Some((width, _)) = self.perspective_tool.output_size {
+                    if value >= 1.0 {
+                        self.perspective_tool.output_size = Some((width, value as u32));
+                    }
+                }
+
+                true
+            }
+            Msg::ResetPerspective => {
+                self.perspective_tool = PerspectiveTool::default();
+
+                true
+            }
+            Msg::ApplyPerspective => {
+                let corners = self.perspective_tool.corners();
+                let size = self.perspective_tool.target_size();
+
+                if let (Some(corners), Some((width, height))) = (corners, size) {
+                    self.correct_perspective(corners, width, height);
+                    self.tool = Tool::None;
+                }
+
+                true
+            }
+            Msg::AffineParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return true;
+                }
+
+                let params = &mut self.affine_params;
+                match param {
+                    AffineParam::ScaleX => params.scale_x = value,
+                    AffineParam::ScaleY => params.scale_y = value,
+                    AffineParam::Rotation => params.rotation = value,
+                    AffineParam::ShearX => params.shear_x = value,
+                    AffineParam::ShearY => params.shear_y = value,
+                    AffineParam::TranslateX => params.translate_x = value,
+                    AffineParam::TranslateY => params.translate_y = value,
+                }
+
+                self.affine_matrix = self
+                    .affine_params
+                    .to_matrix(self.width as f64 / 2.0, self.height as f64 / 2.0);
+                self.update_affine_preview();
+
+                true
+            }
+            Msg::AffineMatrixChanged(row, col, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return true;
+                }
+
+                self.affine_matrix[row][col] = value;
+                self.update_affine_preview();
+
+                true
+            }
+            Msg::AffineModeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.affine_mode = match select.value().as_str() {
+                    "parameters" => AffineMode::Parameters,
+                    "matrix" => AffineMode::Matrix,
+                    _ => panic!("Invalid affine mode selection"),
+                };
+                if self.affine_mode == AffineMode::Parameters {
+                    self.affine_matrix = self
+                        .affine_params
+                        .to_matrix(self.width as f64 / 2.0, self.height as f64 / 2.0);
+                    self.update_affine_preview();
+                }
+
+                true
+            }
+            Msg::AffineInterpolationChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.affine_interpolation = match select.value().as_str() {
+                    "bilinear" => Interpolation::Bilinear,
+                    "nearest" => Interpolation::Nearest,
+                    _ => panic!("Invalid interpolation selection"),
+                };
+                self.update_affine_preview();
+
+                true
+            }
+            Msg::ResetAffine => {
+                self.reset_affine();
+
+                true
+            }
+            Msg::ApplyAffine => {
+                let matrix = self.affine_matrix;
+                let interpolation = self.affine_interpolation;
+                self.apply_masked(|image| image.transform_affine(&matrix, interpolation));
+                self.reset_affine();
+                self.tool = Tool::None;
+
+                true
+            }
+            Msg::SelectionShapeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.selection_tool.shape = match select.value().as_str() {
+                    "rectangle" => SelectionShape::Rectangle,
+                    "ellipse" => SelectionShape::Ellipse,
+                    "lasso" => SelectionShape::Lasso,
+                    "polygon" => SelectionShape::Polygon,
+                    "magic-wand" => SelectionShape::MagicWand,
+                    _ => panic!("Invalid selection shape"),
+                };
+                self.selection_tool.points.clear();
+
+                true
+            }
+            Msg::SelectionModeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.selection_tool.mode = match select.value().as_str() {
+                    "replace" => SelectionMode::Replace,
+                    "add" => SelectionMode::Add,
+                    "subtract" => SelectionMode::Subtract,
+                    "intersect" => SelectionMode::Intersect,
+                    _ => panic!("Invalid selection mode"),
+                };
+
+                true
+            }
+            Msg::SelectionToleranceChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.selection_tool.tolerance = input.value_as_number().clamp(0.0, 255.0) as u8;
+
+                true
+            }
+            Msg::SelectionRadiusChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.selection_tool.radius = input.value_as_number().max(1.0) as u32;
+
+                true
+            }
+            Msg::ClosePolygonSelection => {
+                let points = std::mem::take(&mut self.selection_tool.points);
+                self.finish_selection(points);
+
+                true
+            }
+            Msg::SelectAll => {
+                self.selection = Some(Mask::new(self.width, self.height, 1.0));
+
+                true
+            }
+            Msg::Deselect => {
+                self.selection = None;
+
+                true
+            }
+            Msg::InvertSelection => {
+                match &mut self.selection {
+                    Some(mask) => mask.invert(),
+                    // Inverting "everything" leaves nothing selected.
+                    None => self.selection = Some(Mask::new(self.width, self.height, 0.0)),
+                }
+
+                true
+            }
+            Msg::GrowSelection => {
+                if let Some(mask) = &mut self.selection {
+                    mask.grow(self.selection_tool.radius);
+                }
+
+                true
+            }
+            Msg::ShrinkSelection => {
+                if let Some(mask) = &mut self.selection {
+                    mask.shrink(self.selection_tool.radius);
+                }
+
+                true
+            }
+            Msg::FeatherSelection => {
+                if let Some(mask) = &mut self.selection {
+                    mask.feather(self.selection_tool.radius as f32);
+                }
+
+                true
+            }
+            Msg::AddLayer(event) => {
+                if let Some(file) = file::selected_file(&event) {
+                    let name = file.name();
+                    let layer_cb = ctx
+                        .link()
+                        .callback(move |data: Vec<u8>| Msg::LayerLoaded(name.clone(), data));
+                    file::read_file(&file, layer_cb);
+                }
+
+                false
+            }
+            Msg::LayerLoaded(name, data) => {
+                self.add_layer(name, data);
+
+                true
+            }
+            Msg::LayerOpacityChanged(index, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.layers[index].opacity = input.value_as_number().clamp(0.0, 1.0) as f32;
+
+                true
+            }
+            Msg::LayerBlendModeChanged(index, event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                let mode = select.value().parse::<usize>().unwrap();
+                self.layers[index].blend_mode = BlendMode::ALL[mode];
+
+                true
+            }
+            Msg::LayerVisibilityChanged(index, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.layers[index].visible = input.checked();
+
+                true
+            }
+            Msg::MoveLayerUp(index) => {
+                if index + 1 < self.layers.len() {
+                    self.swap_layers(index, index + 1);
+                }
+
+                true
+            }
+            Msg::MoveLayerDown(index) => {
+                if index > 0 {
+                    self.swap_layers(index, index - 1);
+                }
+
+                true
+            }
+            Msg::RemoveLayer(index) => {
+                self.remove_layer(index);
+
+                true
+            }
+            Msg::FlattenLayers => {
+                self.flatten_layers();
+
+                true
+            }
+            Msg::OperandUpload(event) => {
+                if let Some(file) = file::selected_file(&event) {
+                    let name = file.name();
+                    let operand_cb = ctx
+                        .link()
+                        .callback(move |data: Vec<u8>| Msg::OperandLoaded(name.clone(), data));
+                    file::read_file(&file, operand_cb);
+                }
+
+                false
+            }
+            Msg::OperandLoaded(name, data) => {
+                let operand = Self::decode_data(data).to_rgba8();
+                self.operand = Some((name, operand));
+
+                true
+            }
+            Msg::ImageOperationChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.image_operation = match select.value().as_str() {
+                    "add" => ImageOperation::Add,
+                    "subtract" => ImageOperation::Subtract,
+                    "multiply" => ImageOperation::Multiply,
+                    "divide" => ImageOperation::Divide,
+                    "difference" => ImageOperation::AbsDifference,
+                    "min" => ImageOperation::Min,
+                    "max" => ImageOperation::Max,
+                    "average" => ImageOperation::Average,
+                    "blend" => ImageOperation::Blend(0.5),
+                    "and" => ImageOperation::And,
+                    "or" => ImageOperation::Or,
+                    "xor" => ImageOperation::Xor,
+                    "not" => ImageOperation::Not,
+                    _ => panic!("Invalid image operation selection"),
+                };
+
+                true
+            }
+            Msg::BlendWeightChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.image_operation =
+                    ImageOperation::Blend(input.value_as_number().clamp(0.0, 1.0) as f32);
+
+                true
+            }
+            Msg::OperandFitChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.operand_fit = match select.value().as_str() {
+                    "resize" => OperandFit::Resize,
+                    "top-left" => OperandFit::AlignTopLeft,
+                    "center" => OperandFit::AlignCenter,
+                    _ => panic!("Invalid fit selection"),
+                };
+
+                true
+            }
+            Msg::ApplyImageOperation => {
+                let operation = self.image_operation;
+                let operand = match &self.operand {
+                    Some((_, operand)) => {
+                        arithmetic::fit_operand(operand, self.width, self.height, self.operand_fit)
+                    }
+                    None if operation == ImageOperation::Not => self.bitmap_data.clone(),
+                    None => return false,
+                };
+
+                self.apply_masked(|image| image.apply_image_fn(&operand, operation));
+
+                true
+            }
+            Msg::CompareModeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.compare_mode = match select.value().as_str() {
+                    "off" => CompareMode::Off,
+                    "side-by-side" => CompareMode::SideBySide,
+                    "split" => CompareMode::Split,
+                    "difference" => CompareMode::Difference,
+                    _ => panic!("Invalid comparison mode"),
+                };
+
+                true
+            }
+            Msg::ShowOriginal(show) => {
+                self.show_original = show;
+
+                true
+            }
+            Msg::MetricsSourceChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.metrics_source = match select.value().as_str() {
+                    "original" => MetricsSource::OriginalVsCurrent,
+                    "operand" => MetricsSource::CurrentVsOperand,
+                    _ => panic!("Invalid metrics source"),
+                };
+                self.metrics = None;
+
+                true
+            }
+            Msg::ComputeMetrics => {
+                let reference = match self.metrics_source {
+                    MetricsSource::OriginalVsCurrent => self.original_data().into_owned(),
+                    MetricsSource::CurrentVsOperand => match &self.operand {
+                        Some((_, operand)) => arithmetic::fit_operand(
+                            operand,
+                            self.width,
+                            self.height,
+                            self.operand_fit,
+                        ),
+                        None => {
+                            log::warn!("No second image loaded");
+                            return false;
+                        }
+                    },
+                };
+                self.metrics = Some(self.quality_report(&reference));
+
+                true
+            }
+            Msg::NoiseKindChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                let kind = match select.value().as_str() {
+                    "gaussian" => NoiseKind::Gaussian,
+                    "salt-and-pepper" => NoiseKind::SaltAndPepper,
+                    "speckle" => NoiseKind::Speckle,
+                    "poisson" => NoiseKind::Poisson,
+                    "uniform" => NoiseKind::Uniform,
+                    _ => panic!("Invalid noise selection"),
+                };
+                self.noise_params.kind = kind;
+                self.noise_params.amount = kind.default_amount();
+
+                true
+            }
+            Msg::NoiseAmountChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.noise_params.amount = input.value_as_number().max(0.0) as f32;
+
+                true
+            }
+            Msg::NoiseMonochromeChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.noise_params.monochrome = input.checked();
+
+                true
+            }
+            Msg::NoiseSeedChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.noise_params.seed = input.value_as_number().max(0.0) as u64;
+
+                true
+            }
+            Msg::AddNoise => {
+                let params = self.noise_params;
+                self.apply_masked(|image| image.add_noise(&params));
+
+                true
+            }
+            Msg::DenoiserChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.denoiser = match select.value().as_str() {
+                    "bilateral" => Denoiser::Bilateral {
+                        spatial_sigma: 3.0,
+                        range_sigma: 30.0,
+                    },
+                    "kuwahara" => Denoiser::Kuwahara { radius: 4 },
+                    "anisotropic-kuwahara" => Denoiser::AnisotropicKuwahara { radius: 4 },
+                    "non-local-means" => Denoiser::NonLocalMeans {
+                        search_radius: 5,
+                        patch_radius: 1,
+                        strength: 10.0,
+                    },
+                    _ => panic!("Invalid denoiser selection"),
+                };
+
+                true
+            }
+            Msg::DenoiserParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return false;
+                }
+
+                match (&mut self.denoiser, param) {
+                    (Denoiser::Bilateral { spatial_sigma, .. }, DenoiserParam::SpatialSigma) => {
+                        *spatial_sigma = value.max(0.1) as f32
+                    }
+                    (Denoiser::Bilateral { range_sigma, .. }, DenoiserParam::RangeSigma) => {
+                        *range_sigma = value.max(1.0) as f32
+                    }
+                    (
+                        Denoiser::Kuwahara { radius } | Denoiser::AnisotropicKuwahara { radius },
+                        DenoiserParam::Radius,
+                    ) => *radius = value.max(1.0) as u32,
+                    (
+                        Denoiser::NonLocalMeans { search_radius, .. },
+                        DenoiserParam::SearchRadius,
+                    ) => *search_radius = value.max(1.0) as u32,
+                    (Denoiser::NonLocalMeans { patch_radius, .. }, DenoiserParam::PatchRadius) => {
+                        *patch_radius = value.max(0.0) as u32
+                    }
+                    (Denoiser::NonLocalMeans { strength, .. }, DenoiserParam::Strength) => {
+                        *strength = value.max(1.0) as f32
+                    }
+                    _ => {}
+                }
+
+                true
+            }
+            Msg::StartDenoise => {
+                self.denoise_job = Some(DenoiseJob::new(
+                    &self.pixels.data,
+                    self.width,
+                    self.height,
+                    self.denoiser,
+                ));
+                schedule(ctx, Msg::DenoiseStep);
+
+                true
+            }
+            Msg::DenoiseStep => {
+                let job = match &mut self.denoise_job {
+                    Some(job) => job,
+                    None => return false,
+                };
+
+                let start = js_sys::Date::now();
+                while !job.is_done() && js_sys::Date::now() - start < STEP_BUDGET {
+                    job.step();
+                }
+
+                if !job.is_done() {
+                    schedule(ctx, Msg::DenoiseStep);
+                    return true;
+                }
+
+                // The image may have been edited while the job ran.
+                let job = self.denoise_job.take().unwrap();
+                if job.matches(&self.pixels.data) {
+                    let output = job.finish();
+                    self.apply_masked(|image| image.pixels.data = output);
+                } else {
+                    log::warn!("Image changed while denoising, discarding the result");
+                }
+
+                true
+            }
+            Msg::CancelDenoise => {
+                self.denoise_job = None;
+
+                true
+            }
+            Msg::SpectrumViewChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.spectrum_view = match select.value().as_str() {
+                    "off" => SpectrumView::Off,
+                    "magnitude" => SpectrumView::Magnitude,
+                    "phase" => SpectrumView::Phase,
+                    _ => panic!("Invalid spectrum view selection"),
+                };
+                if self.spectrum_view == SpectrumView::Off {
+                    self.spectrum = None;
+                    self.spectrum_source.clear();
+                }
+
+                true
+            }
+            Msg::FilterKindChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.frequency_filter.kind = match select.value().as_str() {
+                    "none" => FilterKind::None,
+                    "low-pass" => FilterKind::LowPass,
+                    "high-pass" => FilterKind::HighPass,
+                    "band-pass" => FilterKind::BandPass,
+                    "band-stop" => FilterKind::BandStop,
+                    _ => panic!("Invalid filter selection"),
+                };
+
+                true
+            }
+            Msg::FilterShapeChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.frequency_filter.shape = match select.value().as_str() {
+                    "ideal" => FilterShape::Ideal,
+                    "butterworth" => FilterShape::Butterworth,
+                    "gaussian" => FilterShape::Gaussian,
+                    _ => panic!("Invalid filter shape selection"),
+                };
+
+                true
+            }
+            Msg::FrequencyParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return false;
+                }
+
+                match param {
+                    FrequencyParam::Cutoff => self.frequency_filter.cutoff = value.max(0.0),
+                    FrequencyParam::BandWidth => self.frequency_filter.band_width = value.max(0.0),
+                    FrequencyParam::Order => self.frequency_filter.order = value.max(1.0) as u32,
+                }
+
+                true
+            }
+            Msg::MaskBrushChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.mask_brush = input.value_as_number().max(1.0);
+
+                true
+            }
+            Msg::ClearSpectralMask => {
+                self.spectral_mask.clear();
+                self.ensure_spectral_mask();
+
+                true
+            }
+            Msg::DitherTargetChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.dither_target = match select.value().as_str() {
+                    "black-white" => DitherTarget::BlackWhite,
+                    "levels" => DitherTarget::Levels(4),
+                    "game-boy" => DitherTarget::Palette(dither::GAME_BOY_PALETTE.to_vec()),
+                    "cga" => DitherTarget::Palette(dither::CGA_PALETTE.to_vec()),
+                    _ => panic!("Invalid dither target selection"),
+                };
+
+                true
+            }
+            Msg::DitherLevelsChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let levels = input.value_as_number();
+                if levels.is_nan() {
+                    return false;
+                }
+                self.dither_target = DitherTarget::Levels(levels.clamp(2.0, 256.0) as u32);
+
+                true
+            }
+            Msg::DitherMethodChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.dither_method = match select.value().as_str() {
+                    "none" => DitherMethod::None,
+                    "floyd-steinberg" => DitherMethod::FloydSteinberg,
+                    "jarvis" => DitherMethod::JarvisJudiceNinke,
+                    "stucki" => DitherMethod::Stucki,
+                    "atkinson" => DitherMethod::Atkinson,
+                    "sierra" => DitherMethod::Sierra,
+                    "bayer-2" => DitherMethod::Bayer(2),
+                    "bayer-4" => DitherMethod::Bayer(4),
+                    "bayer-8" => DitherMethod::Bayer(8),
+                    "blue-noise" => DitherMethod::BlueNoise,
+                    _ => panic!("Invalid dither method selection"),
+                };
+
+                true
+            }
+            Msg::DitherSerpentineChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.dither_serpentine = input.checked();
+
+                true
+            }
+            Msg::ApplyDither => {
+                let target = self.dither_target.clone();
+                let (method, serpentine) = (self.dither_method, self.dither_serpentine);
+                self.apply_masked(|image| image.dither(&target, method, serpentine));
+
+                true
+            }
+            Msg::LutEffectChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.lut_params.effect = match select.value().as_str() {
+                    "invert" => LutEffect::Invert,
+                    "posterize" => LutEffect::Posterize,
+                    "solarize" => LutEffect::Solarize,
+                    "threshold" => LutEffect::Threshold,
+                    "duotone" => LutEffect::Duotone,
+                    "gradient-map" => LutEffect::GradientMap,
+                    _ => panic!("Invalid effect selection"),
+                };
+
+                true
+            }
+            Msg::InvertChannelChanged(channel, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.lut_params.invert[channel] = input.checked();
+
+                true
+            }
+            Msg::LutParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number();
+                if value.is_nan() {
+                    return false;
+                }
+
+                match param {
+                    LutParam::Levels => self.lut_params.levels = value.clamp(2.0, 256.0) as u32,
+                    LutParam::Threshold => {
+                        self.lut_params.threshold = value.clamp(0.0, 255.0) as u8
+                    }
+                }
 
                 true
             }
-            Msg::ValueChanged(event) => {
+            Msg::LutColorChanged(target, event) => {
                 let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
-                self.input_value = input.value_as_number() as f32;
+                let color = match color::from_hex(&input.value()) {
+                    Some(color) => color,
+                    None => return false,
+                };
+
+                match target {
+                    LutColor::Below => self.lut_params.below = color,
+                    LutColor::Above => self.lut_params.above = color,
+                    LutColor::Shadow => self.lut_params.shadow = color,
+                    LutColor::Highlight => self.lut_params.highlight = color,
+                }
 
                 true
             }
-            Msg::BrightnessChanged(event) => {
+            Msg::SepiaPreset => {
+                self.lut_params.shadow = lut::SEPIA_SHADOW;
+                self.lut_params.highlight = lut::SEPIA_HIGHLIGHT;
+
+                true
+            }
+            Msg::GradientStopColorChanged(index, event) => {
                 let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
-                self.brigthness_scale = input.value_as_number() as f32;
-                self.change_brightness(self.brigthness_scale);
+                match color::from_hex(&input.value()) {
+                    Some(color) => self.lut_params.stops[index].color = color,
+                    None => return false,
+                }
 
                 true
             }
-            Msg::ToGrayscaleAvg => {
-                self.to_grayscale_avg();
+            Msg::GradientStopPositionChanged(index, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let position = input.value_as_number();
+                if position.is_nan() {
+                    return false;
+                }
+                self.lut_params.stops[index].position = position.clamp(0.0, 1.0) as f32;
 
                 true
-            },
-            Msg::ToGrayscaleAvgWeighted => {
-                self.to_grayscale_avg_weighted();
+            }
+            Msg::AddGradientStop => {
+                let [r, g, b, _] = self.active_color;
+                self.lut_params.stops.push(GradientStop {
+                    position: 0.5,
+                    color: [r, g, b],
+                });
 
                 true
-            },
-            Msg::FilterSmooth => {
-                self.filter_smooth();
+            }
+            Msg::RemoveGradientStop(index) => {
+                self.lut_params.stops.remove(index);
 
                 true
-            },
-            Msg::FilterMedian => {
-                self.filter_median();
+            }
+            Msg::ApplyLut => {
+                let params = self.lut_params.clone();
+                self.apply_masked(|image| image.apply_lut(&params));
 
                 true
-            },
-            Msg::FilterEdgeDetection => {
-                self.filter_sobel();
+            }
+            Msg::MixerPresetChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                let preset: usize = select.value().parse().unwrap();
+                let alpha = self.channel_mixer.alpha;
+                self.channel_mixer = ChannelMixer::preset(MixerPreset::ALL[preset]);
+                self.channel_mixer.alpha = alpha;
 
                 true
-            },
-            Msg::FilterSharpen => {
-                self.filter_highpass_sharpen();
+            }
+            Msg::MixerWeightChanged(output, input, event) => {
+                let element: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let weight = element.value_as_number();
+                if weight.is_nan() {
+                    return false;
+                }
+                self.channel_mixer.matrix[output][input] = weight as f32;
 
                 true
-            },
-            Msg::FilterGaussianBlur => {
-                self.filter_gaussian_blur();
+            }
+            Msg::MixerOffsetChanged(output, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let offset = input.value_as_number();
+                if offset.is_nan() {
+                    return false;
+                }
+                self.channel_mixer.offsets[output] = offset.clamp(-255.0, 255.0) as f32;
 
                 true
-            },
+            }
+            Msg::MixerAlphaChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.channel_mixer.alpha = input.checked();
+
+                true
+            }
+            Msg::ApplyChannelMixer => {
+                let mixer = self.channel_mixer;
+                self.apply_masked(|image| image.mix_channels(&mixer));
+
+                true
+            }
+            Msg::SplitChannels => {
+                self.split_channels();
+
+                true
+            }
+            Msg::MergeSourceChanged(channel, event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.merge_sources[channel] = select.value().parse().ok();
+
+                true
+            }
+            Msg::MergeChannels => {
+                let sources = self.merge_sources;
+                self.apply_masked(|image| image.merge_channels(sources));
+
+                true
+            }
+            Msg::BitPlaneChannelChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.bit_plane_channel = select.value().parse().unwrap();
+
+                true
+            }
+            Msg::BitPlaneChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.bit_plane = select.value().parse().unwrap();
+
+                true
+            }
+            Msg::ShowBitPlane => {
+                let (channel, bit) = (self.bit_plane_channel, self.bit_plane);
+                self.apply_masked(|image| image.show_bit_plane(channel, bit));
+
+                true
+            }
+            Msg::KeptPlaneChanged(bit, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                if input.checked() {
+                    self.kept_planes |= 1 << bit;
+                } else {
+                    self.kept_planes &= !(1 << bit);
+                }
+
+                true
+            }
+            Msg::KeepBitPlanes => {
+                let planes = self.kept_planes;
+                self.apply_masked(|image| image.keep_bit_planes(planes));
+
+                true
+            }
+            Msg::BitDepthChanged(channel, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let bits = input.value_as_number();
+                if bits.is_nan() {
+                    return false;
+                }
+                self.bit_depths[channel] = bits.clamp(1.0, 8.0) as u32;
+
+                true
+            }
+            Msg::ReduceBitDepth => {
+                let bits = self.bit_depths;
+                self.apply_masked(|image| image.reduce_bit_depth(bits));
+
+                true
+            }
+            Msg::StegoChannelChanged(channel, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.stego_params.channels[channel] = input.checked();
+
+                true
+            }
+            Msg::StegoBitsChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let bits = input.value_as_number();
+                if bits.is_nan() {
+                    return false;
+                }
+                self.stego_params.bits = bits.clamp(1.0, 8.0) as u32;
+
+                true
+            }
+            Msg::StegoPasswordChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.stego_params.password = input.value();
+
+                true
+            }
+            Msg::StegoMessageChanged(event) => {
+                let input: HtmlTextAreaElement = event.target().unwrap().dyn_into().unwrap();
+                self.stego_message = input.value();
+
+                true
+            }
+            Msg::StegoFileChosen(event) => {
+                if let Some(file) = file::selected_file(&event) {
+                    let name = file.name();
+                    let file_cb = ctx
+                        .link()
+                        .callback(move |data: Vec<u8>| Msg::StegoFileLoaded(name.clone(), data));
+                    file::read_file(&file, file_cb);
+                }
+
+                false
+            }
+            Msg::StegoFileLoaded(name, data) => {
+                self.stego_file = Some((name, data));
+
+                true
+            }
+            Msg::EmbedText => {
+                self.embed_and_report(Payload::Text(self.stego_message.clone()));
+
+                true
+            }
+            Msg::EmbedFile => {
+                let (name, data) = match &self.stego_file {
+                    Some((name, data)) => (name.clone(), data.clone()),
+                    None => return false,
+                };
+                self.embed_and_report(Payload::File { name, data });
+
+                true
+            }
+            Msg::ExtractPayload => {
+                match self.extract_payload(&self.stego_params) {
+                    Ok(payload) => {
+                        self.stego_status = None;
+                        self.extracted = Some(payload);
+                    }
+                    Err(error) => {
+                        self.stego_status = Some(error.to_string());
+                        self.extracted = None;
+                    }
+                }
+
+                true
+            }
+            Msg::DownloadExtracted => {
+                if let Some(Payload::File { name, data }) = &self.extracted {
+                    file::download(name, "application/octet-stream", data);
+                }
+
+                false
+            }
+            Msg::WatermarkTextChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.watermark.text = input.value();
+
+                true
+            }
+            Msg::WatermarkParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number() as f32;
+                if value.is_nan() {
+                    return false;
+                }
+
+                let params = &mut self.watermark;
+                match param {
+                    WatermarkParam::Size => params.size = value.clamp(4.0, 512.0),
+                    WatermarkParam::Scale => params.scale = value.clamp(0.01, 10.0),
+                    WatermarkParam::Opacity => params.opacity = value.clamp(0.0, 1.0),
+                    WatermarkParam::Rotation => params.rotation = value,
+                    WatermarkParam::Margin => params.margin = value.max(0.0),
+                }
+
+                true
+            }
+            Msg::WatermarkColorChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                match color::from_hex(&input.value()) {
+                    Some(color) => self.watermark.color = color,
+                    None => return false,
+                }
+
+                true
+            }
+            Msg::WatermarkPlacementChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.watermark.placement = match select.value().as_str() {
+                    "top-left" => Placement::TopLeft,
+                    "top-right" => Placement::TopRight,
+                    "center" => Placement::Center,
+                    "bottom-left" => Placement::BottomLeft,
+                    "bottom-right" => Placement::BottomRight,
+                    "tiled" => Placement::Tiled,
+                    _ => panic!("Invalid placement selection"),
+                };
+
+                true
+            }
+            Msg::WatermarkImageUpload(event) => {
+                if let Some(file) = file::selected_file(&event) {
+                    let name = file.name();
+                    let watermark_cb = ctx.link().callback(move |data: Vec<u8>| {
+                        Msg::WatermarkImageLoaded(name.clone(), data)
+                    });
+                    file::read_file(&file, watermark_cb);
+                }
+
+                false
+            }
+            Msg::WatermarkImageLoaded(name, data) => {
+                let image = Self::decode_data(data).to_rgba8();
+                self.watermark_image = Some((name, image));
+
+                true
+            }
+            Msg::StampText => {
+                let params = self.watermark.clone();
+                self.apply_masked(|image| image.stamp_text(&params));
+
+                true
+            }
+            Msg::StampImage => {
+                let (params, stamp) = match &self.watermark_image {
+                    Some((_, stamp)) => (self.watermark.clone(), stamp.clone()),
+                    None => return false,
+                };
+                self.apply_masked(|image| image.stamp_image(&stamp, &params));
+
+                true
+            }
+            Msg::DrawToolChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.draw_settings.tool = match select.value().as_str() {
+                    "brush" => DrawTool::Brush,
+                    "eraser" => DrawTool::Eraser,
+                    "line" => DrawTool::Line,
+                    "rectangle" => DrawTool::Rectangle,
+                    "ellipse" => DrawTool::Ellipse,
+                    "fill" => DrawTool::Fill,
+                    _ => panic!("Invalid draw tool selection"),
+                };
+
+                true
+            }
+            Msg::DrawParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number() as f32;
+                if value.is_nan() {
+                    return false;
+                }
+
+                let settings = &mut self.draw_settings;
+                match param {
+                    DrawParam::Size => settings.size = value.clamp(1.0, 500.0),
+                    DrawParam::Hardness => settings.hardness = value.clamp(0.0, 1.0),
+                    DrawParam::Opacity => settings.opacity = value.clamp(0.0, 1.0),
+                    DrawParam::Tolerance => settings.tolerance = value.clamp(0.0, 255.0) as u8,
+                }
+
+                true
+            }
+            Msg::DrawFilledChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.draw_settings.filled = input.checked();
+
+                true
+            }
+            Msg::FillConnectivityChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.draw_settings.connectivity = match select.value().as_str() {
+                    "4" => Connectivity::Four,
+                    "8" => Connectivity::Eight,
+                    _ => panic!("Invalid connectivity selection"),
+                };
+
+                true
+            }
+            Msg::Undo => {
+                self.undo();
+
+                true
+            }
+            Msg::ChromaKeyColorChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                match color::from_hex(&input.value()) {
+                    Some(color) => self.chroma_key.key = color,
+                    None => return false,
+                }
+
+                true
+            }
+            Msg::ChromaKeyFromActiveColor => {
+                let [r, g, b, _] = self.active_color;
+                self.chroma_key.key = [r, g, b];
+
+                true
+            }
+            Msg::ChromaParamChanged(param, event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let value = input.value_as_number() as f32;
+                if value.is_nan() {
+                    return false;
+                }
+
+                let params = &mut self.chroma_key;
+                match param {
+                    ChromaParam::Tolerance => params.tolerance = value.max(0.0),
+                    ChromaParam::Softness => params.softness = value.max(0.0),
+                    ChromaParam::Spill => params.spill = value.clamp(0.0, 1.0),
+                }
+
+                true
+            }
+            Msg::ApplyChromaKey => {
+                let params = self.chroma_key;
+                self.apply_masked(|image| image.chroma_key(&params));
+
+                true
+            }
+            Msg::QuantizeMethodChanged(event) => {
+                let select: HtmlSelectElement = event.target().unwrap().dyn_into().unwrap();
+                self.quantize_method = match select.value().as_str() {
+                    "median-cut" => QuantizeMethod::MedianCut,
+                    "octree" => QuantizeMethod::Octree,
+                    "k-means" => QuantizeMethod::KMeans,
+                    _ => panic!("Invalid quantize method selection"),
+                };
+
+                true
+            }
+            Msg::QuantizeColorsChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                let colors = input.value_as_number();
+                if colors.is_nan() {
+                    return false;
+                }
+                self.quantize_colors = colors.clamp(1.0, 256.0) as u32;
+
+                true
+            }
+            Msg::QuantizeSeedChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.quantize_seed = input.value_as_number().max(0.0) as u64;
+
+                true
+            }
+            Msg::QuantizeDitherChanged(event) => {
+                let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+                self.quantize_dither = input.checked();
+
+                true
+            }
+            Msg::ApplyQuantize => {
+                let (colors, method, seed) = (
+                    self.quantize_colors,
+                    self.quantize_method,
+                    self.quantize_seed,
+                );
+                let dither_method = if self.quantize_dither {
+                    self.dither_method
+                } else {
+                    DitherMethod::None
+                };
+                let serpentine = self.dither_serpentine;
+                let mut palette = Vec::new();
+                self.apply_masked(|image| {
+                    palette = image.quantize(colors, method, seed, dither_method, serpentine)
+                });
+                self.palette = palette;
+
+                true
+            }
+            Msg::ExportPalette(format) => {
+                match format {
+                    PaletteFormat::Gpl => file::download(
+                        "palette.gpl",
+                        "text/plain",
+                        quantize::to_gpl(&self.palette, "imgmod").as_bytes(),
+                    ),
+                    PaletteFormat::Aco => file::download(
+                        "palette.aco",
+                        "application/octet-stream",
+                        &quantize::to_aco(&self.palette),
+                    ),
+                    PaletteFormat::Json => file::download(
+                        "palette.json",
+                        "application/json",
+                        quantize::to_json(&self.palette).as_bytes(),
+                    ),
+                }
+
+                false
+            }
+            Msg::ApplyFrequencyFilter => {
+                self.ensure_spectral_mask();
+                let filter = self.frequency_filter;
+                let mask = self.spectral_mask.clone();
+                self.apply_masked(|image| image.filter_frequency(&filter, &mask));
+
+                true
+            }
         }
     }
 
@@ -578,32 +4596,181 @@ impl Component for Image {
         true
     }
 
-    fn rendered(&mut self, _ctx: &Context<Self>, first_render: bool) {
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
         if first_render {
-            self.canvas_ctx = Some(
-                self.canvas_ref
-                    .cast::<HtmlCanvasElement>()
-                    .unwrap()
-                    .get_context("2d")
-                    .unwrap()
-                    .unwrap()
-                    .dyn_into::<CanvasRenderingContext2d>()
-                    .unwrap(),
-            );
+            let canvas = self.canvas_ref.cast::<HtmlCanvasElement>().unwrap();
+            let offscreen = web_sys::window()
+                .unwrap()
+                .document()
+                .unwrap()
+                .create_element("canvas")
+                .unwrap()
+                .dyn_into::<HtmlCanvasElement>()
+                .unwrap();
+
+            self.canvas_ctx = Some(context_2d(&canvas));
+            self.offscreen_ctx = Some(context_2d(&offscreen));
+            self.checkerboard = Some(checkerboard(self.canvas_ctx.as_ref().unwrap()));
+
+            // Yew registers wheel listeners as passive, which would keep the
+            // page scrolling while zooming.
+            let link = ctx.link().clone();
+            self.wheel_listener = Some(EventListener::new_with_options(
+                &canvas,
+                "wheel",
+                EventListenerOptions::enable_prevent_default(),
+                move |event| {
+                    let event = event.dyn_ref::<WheelEvent>().unwrap();
+                    event.prevent_default();
+                    link.send_message(Msg::CanvasWheel(
+                        event.delta_y(),
+                        event.client_x(),
+                        event.client_y(),
+                    ));
+                },
+            ));
         }
 
+        self.refresh_spectrum();
+
         let canvas_ctx = self.canvas_ctx.as_ref().unwrap();
+        let offscreen_ctx = self.offscreen_ctx.as_ref().unwrap();
+        let offscreen = offscreen_ctx.canvas().unwrap();
+        let (display_width, display_height) = self.display_size();
+        let bitmap_data = self.display_data();
         let image_data = ImageData::new_with_u8_clamped_array_and_sh(
-            Clamped(&self.bitmap_data),
-            self.width,
-            self.height,
+            Clamped(&bitmap_data),
+            display_width,
+            display_height,
         )
         .unwrap();
 
-        canvas_ctx.clear_rect(0.0, 0.0, self.width.into(), self.height.into());
-        canvas_ctx.set_image_smoothing_enabled(false);
-        canvas_ctx
+        offscreen.set_width(display_width);
+        offscreen.set_height(display_height);
+        offscreen_ctx
             .put_image_data(&image_data, 0.0, 0.0)
             .expect("Couldn't draw image");
+
+        let zoom = self.viewport.zoom;
+        canvas_ctx
+            .set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+            .unwrap();
+        canvas_ctx.clear_rect(
+            0.0,
+            0.0,
+            self.viewport.width.into(),
+            self.viewport.height.into(),
+        );
+        canvas_ctx
+            .set_transform(1.0, 0.0, 0.0, 1.0, self.viewport.pan_x, self.viewport.pan_y)
+            .unwrap();
+        canvas_ctx.set_fill_style(self.checkerboard.as_ref().unwrap());
+        canvas_ctx.fill_rect(
+            0.0,
+            0.0,
+            display_width as f64 * zoom,
+            display_height as f64 * zoom,
+        );
+        canvas_ctx.set_image_smoothing_enabled(zoom < 1.0);
+        canvas_ctx
+            .set_transform(
+                zoom,
+                0.0,
+                0.0,
+                zoom,
+                self.viewport.pan_x,
+                self.viewport.pan_y,
+            )
+            .unwrap();
+        canvas_ctx
+            .draw_image_with_html_canvas_element(&offscreen, 0.0, 0.0)
+            .expect("Couldn't draw image");
+
+        if zoom >= viewport::GRID_ZOOM {
+            self.draw_pixel_grid(canvas_ctx);
+        }
+
+        if self.tool == Tool::Crop {
+            if let Some(rect) = self.crop_tool.rect {
+                self.draw_crop_overlay(canvas_ctx, rect);
+            }
+        }
+
+        if self.tool == Tool::Perspective {
+            self.draw_perspective_overlay(canvas_ctx);
+        }
+
+        if self.tool == Tool::Select {
+            self.draw_selection_overlay(canvas_ctx);
+        }
+
+        if self.compare_mode == CompareMode::Split
+            && !self.show_original
+            && self.spectrum_view == SpectrumView::Off
+        {
+            self.draw_split_line(canvas_ctx);
+        }
     }
 }
+
+fn context_2d(canvas: &HtmlCanvasElement) -> CanvasRenderingContext2d {
+    canvas
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<CanvasRenderingContext2d>()
+        .unwrap()
+}
+
+/// Repeating pattern of light grey squares, a fixed size on screen.
+fn checkerboard(canvas_ctx: &CanvasRenderingContext2d) -> CanvasPattern {
+    const SQUARE: u32 = 8;
+
+    let tile = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("canvas")
+        .unwrap()
+        .dyn_into::<HtmlCanvasElement>()
+        .unwrap();
+    tile.set_width(SQUARE * 2);
+    tile.set_height(SQUARE * 2);
+
+    let tile_ctx = context_2d(&tile);
+    let size = SQUARE as f64;
+    tile_ctx.set_fill_style(&JsValue::from_str("#ffffff"));
+    tile_ctx.fill_rect(0.0, 0.0, size * 2.0, size * 2.0);
+    tile_ctx.set_fill_style(&JsValue::from_str("#cccccc"));
+    tile_ctx.fill_rect(size, 0.0, size, size);
+    tile_ctx.fill_rect(0.0, size, size, size);
+
+    canvas_ctx
+        .create_pattern_with_html_canvas_element(&tile, "repeat")
+        .unwrap()
+        .unwrap()
+}
+
+/// Distance between two touches and their midpoint in client coordinates.
+fn pinch(first: &Touch, second: &Touch) -> (f64, i32, i32) {
+    let dx = (first.client_x() - second.client_x()) as f64;
+    let dy = (first.client_y() - second.client_y()) as f64;
+
+    (
+        dx.hypot(dy),
+        (first.client_x() + second.client_x()) / 2,
+        (first.client_y() + second.client_y()) / 2,
+    )
+}
+
+/// Sends `msg` to the component from a timeout, letting the browser paint
+/// and handle input before it is processed.
+fn schedule(ctx: &Context<Image>, msg: Msg) {
+    let link = ctx.link().clone();
+    let callback = Closure::once_into_js(move || link.send_message(msg));
+
+    web_sys::window()
+        .unwrap()
+        .set_timeout_with_callback(callback.unchecked_ref())
+        .unwrap();
+}
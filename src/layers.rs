@@ -0,0 +1,311 @@
+use crate::buffer::Sample;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    SoftLight,
+    Difference,
+    Add,
+    Subtract,
+    Darken,
+    Lighten,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 14] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+        BlendMode::SoftLight,
+        BlendMode::Difference,
+        BlendMode::Add,
+        BlendMode::Subtract,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+        BlendMode::Hue,
+        BlendMode::Saturation,
+        BlendMode::Color,
+        BlendMode::Luminosity,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Screen => "Screen",
+            BlendMode::Overlay => "Overlay",
+            BlendMode::SoftLight => "Soft light",
+            BlendMode::Difference => "Difference",
+            BlendMode::Add => "Add",
+            BlendMode::Subtract => "Subtract",
+            BlendMode::Darken => "Darken",
+            BlendMode::Lighten => "Lighten",
+            BlendMode::Hue => "Hue",
+            BlendMode::Saturation => "Saturation",
+            BlendMode::Color => "Color",
+            BlendMode::Luminosity => "Luminosity",
+        }
+    }
+
+    /// Blends a source colour onto a backdrop colour, both with channels in
+    /// 0.0..=1.0, following the W3C compositing definitions.
+    pub fn blend(&self, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+        match self {
+            BlendMode::Hue => set_lum(set_sat(source, saturation(backdrop)), luminosity(backdrop)),
+            BlendMode::Saturation => {
+                set_lum(set_sat(backdrop, saturation(source)), luminosity(backdrop))
+            }
+            BlendMode::Color => set_lum(source, luminosity(backdrop)),
+            BlendMode::Luminosity => set_lum(backdrop, luminosity(source)),
+            _ => [
+                self.blend_channel(backdrop[0], source[0]),
+                self.blend_channel(backdrop[1], source[1]),
+                self.blend_channel(backdrop[2], source[2]),
+            ],
+        }
+    }
+
+    fn blend_channel(&self, backdrop: f32, source: f32) -> f32 {
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => backdrop * source,
+            BlendMode::Screen => backdrop + source - backdrop * source,
+            BlendMode::Overlay => {
+                if backdrop <= 0.5 {
+                    2.0 * backdrop * source
+                } else {
+                    1.0 - 2.0 * (1.0 - backdrop) * (1.0 - source)
+                }
+            }
+            BlendMode::SoftLight => {
+                if source <= 0.5 {
+                    backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+                } else {
+                    let d = if backdrop <= 0.25 {
+                        ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                    } else {
+                        backdrop.sqrt()
+                    };
+                    backdrop + (2.0 * source - 1.0) * (d - backdrop)
+                }
+            }
+            BlendMode::Difference => (backdrop - source).abs(),
+            BlendMode::Add => (backdrop + source).min(1.0),
+            BlendMode::Subtract => (backdrop - source).max(0.0),
+            BlendMode::Darken => backdrop.min(source),
+            BlendMode::Lighten => backdrop.max(source),
+            BlendMode::Hue | BlendMode::Saturation | BlendMode::Color | BlendMode::Luminosity => {
+                unreachable!()
+            }
+        }
+    }
+}
+
+fn luminosity(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn clip_color(color: [f32; 3]) -> [f32; 3] {
+    let l = luminosity(color);
+    let n = color[0].min(color[1]).min(color[2]);
+    let x = color[0].max(color[1]).max(color[2]);
+
+    color.map(|c| {
+        let mut c = c;
+        if n < 0.0 {
+            c = l + (c - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            c = l + (c - l) * (1.0 - l) / (x - l);
+        }
+        c
+    })
+}
+
+fn set_lum(color: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - luminosity(color);
+
+    clip_color(color.map(|c| c + d))
+}
+
+fn saturation(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_sat(color: [f32; 3], s: f32) -> [f32; 3] {
+    let max = color[0].max(color[1]).max(color[2]);
+    let min = color[0].min(color[1]).min(color[2]);
+
+    if max <= min {
+        return [0.0; 3];
+    }
+
+    color.map(|c| (c - min) * s / (max - min))
+}
+
+pub struct Layer {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub visible: bool,
+}
+
+impl Layer {
+    pub fn new(name: String, data: Vec<u8>, width: u32, height: u32) -> Self {
+        Self {
+            name,
+            data,
+            width,
+            height,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            visible: true,
+        }
+    }
+
+    /// Composites the layer over an RGBA backdrop of the given size, aligned
+    /// to the top-left corner. Parts of the layer outside the backdrop are
+    /// ignored.
+    pub fn composite_onto<S: Sample>(&self, backdrop: &mut [S], width: u32, height: u32) {
+        if !self.visible || self.opacity <= 0.0 {
+            return;
+        }
+
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                let b = ((y * width + x) * 4) as usize;
+                let s = ((y * self.width + x) * 4) as usize;
+
+                let pixel = composite_pixel(
+                    &backdrop[b..b + 4],
+                    &self.data[s..s + 4],
+                    self.opacity,
+                    self.blend_mode,
+                );
+                backdrop[b..b + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+}
+
+/// Source-over compositing with a blend mode:
+/// `co = as * (1 - ab) * Cs + as * ab * B(Cb, Cs) + (1 - as) * ab * Cb`.
+fn composite_pixel<S: Sample>(
+    backdrop: &[S],
+    source: &[u8],
+    opacity: f32,
+    mode: BlendMode,
+) -> [S; 4] {
+    let alpha_s = source[3].to_unit() * opacity;
+    let alpha_b = backdrop[3].to_unit();
+    let alpha_o = alpha_s + alpha_b * (1.0 - alpha_s);
+
+    if alpha_o <= 0.0 {
+        return [S::default(); 4];
+    }
+
+    let cb = [0, 1, 2].map(|i| backdrop[i].to_unit());
+    let cs = [0, 1, 2].map(|i| source[i].to_unit());
+    let blended = mode.blend(cb, cs);
+
+    let mut result = [S::default(); 4];
+    for i in 0..3 {
+        let premultiplied = alpha_s * (1.0 - alpha_b) * cs[i]
+            + alpha_s * alpha_b * blended[i]
+            + (1.0 - alpha_s) * alpha_b * cb[i];
+        result[i] = S::from_unit((premultiplied / alpha_o).clamp(0.0, 1.0));
+    }
+    result[3] = S::from_unit(alpha_o);
+
+    result
+}
+
+/// Flattens a stack of layers, bottom first, onto a copy of `base`.
+pub fn flatten<S: Sample>(base: &[S], width: u32, height: u32, layers: &[Layer]) -> Vec<S> {
+    let mut output = base.to_vec();
+
+    for layer in layers {
+        layer.composite_onto(&mut output, width, height);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-5,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    /// Backdrop, source and expected result for one channel.
+    fn check(mode: BlendMode, cases: &[(f32, f32, f32)]) {
+        for (backdrop, source, expected) in cases {
+            let blended = mode.blend([*backdrop; 3], [*source; 3]);
+            assert_close(&blended, &[*expected; 3]);
+        }
+    }
+
+    #[test]
+    fn separable_modes_match_reference_values() {
+        check(BlendMode::Multiply, &[(0.5, 0.4, 0.2), (1.0, 0.3, 0.3)]);
+        check(BlendMode::Screen, &[(0.5, 0.4, 0.7), (0.0, 0.3, 0.3)]);
+        check(BlendMode::Overlay, &[(0.25, 0.6, 0.3), (0.75, 0.6, 0.8)]);
+        check(
+            BlendMode::SoftLight,
+            &[
+                (0.5, 0.25, 0.375),
+                (0.16, 0.75, 0.279168),
+                (0.64, 0.75, 0.72),
+            ],
+        );
+    }
+
+    #[test]
+    fn non_separable_modes_keep_greys() {
+        let modes = [
+            BlendMode::Hue,
+            BlendMode::Saturation,
+            BlendMode::Color,
+            BlendMode::Luminosity,
+        ];
+
+        for mode in modes {
+            for grey in [0.0, 0.3, 0.5, 1.0] {
+                assert_close(&mode.blend([grey; 3], [grey; 3]), &[grey; 3]);
+            }
+        }
+
+        // A grey backdrop has no saturation for a colourful source to keep.
+        for mode in [BlendMode::Hue, BlendMode::Saturation] {
+            assert_close(&mode.blend([0.4; 3], [0.9, 0.2, 0.1]), &[0.4; 3]);
+        }
+    }
+
+    #[test]
+    fn source_over_with_half_transparent_inputs() {
+        let backdrop = [1.0, 0.0, 0.0, 0.5];
+        let source = [0, 0, 255, 255];
+        let pixel = composite_pixel(&backdrop, &source, 0.5, BlendMode::Normal);
+
+        assert_close(&pixel, &[1.0 / 3.0, 0.0, 2.0 / 3.0, 0.75]);
+    }
+}
@@ -0,0 +1,16 @@
+//! Pure image processing used by the app, exposed as a library so it can be
+//! checked by automated regression tests without a browser.
+
+pub mod alpha;
+pub mod blur;
+pub mod buffer;
+pub mod channels;
+pub mod crop;
+pub mod dither;
+pub mod fft;
+pub mod layers;
+pub mod metrics;
+pub mod quantize;
+pub mod rng;
+pub mod stego;
+pub mod transform;
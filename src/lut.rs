@@ -0,0 +1,173 @@
+/// Duotone colours that give a sepia print.
+pub const SEPIA_SHADOW: [u8; 3] = [46, 26, 12];
+pub const SEPIA_HIGHLIGHT: [u8; 3] = [255, 236, 196];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LutEffect {
+    Invert,
+    Posterize,
+    Solarize,
+    /// Paints luminance up to the threshold in one colour and above it in
+    /// another.
+    Threshold,
+    /// Maps luminance onto a ramp from the shadow to the highlight colour.
+    Duotone,
+    /// Maps luminance onto the colour ramp given by the gradient stops.
+    GradientMap,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct GradientStop {
+    /// Luminance the colour is placed at, in 0..=1.
+    pub position: f32,
+    pub color: [u8; 3],
+}
+
+#[derive(Clone, PartialEq)]
+pub struct LutParams {
+    pub effect: LutEffect,
+    /// Which of the red, green and blue channels to invert.
+    pub invert: [bool; 3],
+    pub levels: u32,
+    /// Values above the threshold are inverted when solarizing, or take
+    /// the upper colour when thresholding.
+    pub threshold: u8,
+    pub below: [u8; 3],
+    pub above: [u8; 3],
+    pub shadow: [u8; 3],
+    pub highlight: [u8; 3],
+    pub stops: Vec<GradientStop>,
+}
+
+impl Default for LutParams {
+    fn default() -> Self {
+        Self {
+            effect: LutEffect::Invert,
+            invert: [true; 3],
+            levels: 4,
+            threshold: 128,
+            below: [0, 0, 0],
+            above: [255, 255, 255],
+            shadow: SEPIA_SHADOW,
+            highlight: SEPIA_HIGHLIGHT,
+            stops: vec![
+                GradientStop {
+                    position: 0.0,
+                    color: [16, 0, 64],
+                },
+                GradientStop {
+                    position: 0.5,
+                    color: [224, 64, 32],
+                },
+                GradientStop {
+                    position: 1.0,
+                    color: [255, 240, 128],
+                },
+            ],
+        }
+    }
+}
+
+/// Builds the table mapping each channel value to its new value.
+fn channel_lut(params: &LutParams, channel: usize) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    let levels = params.levels.clamp(2, 256) as f32 - 1.0;
+
+    for (value, entry) in lut.iter_mut().enumerate() {
+        let value = value as u8;
+        *entry = match params.effect {
+            LutEffect::Invert if params.invert[channel] => 255 - value,
+            LutEffect::Posterize => {
+                ((value as f32 * levels / 255.0).round() * 255.0 / levels).round() as u8
+            }
+            LutEffect::Solarize if value > params.threshold => 255 - value,
+            _ => value,
+        };
+    }
+
+    lut
+}
+
+/// Builds the colour for each luminance value from gradient stops, which
+/// need not be sorted. Luminance before the first or after the last stop
+/// takes that stop's colour.
+fn color_ramp(stops: &[GradientStop]) -> Option<Vec<[u8; 3]>> {
+    let mut stops = stops.to_vec();
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    let (first, last) = (*stops.first()?, *stops.last()?);
+
+    let ramp = (0..256)
+        .map(|luminance| {
+            let t = luminance as f32 / 255.0;
+            if t <= first.position {
+                return first.color;
+            }
+
+            match stops.windows(2).find(|pair| t <= pair[1].position) {
+                Some([from, to]) => {
+                    let span = to.position - from.position;
+                    let weight = if span > 0.0 {
+                        (t - from.position) / span
+                    } else {
+                        1.0
+                    };
+                    [0, 1, 2].map(|c| {
+                        let (a, b) = (from.color[c] as f32, to.color[c] as f32);
+                        (a + (b - a) * weight).round() as u8
+                    })
+                }
+                _ => last.color,
+            }
+        })
+        .collect();
+
+    Some(ramp)
+}
+
+/// Applies the effect to the colour channels of an RGBA buffer in place
+/// through lookup tables, leaving alpha untouched.
+pub fn apply(data: &mut [u8], params: &LutParams) {
+    let ramp = match params.effect {
+        LutEffect::Threshold => Some(
+            (0..256)
+                .map(|luminance| {
+                    if luminance > params.threshold as usize {
+                        params.above
+                    } else {
+                        params.below
+                    }
+                })
+                .collect(),
+        ),
+        LutEffect::Duotone => color_ramp(&[
+            GradientStop {
+                position: 0.0,
+                color: params.shadow,
+            },
+            GradientStop {
+                position: 1.0,
+                color: params.highlight,
+            },
+        ]),
+        LutEffect::GradientMap => color_ramp(&params.stops),
+        _ => {
+            let luts = [0, 1, 2].map(|channel| channel_lut(params, channel));
+            for pixel in data.chunks_exact_mut(4) {
+                for (value, lut) in pixel.iter_mut().zip(&luts) {
+                    *value = lut[*value as usize];
+                }
+            }
+            return;
+        }
+    };
+
+    let ramp = match ramp {
+        Some(ramp) => ramp,
+        None => return,
+    };
+    for pixel in data.chunks_exact_mut(4) {
+        let luminance =
+            pixel[0] as f32 * 0.2126 + pixel[1] as f32 * 0.7152 + pixel[2] as f32 * 0.0722;
+        pixel[..3].copy_from_slice(&ramp[luminance.round() as usize]);
+    }
+}
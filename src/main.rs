@@ -1,9 +1,24 @@
+mod arithmetic;
+mod bitplane;
+mod chroma;
+mod color;
+mod compare;
+mod denoise;
+mod draw;
+mod file;
 mod image;
+mod lut;
+mod noise;
+mod perspective;
+mod selection;
+mod viewport;
+mod watermark;
 
 use crate::image::Image;
-use gloo_events::EventListener;
-use js_sys::Uint8Array;
-use wasm_bindgen::JsCast;
+use imgmod::{
+    alpha, blur, buffer, channels, crop, dither, fft, layers, metrics, quantize, rng, stego,
+    transform,
+};
 use yew::prelude::*;
 
 enum Msg {
@@ -50,20 +65,8 @@ impl Component for App {
             Msg::FileUpload(event) => {
                 self.is_loading = true;
                 let file_cb = ctx.link().callback(|value: Vec<u8>| Msg::FileLoaded(value));
-                let target = event.target().unwrap();
-                let target: web_sys::HtmlInputElement = target.dyn_into().unwrap();
-                let file = target.files().unwrap().get(0).unwrap();
-                let file_reader = web_sys::FileReader::new().unwrap();
-                file_reader.read_as_array_buffer(&file).unwrap();
-                let listener = EventListener::new(&file_reader, "load", move |event| {
-                    let target = event.target().unwrap();
-                    let target: web_sys::FileReader = target.dyn_into().unwrap();
-                    let result = target.result().unwrap();
-                    let array = Uint8Array::new(&result);
-
-                    file_cb.emit(array.to_vec());
-                });
-                listener.forget();
+                let file = file::selected_file(&event).unwrap();
+                file::read_file(&file, file_cb);
 
                 true
             }
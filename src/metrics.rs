@@ -0,0 +1,238 @@
+use crate::blur;
+
+const MAX_VALUE: f64 = 255.0;
+const SSIM_SIGMA: f32 = 1.5;
+const SSIM_C1: f64 = (0.01 * MAX_VALUE) * (0.01 * MAX_VALUE);
+const SSIM_C2: f64 = (0.03 * MAX_VALUE) * (0.03 * MAX_VALUE);
+/// Per-scale exponents from Wang et al., "Multi-scale structural
+/// similarity for image quality assessment".
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Quality {
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in dB; infinite for identical images.
+    pub psnr: f64,
+    pub ssim: f64,
+    pub ms_ssim: f64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct QualityReport {
+    /// Red, green and blue, in that order.
+    pub channels: [Quality; 3],
+    pub overall: Quality,
+}
+
+/// Compares two equally sized RGBA buffers channel by channel. The alpha
+/// channel is ignored.
+pub fn compare(reference: &[u8], distorted: &[u8], width: u32, height: u32) -> QualityReport {
+    let channels = [0, 1, 2].map(|channel| {
+        let a = plane(reference, channel);
+        let b = plane(distorted, channel);
+        let mse = mse(&a, &b);
+
+        Quality {
+            mse,
+            psnr: psnr(mse),
+            ssim: ssim(&a, &b, width, height),
+            ms_ssim: ms_ssim(&a, &b, width, height),
+        }
+    });
+
+    let mean = |value: fn(&Quality) -> f64| channels.iter().map(value).sum::<f64>() / 3.0;
+    let overall_mse = mean(|quality| quality.mse);
+
+    QualityReport {
+        channels,
+        overall: Quality {
+            mse: overall_mse,
+            psnr: psnr(overall_mse),
+            ssim: mean(|quality| quality.ssim),
+            ms_ssim: mean(|quality| quality.ms_ssim),
+        },
+    }
+}
+
+fn plane(data: &[u8], channel: usize) -> Vec<f32> {
+    data.iter()
+        .skip(channel)
+        .step_by(4)
+        .map(|value| *value as f32)
+        .collect()
+}
+
+pub fn mse(a: &[f32], b: &[f32]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+
+    let sum: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| {
+            let difference = (*x - *y) as f64;
+            difference * difference
+        })
+        .sum();
+
+    sum / a.len() as f64
+}
+
+pub fn psnr(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (MAX_VALUE * MAX_VALUE / mse).log10()
+    }
+}
+
+/// Mean luminance and contrast-structure terms of SSIM, computed with an
+/// 11x11 Gaussian window (sigma 1.5).
+fn ssim_terms(a: &[f32], b: &[f32], width: u32, height: u32) -> (f64, f64) {
+    let blur = |plane: &[f32]| blur::gaussian_blur_plane(plane, width, height, SSIM_SIGMA);
+    let product =
+        |x: &[f32], y: &[f32]| -> Vec<f32> { x.iter().zip(y).map(|(p, q)| p * q).collect() };
+
+    let mean_a = blur(a);
+    let mean_b = blur(b);
+    let mean_aa = blur(&product(a, a));
+    let mean_bb = blur(&product(b, b));
+    let mean_ab = blur(&product(a, b));
+
+    let mut luminance = 0.0;
+    let mut contrast_structure = 0.0;
+
+    for i in 0..a.len() {
+        let (mu_a, mu_b) = (mean_a[i] as f64, mean_b[i] as f64);
+        let variance_a = mean_aa[i] as f64 - mu_a * mu_a;
+        let variance_b = mean_bb[i] as f64 - mu_b * mu_b;
+        let covariance = mean_ab[i] as f64 - mu_a * mu_b;
+
+        luminance += (2.0 * mu_a * mu_b + SSIM_C1) / (mu_a * mu_a + mu_b * mu_b + SSIM_C1);
+        contrast_structure += (2.0 * covariance + SSIM_C2) / (variance_a + variance_b + SSIM_C2);
+    }
+
+    let count = a.len().max(1) as f64;
+    (luminance / count, contrast_structure / count)
+}
+
+/// Structural similarity of two single-channel planes; 1.0 means identical.
+pub fn ssim(a: &[f32], b: &[f32], width: u32, height: u32) -> f64 {
+    let (luminance, contrast_structure) = ssim_terms(a, b, width, height);
+
+    luminance * contrast_structure
+}
+
+/// Multi-scale SSIM over up to five dyadic scales. Scales that would shrink
+/// the image below the SSIM window are skipped and the remaining weights
+/// renormalised.
+pub fn ms_ssim(a: &[f32], b: &[f32], width: u32, height: u32) -> f64 {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    let (mut width, mut height) = (width, height);
+
+    let scales = MS_SSIM_WEIGHTS
+        .iter()
+        .scan((width, height), |size, _| {
+            let fits = size.0.min(size.1) >= 11;
+            *size = (size.0 / 2, size.1 / 2);
+            Some(fits)
+        })
+        .take_while(|fits| *fits)
+        .count()
+        .max(1);
+    let weights = &MS_SSIM_WEIGHTS[..scales];
+    let total: f64 = weights.iter().sum();
+
+    let mut result = 1.0;
+    for (scale, weight) in weights.iter().enumerate() {
+        let (luminance, contrast_structure) = ssim_terms(&a, &b, width, height);
+        let weight = weight / total;
+
+        result *= contrast_structure.max(0.0).powf(weight);
+        if scale == scales - 1 {
+            result *= luminance.max(0.0).powf(weight);
+        } else {
+            a = downsample(&a, width, height);
+            b = downsample(&b, width, height);
+            width /= 2;
+            height /= 2;
+        }
+    }
+
+    result
+}
+
+/// Halves a plane in both directions by averaging 2x2 blocks.
+fn downsample(plane: &[f32], width: u32, height: u32) -> Vec<f32> {
+    let (out_width, out_height) = (width / 2, height / 2);
+    let mut output = Vec::with_capacity((out_width * out_height) as usize);
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let index = |dx: u32, dy: u32| ((y * 2 + dy) * width + x * 2 + dx) as usize;
+            output.push(
+                (plane[index(0, 0)] + plane[index(1, 0)] + plane[index(0, 1)] + plane[index(1, 1)])
+                    / 4.0,
+            );
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 32;
+    const HEIGHT: u32 = 32;
+
+    /// Opaque RGBA gradient with some structure in every channel.
+    fn gradient() -> Vec<u8> {
+        (0..WIDTH * HEIGHT)
+            .flat_map(|i| {
+                let (x, y) = (i % WIDTH, i / WIDTH);
+                [(x * 6) as u8, (y * 6) as u8, ((x + y) * 3) as u8, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_images_are_perfect() {
+        let data = gradient();
+        let report = compare(&data, &data, WIDTH, HEIGHT);
+
+        for quality in report.channels.iter().chain([&report.overall]) {
+            assert_eq!(quality.mse, 0.0);
+            assert_eq!(quality.psnr, f64::INFINITY);
+            assert!((quality.ssim - 1.0).abs() < 1e-9);
+            assert!((quality.ms_ssim - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn constant_offset_gives_known_mse_and_psnr() {
+        let reference = gradient();
+        let distorted: Vec<u8> = reference
+            .iter()
+            .enumerate()
+            .map(|(i, value)| if i % 4 == 3 { *value } else { value + 10 })
+            .collect();
+        let report = compare(&reference, &distorted, WIDTH, HEIGHT);
+
+        let expected_psnr = 10.0 * (255.0f64 * 255.0 / 100.0).log10();
+        for quality in report.channels.iter().chain([&report.overall]) {
+            assert_eq!(quality.mse, 100.0);
+            assert!((quality.psnr - expected_psnr).abs() < 1e-9);
+            assert!(quality.ssim < 1.0);
+        }
+    }
+
+    #[test]
+    fn mse_averages_squared_differences() {
+        assert_eq!(mse(&[0.0, 0.0], &[3.0, 4.0]), 12.5);
+        assert_eq!(mse(&[], &[]), 0.0);
+    }
+}
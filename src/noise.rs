@@ -0,0 +1,105 @@
+use crate::buffer::Sample;
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NoiseKind {
+    /// Additive noise with standard deviation `amount`, in levels.
+    Gaussian,
+    /// Replaces a share `amount` of values with black or white.
+    SaltAndPepper,
+    /// Multiplicative noise with relative standard deviation `amount`.
+    Speckle,
+    /// Shot noise, with each unit of `amount` counting as one photon.
+    Poisson,
+    /// Additive noise drawn uniformly from `-amount..amount`.
+    Uniform,
+}
+
+impl NoiseKind {
+    pub fn default_amount(&self) -> f32 {
+        match self {
+            NoiseKind::Gaussian => 20.0,
+            NoiseKind::SaltAndPepper => 0.05,
+            NoiseKind::Speckle => 0.2,
+            NoiseKind::Poisson => 1.0,
+            NoiseKind::Uniform => 30.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct NoiseParams {
+    pub kind: NoiseKind,
+    pub amount: f32,
+    /// Use one noise sample per pixel for all colour channels instead of
+    /// one per channel.
+    pub monochrome: bool,
+    pub seed: u64,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::Gaussian,
+            amount: NoiseKind::Gaussian.default_amount(),
+            monochrome: false,
+            seed: 0,
+        }
+    }
+}
+
+/// Returns the noisy version of a single value in 0..=255, unclamped.
+fn noisy_value(kind: NoiseKind, value: f64, amount: f64, rng: &mut Rng) -> f64 {
+    match kind {
+        NoiseKind::Gaussian => value + rng.gaussian() * amount,
+        NoiseKind::SaltAndPepper => {
+            if rng.next_f64() >= amount {
+                value
+            } else if rng.next_f64() < 0.5 {
+                0.0
+            } else {
+                255.0
+            }
+        }
+        NoiseKind::Speckle => value + value * rng.gaussian() * amount,
+        NoiseKind::Poisson => {
+            if amount <= 0.0 {
+                value
+            } else {
+                rng.poisson(value / amount) * amount
+            }
+        }
+        NoiseKind::Uniform => value + (rng.next_f64() * 2.0 - 1.0) * amount,
+    }
+}
+
+/// Adds noise to the colour channels of an RGBA buffer in place. The same
+/// parameters always produce the same result.
+pub fn add_noise<S: Sample>(data: &mut [S], params: &NoiseParams) {
+    let mut rng = Rng::new(params.seed);
+    let amount = params.amount as f64;
+    let level = |value: S| value.to_unit() as f64 * 255.0;
+    let clamp = |value: f64| S::from_unit((value / 255.0).clamp(0.0, 1.0) as f32);
+
+    for pixel in data.chunks_exact_mut(4) {
+        if !params.monochrome {
+            for channel in &mut pixel[..3] {
+                *channel = clamp(noisy_value(params.kind, level(*channel), amount, &mut rng));
+            }
+        } else if params.kind == NoiseKind::SaltAndPepper {
+            // A negative input comes back unchanged when the pixel is kept.
+            let value = noisy_value(params.kind, -1.0, amount, &mut rng);
+            if value >= 0.0 {
+                pixel[..3].fill(clamp(value));
+            }
+        } else {
+            // Shift all channels by the change the noise makes to the
+            // pixel's mean, which keeps the hue and adds grey grain.
+            let mean = pixel[..3].iter().map(|c| level(*c)).sum::<f64>() / 3.0;
+            let offset = noisy_value(params.kind, mean, amount, &mut rng) - mean;
+            for channel in &mut pixel[..3] {
+                *channel = clamp(level(*channel) + offset);
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+#[derive(Default)]
+pub struct PerspectiveTool {
+    pub points: Vec<(f64, f64)>,
+    /// Output size chosen by the user, or `None` to estimate it from the
+    /// edge lengths of the selected quadrilateral.
+    pub output_size: Option<(u32, u32)>,
+    dragging: Option<usize>,
+}
+
+impl PerspectiveTool {
+    pub fn is_dragging(&self) -> bool {
+        self.dragging.is_some()
+    }
+
+    /// Grabs an existing corner under the pointer, or places a new one while
+    /// fewer than four have been set.
+    pub fn pointer_down(&mut self, x: f64, y: f64, tolerance: f64) {
+        let hit = self
+            .points
+            .iter()
+            .position(|(px, py)| (px - x).abs() <= tolerance && (py - y).abs() <= tolerance);
+
+        match hit {
+            Some(index) => self.dragging = Some(index),
+            None if self.points.len() < 4 => {
+                self.points.push((x, y));
+                self.dragging = Some(self.points.len() - 1);
+            }
+            None => {}
+        }
+    }
+
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        if let Some(index) = self.dragging {
+            self.points[index] = (x, y);
+        }
+    }
+
+    pub fn pointer_up(&mut self) {
+        self.dragging = None;
+    }
+
+    /// Returns the four corners ordered top-left, top-right, bottom-right,
+    /// bottom-left regardless of the order they were clicked in.
+    pub fn corners(&self) -> Option<[(f64, f64); 4]> {
+        if self.points.len() != 4 {
+            return None;
+        }
+
+        let center_x = self.points.iter().map(|(x, _)| x).sum::<f64>() / 4.0;
+        let center_y = self.points.iter().map(|(_, y)| y).sum::<f64>() / 4.0;
+
+        let mut sorted = self.points.clone();
+        sorted.sort_by(|a, b| {
+            let angle_a = (a.1 - center_y).atan2(a.0 - center_x);
+            let angle_b = (b.1 - center_y).atan2(b.0 - center_x);
+            angle_a.partial_cmp(&angle_b).unwrap()
+        });
+
+        let top_left = sorted
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.0 + a.1).partial_cmp(&(b.0 + b.1)).unwrap())
+            .map(|(index, _)| index)
+            .unwrap();
+        sorted.rotate_left(top_left);
+
+        Some([sorted[0], sorted[1], sorted[2], sorted[3]])
+    }
+
+    /// Size of the rectified output, either as chosen by the user or
+    /// estimated from the longer of each pair of opposite edges.
+    pub fn target_size(&self) -> Option<(u32, u32)> {
+        let [top_left, top_right, bottom_right, bottom_left] = self.corners()?;
+
+        if let Some(size) = self.output_size {
+            return Some(size);
+        }
+
+        let distance =
+            |a: (f64, f64), b: (f64, f64)| ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt();
+        let width = distance(top_left, top_right).max(distance(bottom_left, bottom_right));
+        let height = distance(top_left, bottom_left).max(distance(top_right, bottom_right));
+
+        Some((
+            width.round().max(1.0) as u32,
+            height.round().max(1.0) as u32,
+        ))
+    }
+}
@@ -0,0 +1,366 @@
+use crate::rng::Rng;
+
+/// Rough number of pixels k-means looks at; bigger images are sampled
+/// with an even stride.
+const KMEANS_SAMPLES: usize = 50_000;
+const KMEANS_ITERATIONS: usize = 16;
+/// Depth of the octree; 8 levels resolve every bit of a channel.
+const OCTREE_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum QuantizeMethod {
+    MedianCut,
+    Octree,
+    KMeans,
+}
+
+/// Picks a palette of at most `colors` entries for the RGB channels of an
+/// RGBA buffer. `seed` only affects k-means.
+pub fn palette(data: &[u8], colors: usize, method: QuantizeMethod, seed: u64) -> Vec<[u8; 3]> {
+    let pixels: Vec<[u8; 3]> = data.chunks_exact(4).map(|p| [p[0], p[1], p[2]]).collect();
+    if pixels.is_empty() || colors == 0 {
+        return Vec::new();
+    }
+
+    match method {
+        QuantizeMethod::MedianCut => median_cut(pixels, colors),
+        QuantizeMethod::Octree => octree(&pixels, colors),
+        QuantizeMethod::KMeans => k_means(&pixels, colors, seed),
+    }
+}
+
+fn average(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for pixel in pixels {
+        for (total, value) in sum.iter_mut().zip(pixel) {
+            *total += *value as u64;
+        }
+    }
+
+    let count = pixels.len().max(1) as u64;
+    sum.map(|total| ((total + count / 2) / count) as u8)
+}
+
+/// Channel with the widest spread in `pixels`, and that spread.
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let min = pixels.iter().map(|p| p[c]).min().unwrap_or(0);
+            let max = pixels.iter().map(|p| p[c]).max().unwrap_or(0);
+            (c, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+/// Heckbert's median cut: repeatedly splits the box with the widest
+/// channel range at the median of that channel.
+fn median_cut(pixels: Vec<[u8; 3]>, colors: usize) -> Vec<[u8; 3]> {
+    // Each box keeps its widest channel and range so only the two halves
+    // of a split need measuring again.
+    let widest = widest_channel(&pixels);
+    let mut boxes = vec![(pixels, widest)];
+
+    while boxes.len() < colors {
+        let (index, (_, (channel, range))) = boxes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, (_, range)))| *range)
+            .unwrap();
+        if *range == 0 {
+            break;
+        }
+
+        let channel = *channel;
+        let (mut pixels, _) = boxes.swap_remove(index);
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        for half in [pixels, upper] {
+            let widest = widest_channel(&half);
+            boxes.push((half, widest));
+        }
+    }
+
+    boxes.iter().map(|(pixels, _)| average(pixels)).collect()
+}
+
+#[derive(Default)]
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    sum: [u64; 3],
+    count: u64,
+    leaf: bool,
+}
+
+/// Gervautz and Purgathofer's octree quantizer: every colour is added to a
+/// tree keyed by its bits, then the deepest nodes are merged into their
+/// parents until few enough leaves remain.
+fn octree(pixels: &[[u8; 3]], colors: usize) -> Vec<[u8; 3]> {
+    let mut nodes = vec![OctreeNode::default()];
+    // Nodes with children, by depth, that can be folded into leaves.
+    let mut reducible: Vec<Vec<usize>> = vec![Vec::new(); OCTREE_DEPTH];
+    let mut leaves = 0;
+
+    for pixel in pixels {
+        let mut node = 0;
+        for (depth, reducible) in reducible.iter_mut().enumerate() {
+            if nodes[node].leaf {
+                break;
+            }
+
+            let shift = 7 - depth;
+            let child = ((pixel[0] >> shift & 1) << 2
+                | (pixel[1] >> shift & 1) << 1
+                | (pixel[2] >> shift & 1)) as usize;
+
+            node = match nodes[node].children[child] {
+                Some(index) => index,
+                None => {
+                    if nodes[node].children.iter().all(Option::is_none) {
+                        reducible.push(node);
+                    }
+
+                    let index = nodes.len();
+                    nodes.push(OctreeNode {
+                        leaf: depth == OCTREE_DEPTH - 1,
+                        ..OctreeNode::default()
+                    });
+                    nodes[node].children[child] = Some(index);
+                    if depth == OCTREE_DEPTH - 1 {
+                        leaves += 1;
+                    }
+                    index
+                }
+            };
+        }
+
+        let node = &mut nodes[node];
+        for (total, value) in node.sum.iter_mut().zip(pixel) {
+            *total += *value as u64;
+        }
+        node.count += 1;
+    }
+
+    while leaves > colors {
+        let depth = match reducible.iter().rposition(|nodes| !nodes.is_empty()) {
+            Some(depth) => depth,
+            None => break,
+        };
+        let index = reducible[depth].pop().unwrap();
+
+        let mut merged = 0;
+        for child in nodes[index]
+            .children
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>()
+        {
+            let (sum, count) = (nodes[child].sum, nodes[child].count);
+            let node = &mut nodes[index];
+            for (total, value) in node.sum.iter_mut().zip(sum) {
+                *total += value;
+            }
+            node.count += count;
+            merged += 1;
+        }
+
+        let node = &mut nodes[index];
+        node.children = [None; 8];
+        node.leaf = true;
+        leaves = leaves + 1 - merged;
+    }
+
+    let mut palette = Vec::with_capacity(leaves);
+    collect_leaves(&nodes, 0, &mut palette);
+    palette
+}
+
+fn collect_leaves(nodes: &[OctreeNode], index: usize, palette: &mut Vec<[u8; 3]>) {
+    let node = &nodes[index];
+
+    if node.leaf {
+        if node.count > 0 {
+            palette.push(
+                node.sum
+                    .map(|sum| ((sum + node.count / 2) / node.count) as u8),
+            );
+        }
+        return;
+    }
+
+    for child in node.children.iter().flatten() {
+        collect_leaves(nodes, *child, palette);
+    }
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Lloyd's k-means in RGB space, seeded with k-means++ from `seed`.
+fn k_means(pixels: &[[u8; 3]], colors: usize, seed: u64) -> Vec<[u8; 3]> {
+    let stride = (pixels.len() / KMEANS_SAMPLES).max(1);
+    let samples: Vec<[f32; 3]> = pixels
+        .iter()
+        .step_by(stride)
+        .map(|p| p.map(|c| c as f32))
+        .collect();
+    let mut rng = Rng::new(seed);
+
+    // k-means++: each further centre is drawn with probability proportional
+    // to its squared distance from the nearest centre so far.
+    let pick =
+        |rng: &mut Rng| ((rng.next_f64() * samples.len() as f64) as usize).min(samples.len() - 1);
+    let mut centers = vec![samples[pick(&mut rng)]];
+    let mut nearest: Vec<f32> = samples.iter().map(|s| distance(*s, centers[0])).collect();
+
+    while centers.len() < colors {
+        let total: f64 = nearest.iter().map(|d| *d as f64).sum();
+        if total <= 0.0 {
+            break;
+        }
+
+        let mut target = rng.next_f64() * total;
+        let mut chosen = samples.len() - 1;
+        for (i, d) in nearest.iter().enumerate() {
+            target -= *d as f64;
+            if target < 0.0 {
+                chosen = i;
+                break;
+            }
+        }
+
+        let center = samples[chosen];
+        centers.push(center);
+        for (d, sample) in nearest.iter_mut().zip(&samples) {
+            *d = d.min(distance(*sample, center));
+        }
+    }
+
+    let mut assignment = vec![usize::MAX; samples.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (sample, assigned) in samples.iter().zip(assignment.iter_mut()) {
+            let closest = (0..centers.len())
+                .min_by(|a, b| {
+                    distance(*sample, centers[*a]).total_cmp(&distance(*sample, centers[*b]))
+                })
+                .unwrap();
+            if *assigned != closest {
+                *assigned = closest;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![([0.0f64; 3], 0usize); centers.len()];
+        for (sample, assigned) in samples.iter().zip(&assignment) {
+            let (sum, count) = &mut sums[*assigned];
+            for c in 0..3 {
+                sum[c] += sample[c] as f64;
+            }
+            *count += 1;
+        }
+
+        // Empty clusters keep their previous centre.
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum.map(|total| (total / count as f64) as f32);
+            }
+        }
+    }
+
+    centers
+        .iter()
+        .map(|center| center.map(|c| c.round().clamp(0.0, 255.0) as u8))
+        .collect()
+}
+
+/// GIMP palette file.
+pub fn to_gpl(palette: &[[u8; 3]], name: &str) -> String {
+    let mut output = format!("GIMP Palette\nName: {}\nColumns: 8\n#\n", name);
+
+    for [r, g, b] in palette {
+        output.push_str(&format!(
+            "{:3} {:3} {:3}\t#{:02x}{:02x}{:02x}\n",
+            r, g, b, r, g, b
+        ));
+    }
+
+    output
+}
+
+/// Adobe colour swatch file, version 1, with 16-bit RGB entries.
+pub fn to_aco(palette: &[[u8; 3]]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(4 + palette.len() * 10);
+    output.extend_from_slice(&1u16.to_be_bytes());
+    output.extend_from_slice(&(palette.len() as u16).to_be_bytes());
+
+    for color in palette {
+        // Colour space 0 is RGB.
+        output.extend_from_slice(&0u16.to_be_bytes());
+        for channel in color {
+            output.extend_from_slice(&(*channel as u16 * 257).to_be_bytes());
+        }
+        output.extend_from_slice(&0u16.to_be_bytes());
+    }
+
+    output
+}
+
+pub fn to_json(palette: &[[u8; 3]]) -> String {
+    let colors: Vec<String> = palette
+        .iter()
+        .map(|[r, g, b]| format!("    \"#{:02x}{:02x}{:02x}\"", r, g, b))
+        .collect();
+
+    format!("{{\n  \"colors\": [\n{}\n  ]\n}}\n", colors.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGBA image with far more distinct colours than any palette here.
+    fn image() -> Vec<u8> {
+        (0..64 * 64)
+            .flat_map(|i| {
+                [
+                    (i % 64 * 4) as u8,
+                    (i / 64 * 4) as u8,
+                    (i * 7 % 256) as u8,
+                    255,
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn palettes_have_at_most_the_requested_colours() {
+        let data = image();
+        for method in [
+            QuantizeMethod::MedianCut,
+            QuantizeMethod::Octree,
+            QuantizeMethod::KMeans,
+        ] {
+            for colors in [1, 2, 5, 16, 64] {
+                let palette = palette(&data, colors, method, 0);
+                assert!(!palette.is_empty() && palette.len() <= colors);
+            }
+        }
+    }
+
+    #[test]
+    fn k_means_is_reproducible_from_its_seed() {
+        let data = image();
+
+        assert_eq!(
+            palette(&data, 8, QuantizeMethod::KMeans, 42),
+            palette(&data, 8, QuantizeMethod::KMeans, 42)
+        );
+    }
+}
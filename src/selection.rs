@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+
+use crate::blur;
+use crate::buffer::Sample;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectionShape {
+    Rectangle,
+    Ellipse,
+    Lasso,
+    Polygon,
+    MagicWand,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectionMode {
+    Replace,
+    Add,
+    Subtract,
+    Intersect,
+}
+
+/// Soft selection where every pixel holds a coverage between 0.0 (not
+/// selected) and 1.0 (fully selected).
+#[derive(Clone)]
+pub struct Mask {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl Mask {
+    pub fn new(width: u32, height: u32, value: f32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![value; (width * height) as usize],
+        }
+    }
+
+    /// Whether no pixel is selected at all.
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().all(|coverage| *coverage <= 0.0)
+    }
+
+    pub fn rectangle(width: u32, height: u32, from: (f64, f64), to: (f64, f64)) -> Self {
+        let points = [
+            (from.0, from.1),
+            (to.0, from.1),
+            (to.0, to.1),
+            (from.0, to.1),
+        ];
+
+        Self::polygon(width, height, &points)
+    }
+
+    /// Ellipse inscribed in the rectangle spanned by `from` and `to`.
+    pub fn ellipse(width: u32, height: u32, from: (f64, f64), to: (f64, f64)) -> Self {
+        let center_x = (from.0 + to.0) / 2.0;
+        let center_y = (from.1 + to.1) / 2.0;
+        let radius_x = (to.0 - from.0).abs() / 2.0;
+        let radius_y = (to.1 - from.1).abs() / 2.0;
+
+        let segments = ((radius_x + radius_y) * 2.0).clamp(32.0, 1024.0) as usize;
+        let points: Vec<(f64, f64)> = (0..segments)
+            .map(|i| {
+                let angle = i as f64 / segments as f64 * std::f64::consts::TAU;
+                (
+                    center_x + radius_x * angle.cos(),
+                    center_y + radius_y * angle.sin(),
+                )
+            })
+            .collect();
+
+        Self::polygon(width, height, &points)
+    }
+
+    /// Rasterises a closed polygon with the even-odd rule, anti-aliasing
+    /// edges using four sub-scanlines per pixel row.
+    pub fn polygon(width: u32, height: u32, points: &[(f64, f64)]) -> Self {
+        const SUBSAMPLES: usize = 4;
+
+        let mut mask = Self::new(width, height, 0.0);
+        if points.len() < 3 {
+            return mask;
+        }
+
+        let weight = 1.0 / SUBSAMPLES as f32;
+        let mut crossings = Vec::new();
+
+        for y in 0..height {
+            for sub in 0..SUBSAMPLES {
+                let scan_y = y as f64 + (sub as f64 + 0.5) / SUBSAMPLES as f64;
+
+                crossings.clear();
+                for i in 0..points.len() {
+                    let (x0, y0) = points[i];
+                    let (x1, y1) = points[(i + 1) % points.len()];
+
+                    if (y0 <= scan_y && y1 > scan_y) || (y1 <= scan_y && y0 > scan_y) {
+                        crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
+                    }
+                }
+                crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                for span in crossings.chunks_exact(2) {
+                    mask.fill_span(y, span[0], span[1], weight);
+                }
+            }
+        }
+
+        mask
+    }
+
+    /// Adds `weight` times the horizontal coverage of `[from, to)` to each
+    /// pixel of the given row.
+    fn fill_span(&mut self, y: u32, from: f64, to: f64, weight: f32) {
+        let from = from.clamp(0.0, self.width as f64);
+        let to = to.clamp(0.0, self.width as f64);
+        if to <= from {
+            return;
+        }
+
+        let row = (y * self.width) as usize;
+        let first = from.floor() as u32;
+        let last = (to.ceil() as u32).min(self.width);
+
+        for x in first..last {
+            let coverage = (to.min(x as f64 + 1.0) - from.max(x as f64)).max(0.0) as f32;
+            let value = &mut self.data[row + x as usize];
+            *value = (*value + coverage * weight).min(1.0);
+        }
+    }
+
+    /// Selects the region connected to `seed` whose colours differ from the
+    /// seed colour by at most `tolerance` in every channel.
+    pub fn magic_wand(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        seed: (u32, u32),
+        tolerance: u8,
+    ) -> Self {
+        let mut mask = Self::new(width, height, 0.0);
+        if seed.0 >= width || seed.1 >= height {
+            return mask;
+        }
+
+        let seed_index = ((seed.1 * width + seed.0) * 4) as usize;
+        let seed_color = &data[seed_index..seed_index + 4];
+        let matches = |index: usize| {
+            let pixel = &data[index * 4..index * 4 + 4];
+            pixel
+                .iter()
+                .zip(seed_color)
+                .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+        };
+
+        let mut queue = VecDeque::new();
+        let start = (seed.1 * width + seed.0) as usize;
+        mask.data[start] = 1.0;
+        queue.push_back(seed);
+
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+
+            for (nx, ny) in neighbours {
+                if nx >= width || ny >= height {
+                    continue;
+                }
+
+                let index = (ny * width + nx) as usize;
+                if mask.data[index] == 0.0 && matches(index) {
+                    mask.data[index] = 1.0;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        mask
+    }
+
+    pub fn combine(&mut self, other: &Mask, mode: SelectionMode) {
+        for (value, other) in self.data.iter_mut().zip(&other.data) {
+            *value = match mode {
+                SelectionMode::Replace => *other,
+                SelectionMode::Add => value.max(*other),
+                SelectionMode::Subtract => *value * (1.0 - other),
+                SelectionMode::Intersect => value.min(*other),
+            };
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for value in self.data.iter_mut() {
+            *value = 1.0 - *value;
+        }
+    }
+
+    /// Expands the selection by `radius` pixels.
+    pub fn grow(&mut self, radius: u32) {
+        self.morph(radius, f32::max);
+    }
+
+    /// Contracts the selection by `radius` pixels.
+    pub fn shrink(&mut self, radius: u32) {
+        self.morph(radius, f32::min);
+    }
+
+    /// Softens the selection edge with a Gaussian of the given radius.
+    pub fn feather(&mut self, radius: f32) {
+        self.data = blur::gaussian_blur_plane(&self.data, self.width, self.height, radius / 2.0);
+    }
+
+    /// Separable square-neighbourhood dilation or erosion, depending on
+    /// whether `pick` is `max` or `min`.
+    fn morph(&mut self, radius: u32, pick: fn(f32, f32) -> f32) {
+        let width = self.width as i32;
+        let height = self.height as i32;
+        let radius = radius as i32;
+        let mut horizontal = self.data.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let from = (x - radius).max(0);
+                let to = (x + radius).min(width - 1);
+                horizontal[(y * width + x) as usize] = (from..=to)
+                    .map(|sx| self.data[(y * width + sx) as usize])
+                    .reduce(pick)
+                    .unwrap();
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let from = (y - radius).max(0);
+                let to = (y + radius).min(height - 1);
+                self.data[(y * width + x) as usize] = (from..=to)
+                    .map(|sy| horizontal[(sy * width + x) as usize])
+                    .reduce(pick)
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Blends `processed` back towards `original` outside the selection so
+    /// that an operation only affects the selected pixels.
+    pub fn apply<S: Sample>(&self, original: &[S], processed: &mut [S]) {
+        for (i, coverage) in self.data.iter().enumerate() {
+            if *coverage >= 1.0 {
+                continue;
+            }
+
+            for c in i * 4..i * 4 + 4 {
+                let from = original[c].to_f32();
+                let to = processed[c].to_f32();
+                processed[c] = S::from_f32(from + (to - from) * coverage);
+            }
+        }
+    }
+
+    /// Returns a copy of `data` with unselected pixels dimmed, for display.
+    pub fn highlight(&self, data: &[u8]) -> Vec<u8> {
+        let mut highlighted = data.to_vec();
+
+        for (i, coverage) in self.data.iter().enumerate() {
+            let factor = 0.4 + 0.6 * coverage;
+            for c in i * 4..i * 4 + 3 {
+                highlighted[c] = (data[c] as f32 * factor) as u8;
+            }
+        }
+
+        highlighted
+    }
+}
+
+pub struct SelectionTool {
+    pub shape: SelectionShape,
+    pub mode: SelectionMode,
+    pub tolerance: u8,
+    pub radius: u32,
+    pub points: Vec<(f64, f64)>,
+    drawing: bool,
+}
+
+impl Default for SelectionTool {
+    fn default() -> Self {
+        Self {
+            shape: SelectionShape::Rectangle,
+            mode: SelectionMode::Replace,
+            tolerance: 32,
+            radius: 4,
+            points: Vec::new(),
+            drawing: false,
+        }
+    }
+}
+
+impl SelectionTool {
+    pub fn is_drawing(&self) -> bool {
+        self.drawing
+    }
+
+    /// Starts a new shape. For polygons each press adds a vertex instead, and
+    /// pressing within `tolerance` of the first vertex closes the outline, in
+    /// which case the finished polygon is returned.
+    pub fn pointer_down(&mut self, x: f64, y: f64, tolerance: f64) -> Option<Vec<(f64, f64)>> {
+        if self.shape == SelectionShape::Polygon {
+            if let Some(&(first_x, first_y)) = self.points.first() {
+                if self.points.len() >= 3
+                    && (first_x - x).abs() <= tolerance
+                    && (first_y - y).abs() <= tolerance
+                {
+                    return Some(std::mem::take(&mut self.points));
+                }
+            }
+
+            self.points.push((x, y));
+            return None;
+        }
+
+        self.points = vec![(x, y)];
+        self.drawing = true;
+
+        None
+    }
+
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        if !self.drawing {
+            return;
+        }
+
+        match self.shape {
+            SelectionShape::Lasso => self.points.push((x, y)),
+            SelectionShape::Rectangle | SelectionShape::Ellipse => {
+                self.points.truncate(1);
+                self.points.push((x, y));
+            }
+            SelectionShape::Polygon | SelectionShape::MagicWand => {}
+        }
+    }
+
+    /// Finishes a dragged shape, returning its points.
+    pub fn pointer_up(&mut self) -> Option<Vec<(f64, f64)>> {
+        if !self.drawing {
+            return None;
+        }
+
+        self.drawing = false;
+        Some(std::mem::take(&mut self.points))
+    }
+
+    /// Builds the mask for a finished shape. Returns `None` for shapes that
+    /// did not cover any area.
+    pub fn build_mask(
+        &self,
+        points: &[(f64, f64)],
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Option<Mask> {
+        match self.shape {
+            SelectionShape::Rectangle | SelectionShape::Ellipse if points.len() == 2 => {
+                let (from, to) = (points[0], points[1]);
+                if (to.0 - from.0).abs() < 1.0 || (to.1 - from.1).abs() < 1.0 {
+                    return None;
+                }
+
+                Some(match self.shape {
+                    SelectionShape::Rectangle => Mask::rectangle(width, height, from, to),
+                    _ => Mask::ellipse(width, height, from, to),
+                })
+            }
+            SelectionShape::Lasso | SelectionShape::Polygon if points.len() >= 3 => {
+                Some(Mask::polygon(width, height, points))
+            }
+            SelectionShape::MagicWand => {
+                let (x, y) = *points.first()?;
+                if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+                    return None;
+                }
+
+                Some(Mask::magic_wand(
+                    data,
+                    width,
+                    height,
+                    (x as u32, y as u32),
+                    self.tolerance,
+                ))
+            }
+            _ => None,
+        }
+    }
+}
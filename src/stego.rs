@@ -0,0 +1,307 @@
+use std::fmt;
+
+use crate::rng::Rng;
+
+/// Marks the start of a hidden payload so extraction can tell an image
+/// with a message from one without.
+const MAGIC: [u8; 4] = *b"LSB1";
+/// Magic, kind byte and payload length.
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4;
+
+#[derive(Clone, PartialEq)]
+pub struct StegoParams {
+    /// Which of the red, green, blue and alpha channels carry data.
+    pub channels: [bool; 4],
+    /// Low bits used in each carrying channel, 1 to 8.
+    pub bits: u32,
+    /// Seeds the order in which values are visited; empty keeps image
+    /// order.
+    pub password: String,
+}
+
+impl Default for StegoParams {
+    fn default() -> Self {
+        Self {
+            channels: [true, true, true, false],
+            bits: 1,
+            password: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Payload {
+    Text(String),
+    File { name: String, data: Vec<u8> },
+}
+
+impl Payload {
+    fn kind(&self) -> u8 {
+        match self {
+            Payload::Text(_) => 0,
+            Payload::File { .. } => 1,
+        }
+    }
+
+    /// Payload bytes after the header. Files store their name first,
+    /// prefixed by its length.
+    fn body(&self) -> Vec<u8> {
+        match self {
+            Payload::Text(text) => text.as_bytes().to_vec(),
+            Payload::File { name, data } => {
+                let name = &name.as_bytes()[..name.len().min(255)];
+                let mut body = Vec::with_capacity(1 + name.len() + data.len());
+                body.push(name.len() as u8);
+                body.extend_from_slice(name);
+                body.extend_from_slice(data);
+                body
+            }
+        }
+    }
+
+    fn from_body(kind: u8, body: Vec<u8>) -> Option<Self> {
+        match kind {
+            0 => String::from_utf8(body).ok().map(Payload::Text),
+            1 => {
+                let name_len = *body.first()? as usize;
+                let name = body.get(1..1 + name_len)?;
+                Some(Payload::File {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    data: body[1 + name_len..].to_vec(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Bytes the payload occupies in the image, header included.
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.body().len()
+    }
+}
+
+pub enum StegoError {
+    TooLarge { needed: usize, capacity: usize },
+    NotFound,
+}
+
+impl fmt::Display for StegoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StegoError::TooLarge { needed, capacity } => write!(
+                f,
+                "Payload needs {} bytes but the image holds {}",
+                needed, capacity
+            ),
+            StegoError::NotFound => write!(f, "No hidden payload found"),
+        }
+    }
+}
+
+/// Indices into the RGBA buffer of the values that carry data, in the
+/// order they are written.
+fn carriers(len: usize, params: &StegoParams) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..len)
+        .filter(|index| params.channels[index % 4])
+        .collect();
+
+    if !params.password.is_empty() {
+        Rng::new(hash(&params.password)).shuffle(&mut indices);
+    }
+
+    indices
+}
+
+/// FNV-1a, to turn a password into a seed.
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Total bytes an RGBA buffer of `len` values can hold, header included.
+pub fn capacity(len: usize, params: &StegoParams) -> usize {
+    let channels = params.channels.iter().filter(|used| **used).count();
+    len / 4 * channels * params.bits.clamp(1, 8) as usize / 8
+}
+
+/// Room left for the payload itself once the header is written.
+pub fn payload_capacity(len: usize, params: &StegoParams) -> usize {
+    capacity(len, params).saturating_sub(HEADER_LEN)
+}
+
+/// Hides `payload` in the low bits of the chosen channels.
+pub fn embed(data: &mut [u8], params: &StegoParams, payload: &Payload) -> Result<(), StegoError> {
+    let body = payload.body();
+    let needed = HEADER_LEN + body.len();
+    let capacity = capacity(data.len(), params);
+    if needed > capacity {
+        return Err(StegoError::TooLarge { needed, capacity });
+    }
+
+    let mut bytes = Vec::with_capacity(needed);
+    bytes.extend_from_slice(&MAGIC);
+    bytes.push(payload.kind());
+    bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&body);
+
+    let bits = params.bits.clamp(1, 8);
+    let mut stream = BitStream::new(carriers(data.len(), params), bits);
+    for byte in bytes {
+        for shift in (0..8).rev() {
+            stream.write(data, byte >> shift & 1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recovers a payload hidden with the same parameters.
+pub fn extract(data: &[u8], params: &StegoParams) -> Result<Payload, StegoError> {
+    let bits = params.bits.clamp(1, 8);
+    let capacity = capacity(data.len(), params);
+    let mut stream = BitStream::new(carriers(data.len(), params), bits);
+    let mut read = |count: usize| -> Vec<u8> {
+        (0..count)
+            .map(|_| (0..8).fold(0, |byte, _| byte << 1 | stream.read(data)))
+            .collect()
+    };
+
+    if capacity < HEADER_LEN {
+        return Err(StegoError::NotFound);
+    }
+    let header = read(HEADER_LEN);
+    if header[..MAGIC.len()] != MAGIC {
+        return Err(StegoError::NotFound);
+    }
+
+    let kind = header[MAGIC.len()];
+    let len = u32::from_be_bytes(header[MAGIC.len() + 1..].try_into().unwrap()) as usize;
+    if len > capacity - HEADER_LEN {
+        return Err(StegoError::NotFound);
+    }
+
+    Payload::from_body(kind, read(len)).ok_or(StegoError::NotFound)
+}
+
+/// Walks the carrier values, `bits` low bits at a time, most significant
+/// first.
+struct BitStream {
+    carriers: Vec<usize>,
+    bits: u32,
+    position: usize,
+}
+
+impl BitStream {
+    fn new(carriers: Vec<usize>, bits: u32) -> Self {
+        Self {
+            carriers,
+            bits,
+            position: 0,
+        }
+    }
+
+    fn next(&mut self) -> (usize, u32) {
+        let index = self.carriers[self.position / self.bits as usize];
+        let shift = self.bits - 1 - (self.position % self.bits as usize) as u32;
+        self.position += 1;
+
+        (index, shift)
+    }
+
+    fn write(&mut self, data: &mut [u8], bit: u8) {
+        let (index, shift) = self.next();
+        data[index] = data[index] & !(1 << shift) | bit << shift;
+    }
+
+    fn read(&mut self, data: &[u8]) -> u8 {
+        let (index, shift) = self.next();
+        data[index] >> shift & 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGBA cover image with varied low bits.
+    fn cover() -> Vec<u8> {
+        (0..32 * 32 * 4).map(|i| (i * 37 % 251) as u8).collect()
+    }
+
+    fn params(bits: u32, password: &str) -> StegoParams {
+        StegoParams {
+            bits,
+            password: password.to_string(),
+            ..StegoParams::default()
+        }
+    }
+
+    fn file() -> Payload {
+        Payload::File {
+            name: "notes.bin".to_string(),
+            data: (0..=255).collect(),
+        }
+    }
+
+    fn round_trip(params: &StegoParams, payload: &Payload) {
+        let mut data = cover();
+        assert!(embed(&mut data, params, payload).is_ok());
+
+        match extract(&data, params) {
+            Ok(extracted) => assert!(extracted == *payload),
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    #[test]
+    fn text_and_files_round_trip() {
+        for bits in [1, 3] {
+            round_trip(
+                &params(bits, ""),
+                &Payload::Text("Hidden in plain sight".into()),
+            );
+            round_trip(&params(bits, ""), &file());
+        }
+    }
+
+    #[test]
+    fn password_round_trip() {
+        let payload = Payload::Text("Only with the password".into());
+        let params = params(1, "hunter2");
+        round_trip(&params, &payload);
+
+        let mut data = cover();
+        assert!(embed(&mut data, &params, &payload).is_ok());
+        let unlocked = extract(&data, &StegoParams::default());
+        assert!(!matches!(unlocked, Ok(extracted) if extracted == payload));
+    }
+
+    #[test]
+    fn payload_one_byte_over_capacity_is_too_large() {
+        let params = params(1, "");
+        let mut data = cover();
+        let room = payload_capacity(data.len(), &params);
+
+        let fits = Payload::Text("x".repeat(room));
+        assert!(embed(&mut data, &params, &fits).is_ok());
+
+        let over = Payload::Text("x".repeat(room + 1));
+        let capacity = capacity(data.len(), &params);
+        assert!(matches!(
+            embed(&mut data, &params, &over),
+            Err(StegoError::TooLarge { needed, capacity: reported })
+                if needed == capacity + 1 && reported == capacity
+        ));
+    }
+
+    #[test]
+    fn plain_image_has_no_payload() {
+        for bits in [1, 3] {
+            assert!(matches!(
+                extract(&cover(), &params(bits, "")),
+                Err(StegoError::NotFound)
+            ));
+        }
+    }
+}
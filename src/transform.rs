@@ -0,0 +1,339 @@
+use crate::buffer::Sample;
+
+pub type Matrix3 = [[f64; 3]; 3];
+
+/// A 2x3 affine matrix; the implicit third row is `[0, 0, 1]`.
+pub type Affine = [[f64; 3]; 2];
+
+pub const IDENTITY_AFFINE: Affine = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
+
+/// Individual affine controls. Rotation and shear are in degrees and the
+/// transform is applied around the image centre.
+#[derive(Clone, Copy, PartialEq)]
+pub struct AffineParams {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub rotation: f64,
+    pub shear_x: f64,
+    pub shear_y: f64,
+    pub translate_x: f64,
+    pub translate_y: f64,
+}
+
+impl Default for AffineParams {
+    fn default() -> Self {
+        Self {
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation: 0.0,
+            shear_x: 0.0,
+            shear_y: 0.0,
+            translate_x: 0.0,
+            translate_y: 0.0,
+        }
+    }
+}
+
+impl AffineParams {
+    /// Composes the controls into a single matrix: scale, then shear, then
+    /// rotate about `center`, then translate.
+    pub fn to_matrix(self, center_x: f64, center_y: f64) -> Affine {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let to_origin = [[1.0, 0.0, -center_x], [0.0, 1.0, -center_y]];
+        let scale = [[self.scale_x, 0.0, 0.0], [0.0, self.scale_y, 0.0]];
+        let shear = [
+            [1.0, self.shear_x.to_radians().tan(), 0.0],
+            [self.shear_y.to_radians().tan(), 1.0, 0.0],
+        ];
+        let rotate = [[cos, -sin, 0.0], [sin, cos, 0.0]];
+        let from_origin = [
+            [1.0, 0.0, center_x + self.translate_x],
+            [0.0, 1.0, center_y + self.translate_y],
+        ];
+
+        [scale, shear, rotate, from_origin]
+            .iter()
+            .fold(to_origin, |matrix, next| compose_affine(next, &matrix))
+    }
+}
+
+/// Returns the matrix that applies `second` after `first`.
+pub fn compose_affine(second: &Affine, first: &Affine) -> Affine {
+    let mut result = [[0.0; 3]; 2];
+
+    for row in 0..2 {
+        for col in 0..3 {
+            result[row][col] = second[row][0] * first[0][col] + second[row][1] * first[1][col];
+        }
+        result[row][2] += second[row][2];
+    }
+
+    result
+}
+
+pub fn invert_affine(matrix: &Affine) -> Option<Affine> {
+    let [[a, b, c], [d, e, f]] = *matrix;
+    let determinant = a * e - b * d;
+
+    if determinant.abs() < 1e-10 {
+        return None;
+    }
+
+    Some([
+        [
+            e / determinant,
+            -b / determinant,
+            (b * f - c * e) / determinant,
+        ],
+        [
+            -d / determinant,
+            a / determinant,
+            (c * d - a * f) / determinant,
+        ],
+    ])
+}
+
+/// Computes the homography that maps each `src` point onto the matching
+/// `dst` point. Returns `None` when the points are degenerate (three or more
+/// of them collinear).
+pub fn homography(src: [(f64, f64); 4], dst: [(f64, f64); 4]) -> Option<Matrix3> {
+    // The system can still be solvable for such points, but only by a
+    // singular matrix that flattens the image onto a line.
+    if has_collinear_triple(&src) || has_collinear_triple(&dst) {
+        return None;
+    }
+
+    // Each correspondence gives two rows of the 8x8 system A * h = b, where
+    // h holds the matrix entries with h33 fixed to 1.
+    let mut system = [[0.0; 9]; 8];
+
+    for i in 0..4 {
+        let (x, y) = src[i];
+        let (u, v) = dst[i];
+
+        system[i * 2] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u];
+        system[i * 2 + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v];
+    }
+
+    let h = solve(&mut system)?;
+
+    Some([[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], 1.0]])
+}
+
+/// Whether any three of the points lie on a line, or two coincide.
+fn has_collinear_triple(points: &[(f64, f64); 4]) -> bool {
+    [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]]
+        .iter()
+        .any(|[a, b, c]| {
+            let (a, b, c) = (points[*a], points[*b], points[*c]);
+            let (ab, ac) = ((b.0 - a.0, b.1 - a.1), (c.0 - a.0, c.1 - a.1));
+            let cross = ab.0 * ac.1 - ab.1 * ac.0;
+
+            cross.abs() <= 1e-9 * ab.0.hypot(ab.1) * ac.0.hypot(ac.1)
+        })
+}
+
+/// Solves an 8x8 linear system given as an augmented matrix using Gaussian
+/// elimination with partial pivoting.
+fn solve(system: &mut [[f64; 9]; 8]) -> Option<[f64; 8]> {
+    for col in 0..8 {
+        let pivot = (col..8).max_by(|a, b| {
+            system[*a][col]
+                .abs()
+                .partial_cmp(&system[*b][col].abs())
+                .unwrap()
+        })?;
+
+        if system[pivot][col].abs() < 1e-10 {
+            return None;
+        }
+
+        system.swap(col, pivot);
+
+        for row in 0..8 {
+            if row == col {
+                continue;
+            }
+
+            let pivot_row = system[col];
+            let factor = system[row][col] / pivot_row[col];
+            for (value, pivot_value) in system[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+
+    let mut result = [0.0; 8];
+    for i in 0..8 {
+        result[i] = system[i][8] / system[i][i];
+    }
+
+    Some(result)
+}
+
+pub fn apply(matrix: &Matrix3, x: f64, y: f64) -> (f64, f64) {
+    let w = matrix[2][0] * x + matrix[2][1] * y + matrix[2][2];
+    let u = (matrix[0][0] * x + matrix[0][1] * y + matrix[0][2]) / w;
+    let v = (matrix[1][0] * x + matrix[1][1] * y + matrix[1][2]) / w;
+
+    (u, v)
+}
+
+/// Samples an RGBA buffer at a fractional position, blending the four
+/// nearest pixels. Positions are in pixel-centre coordinates, so (0, 0) is
+/// the centre of the top-left pixel. Samples outside the image are
+/// transparent, while those within half a pixel of the border repeat the
+/// edge pixels.
+pub fn sample_bilinear<S: Sample>(data: &[S], width: u32, height: u32, x: f64, y: f64) -> [S; 4] {
+    if x < -0.5 || y < -0.5 || x > width as f64 - 0.5 || y > height as f64 - 0.5 {
+        return [S::default(); 4];
+    }
+
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+
+    let pixel = |px: f64, py: f64| -> [f64; 4] {
+        let px = px.clamp(0.0, (width - 1) as f64) as u32;
+        let py = py.clamp(0.0, (height - 1) as f64) as u32;
+
+        let index = ((py * width + px) * 4) as usize;
+        [
+            data[index].to_f32() as f64,
+            data[index + 1].to_f32() as f64,
+            data[index + 2].to_f32() as f64,
+            data[index + 3].to_f32() as f64,
+        ]
+    };
+
+    let top_left = pixel(x0, y0);
+    let top_right = pixel(x0 + 1.0, y0);
+    let bottom_left = pixel(x0, y0 + 1.0);
+    let bottom_right = pixel(x0 + 1.0, y0 + 1.0);
+
+    let mut result = [S::default(); 4];
+    for i in 0..4 {
+        let top = top_left[i] + (top_right[i] - top_left[i]) * fx;
+        let bottom = bottom_left[i] + (bottom_right[i] - bottom_left[i]) * fx;
+
+        result[i] = S::from_f32((top + (bottom - top) * fy) as f32);
+    }
+
+    result
+}
+
+/// Samples the pixel whose centre is closest to the given position, using
+/// the same coordinates as [`sample_bilinear`].
+pub fn sample_nearest<S: Sample>(data: &[S], width: u32, height: u32, x: f64, y: f64) -> [S; 4] {
+    let x = x.round();
+    let y = y.round();
+
+    if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+        return [S::default(); 4];
+    }
+
+    let index = ((y as u32 * width + x as u32) * 4) as usize;
+    [
+        data[index],
+        data[index + 1],
+        data[index + 2],
+        data[index + 3],
+    ]
+}
+
+/// Produces a new RGBA buffer of the given size by mapping every output
+/// pixel back into the source through `inverse` and sampling it there.
+pub fn warp_projective<S: Sample>(
+    data: &[S],
+    width: u32,
+    height: u32,
+    inverse: &Matrix3,
+    out_width: u32,
+    out_height: u32,
+    interpolation: Interpolation,
+) -> Vec<S> {
+    let mut output = vec![S::default(); out_width as usize * out_height as usize * 4];
+    let sample = match interpolation {
+        Interpolation::Nearest => sample_nearest,
+        Interpolation::Bilinear => sample_bilinear,
+    };
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let (src_x, src_y) = apply(inverse, x as f64 + 0.5, y as f64 + 0.5);
+            let color = sample(data, width, height, src_x - 0.5, src_y - 0.5);
+
+            let index = (y as usize * out_width as usize + x as usize) * 4;
+            output[index..index + 4].copy_from_slice(&color);
+        }
+    }
+
+    output
+}
+
+/// Applies the forward affine `matrix` to an RGBA buffer, keeping its size.
+/// Returns `None` when the matrix is not invertible.
+pub fn warp_affine<S: Sample>(
+    data: &[S],
+    width: u32,
+    height: u32,
+    matrix: &Affine,
+    interpolation: Interpolation,
+) -> Option<Vec<S>> {
+    let [first, second] = invert_affine(matrix)?;
+    let inverse = [first, second, [0.0, 0.0, 1.0]];
+
+    Some(warp_projective(
+        data,
+        width,
+        height,
+        &inverse,
+        width,
+        height,
+        interpolation,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: Matrix3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    #[test]
+    fn homography_maps_target_corners_onto_source_corners() {
+        let target = [(0.0, 0.0), (400.0, 0.0), (400.0, 300.0), (0.0, 300.0)];
+        let corners = [(12.0, 30.0), (380.0, 5.0), (420.0, 310.0), (-8.0, 270.0)];
+        let matrix = homography(target, corners).unwrap();
+
+        for (from, to) in target.iter().zip(corners) {
+            let (x, y) = apply(&matrix, from.0, from.1);
+            assert!((x - to.0).abs() < 1e-6 && (y - to.1).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn collinear_corners_have_no_homography() {
+        let target = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let corners = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 5.0)];
+
+        assert!(homography(target, corners).is_none());
+    }
+
+    #[test]
+    fn identity_warp_keeps_the_image() {
+        let data: Vec<u8> = (0..6 * 4 * 4).map(|i| (i * 11 % 256) as u8).collect();
+
+        for interpolation in [Interpolation::Nearest, Interpolation::Bilinear] {
+            let warped = warp_projective(&data, 6, 4, &IDENTITY, 6, 4, interpolation);
+            assert_eq!(warped, data);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+pub const MIN_ZOOM: f64 = 0.01;
+pub const MAX_ZOOM: f64 = 32.0;
+/// Zoom level from which individual pixel borders are outlined.
+pub const GRID_ZOOM: f64 = 8.0;
+
+/// Maps between canvas pixels and image pixels: a canvas point `c` shows
+/// the image point `(c - pan) / zoom`.
+pub struct Viewport {
+    pub width: u32,
+    pub height: u32,
+    pub zoom: f64,
+    pub pan_x: f64,
+    pub pan_y: f64,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            zoom: 1.0,
+            pan_x: 0.0,
+            pan_y: 0.0,
+        }
+    }
+
+    /// Scales an image of the given size to fill the viewport and centres it.
+    pub fn fit(&mut self, image_width: u32, image_height: u32) {
+        let zoom =
+            (self.width as f64 / image_width as f64).min(self.height as f64 / image_height as f64);
+
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center(image_width, image_height);
+    }
+
+    pub fn center(&mut self, image_width: u32, image_height: u32) {
+        self.pan_x = (self.width as f64 - image_width as f64 * self.zoom) / 2.0;
+        self.pan_y = (self.height as f64 - image_height as f64 * self.zoom) / 2.0;
+    }
+
+    /// Changes the zoom while keeping the image point under the canvas
+    /// position `(x, y)` in place.
+    pub fn zoom_at(&mut self, zoom: f64, x: f64, y: f64) {
+        let (image_x, image_y) = self.canvas_to_image(x, y);
+
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan_x = x - image_x * self.zoom;
+        self.pan_y = y - image_y * self.zoom;
+    }
+
+    pub fn zoom_at_center(&mut self, zoom: f64) {
+        self.zoom_at(zoom, self.width as f64 / 2.0, self.height as f64 / 2.0);
+    }
+
+    pub fn pan_by(&mut self, dx: f64, dy: f64) {
+        self.pan_x += dx;
+        self.pan_y += dy;
+    }
+
+    pub fn canvas_to_image(&self, x: f64, y: f64) -> (f64, f64) {
+        ((x - self.pan_x) / self.zoom, (y - self.pan_y) / self.zoom)
+    }
+
+    /// Range of whole image pixels at least partly visible, clipped to an
+    /// image of the given size, as `(left, top, right, bottom)`.
+    pub fn visible_pixels(&self, image_width: u32, image_height: u32) -> (u32, u32, u32, u32) {
+        let (left, top) = self.canvas_to_image(0.0, 0.0);
+        let (right, bottom) = self.canvas_to_image(self.width as f64, self.height as f64);
+
+        (
+            left.floor().clamp(0.0, image_width as f64) as u32,
+            top.floor().clamp(0.0, image_height as f64) as u32,
+            right.ceil().clamp(0.0, image_width as f64) as u32,
+            bottom.ceil().clamp(0.0, image_height as f64) as u32,
+        )
+    }
+}
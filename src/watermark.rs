@@ -0,0 +1,241 @@
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+
+use crate::buffer::{self, Sample};
+
+/// DejaVu Sans, bundled so text renders the same in every browser.
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Placement {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomRight,
+    /// Repeats the watermark over the whole image in staggered rows.
+    Tiled,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct WatermarkParams {
+    pub text: String,
+    /// Text height in pixels.
+    pub size: f32,
+    pub color: [u8; 3],
+    /// Scale applied to an image watermark.
+    pub scale: f32,
+    pub opacity: f32,
+    /// Clockwise rotation in degrees.
+    pub rotation: f32,
+    pub placement: Placement,
+    /// Distance from the image edges, or between tiles, in pixels.
+    pub margin: f32,
+}
+
+impl Default for WatermarkParams {
+    fn default() -> Self {
+        Self {
+            text: String::from("imgmod"),
+            size: 48.0,
+            color: [255, 255, 255],
+            scale: 1.0,
+            opacity: 0.5,
+            rotation: 0.0,
+            placement: Placement::BottomRight,
+            margin: 16.0,
+        }
+    }
+}
+
+/// Straight-alpha RGBA picture to be stamped onto an image.
+pub struct Stamp {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Stamp {
+    /// Renders text in the bundled font, one line per line of `text`,
+    /// with glyph coverage as alpha. Text too large to render gives an
+    /// empty stamp.
+    pub fn text(text: &str, size: f32, color: [u8; 3]) -> Self {
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let font = font.as_scaled(PxScale::from(size.max(1.0)));
+        let line_height = font.height() + font.line_gap();
+
+        let mut glyphs = Vec::new();
+        let mut width = 0.0f32;
+        let mut lines = 0;
+        for (row, line) in text.lines().enumerate() {
+            let baseline = font.ascent() + row as f32 * line_height;
+            let mut caret = 0.0;
+            let mut previous = None;
+            for c in line.chars() {
+                let id = font.glyph_id(c);
+                if let Some(previous) = previous {
+                    caret += font.kern(previous, id);
+                }
+                glyphs.push(id.with_scale_and_position(font.scale(), point(caret, baseline)));
+                caret += font.h_advance(id);
+                previous = Some(id);
+            }
+
+            width = width.max(caret);
+            lines += 1;
+        }
+
+        let height = match lines {
+            0 => 0.0,
+            _ => (lines - 1) as f32 * line_height + font.height(),
+        };
+        let (width, height) = (width.ceil() as u32, height.ceil() as u32);
+        let mut data = match buffer::rgba_len(width, height) {
+            Some(len) => vec![0u8; len],
+            None => {
+                return Self {
+                    data: Vec::new(),
+                    width: 0,
+                    height: 0,
+                }
+            }
+        };
+
+        for glyph in glyphs {
+            let outline = match font.outline_glyph(glyph) {
+                Some(outline) => outline,
+                None => continue,
+            };
+
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let x = bounds.min.x as i32 + x as i32;
+                let y = bounds.min.y as i32 + y as i32;
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    return;
+                }
+
+                let index = (y as usize * width as usize + x as usize) * 4;
+                let alpha = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                data[index..index + 3].copy_from_slice(&color);
+                data[index + 3] = data[index + 3].max(alpha);
+            });
+        }
+
+        Self {
+            data,
+            width,
+            height,
+        }
+    }
+
+    /// Premultiplied bilinear sample at `(u, v)` in stamp pixels; outside
+    /// the stamp is transparent.
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let (u, v) = (u - 0.5, v - 0.5);
+        let (x0, y0) = (u.floor(), v.floor());
+        let (fx, fy) = (u - x0, v - y0);
+        let mut result = [0.0; 4];
+
+        for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+            for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+                let (x, y) = (x0 as i64 + dx, y0 as i64 + dy);
+                if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+                    continue;
+                }
+
+                let index = ((y as u64 * self.width as u64 + x as u64) * 4) as usize;
+                let pixel = &self.data[index..index + 4];
+                let alpha = pixel[3] as f32 / 255.0;
+                let weight = wx * wy;
+                for c in 0..3 {
+                    result[c] += pixel[c] as f32 * alpha * weight;
+                }
+                result[3] += alpha * weight;
+            }
+        }
+
+        result
+    }
+}
+
+/// Composites `stamp`, scaled and rotated, over an RGBA buffer at every
+/// position the placement calls for.
+pub fn apply<S: Sample>(
+    data: &mut [S],
+    width: u32,
+    height: u32,
+    stamp: &Stamp,
+    scale: f32,
+    params: &WatermarkParams,
+) {
+    if stamp.width == 0 || stamp.height == 0 || scale <= 0.0 {
+        return;
+    }
+
+    let (sin, cos) = params.rotation.to_radians().sin_cos();
+    let (stamp_width, stamp_height) = (stamp.width as f32 * scale, stamp.height as f32 * scale);
+    // Half extents of the rotated stamp's bounding box.
+    let half_width = (stamp_width * cos.abs() + stamp_height * sin.abs()) / 2.0;
+    let half_height = (stamp_width * sin.abs() + stamp_height * cos.abs()) / 2.0;
+
+    let (w, h, margin) = (width as f32, height as f32, params.margin);
+    let (left, right) = (margin + half_width, w - margin - half_width);
+    let (top, bottom) = (margin + half_height, h - margin - half_height);
+    let centers = match params.placement {
+        Placement::TopLeft => vec![(left, top)],
+        Placement::TopRight => vec![(right, top)],
+        Placement::Center => vec![(w / 2.0, h / 2.0)],
+        Placement::BottomLeft => vec![(left, bottom)],
+        Placement::BottomRight => vec![(right, bottom)],
+        Placement::Tiled => {
+            let step_x = half_width * 2.0 + margin.max(0.0);
+            let step_y = half_height * 2.0 + margin.max(0.0);
+            let mut centers = Vec::new();
+            let mut y = half_height;
+            let mut row = 0;
+            while y - half_height < h {
+                let mut x = half_width - if row % 2 == 1 { step_x / 2.0 } else { 0.0 };
+                while x - half_width < w {
+                    centers.push((x, y));
+                    x += step_x;
+                }
+                y += step_y;
+                row += 1;
+            }
+            centers
+        }
+    };
+
+    let opacity = params.opacity.clamp(0.0, 1.0);
+    for (center_x, center_y) in centers {
+        let x_range = (center_x - half_width).floor().max(0.0) as u32
+            ..(center_x + half_width).ceil().clamp(0.0, w) as u32;
+        let y_range = (center_y - half_height).floor().max(0.0) as u32
+            ..(center_y + half_height).ceil().clamp(0.0, h) as u32;
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let dx = x as f32 + 0.5 - center_x;
+                let dy = y as f32 + 0.5 - center_y;
+                let u = (dx * cos + dy * sin) / scale + stamp.width as f32 / 2.0;
+                let v = (dy * cos - dx * sin) / scale + stamp.height as f32 / 2.0;
+                let source = stamp.sample(u, v);
+                let alpha = source[3] * opacity;
+                if alpha <= 0.0 {
+                    continue;
+                }
+
+                let index = ((y * width + x) * 4) as usize;
+                let pixel = &mut data[index..index + 4];
+                let backdrop_alpha = pixel[3].to_unit();
+                let out_alpha = alpha + backdrop_alpha * (1.0 - alpha);
+                for c in 0..3 {
+                    let backdrop = pixel[c].to_unit() * 255.0;
+                    let value = source[c] * opacity + backdrop * backdrop_alpha * (1.0 - alpha);
+                    pixel[c] = S::from_unit((value / out_alpha / 255.0).clamp(0.0, 1.0));
+                }
+                pixel[3] = S::from_unit(out_alpha);
+            }
+        }
+    }
+}